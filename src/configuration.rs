@@ -1,8 +1,16 @@
+use crate::onboarding::KycStatus;
 use serde_aux::field_attributes::deserialize_number_from_string;
+use std::collections::HashMap;
 
 #[derive(serde::Deserialize, Clone, Debug)]
 pub struct ServiceSettings {
     pub application: ApplicationSettings,
+    pub local_database: DatabaseSettings,
+    pub countries: CountrySettings,
+    pub onboarding: OnboardingSettings,
+    pub exchange: ExchangeSettings,
+    pub settlement: SettlementSettings,
+    pub holds: HoldSettings,
 }
 
 #[derive(serde::Deserialize, Clone, Debug)]
@@ -12,6 +20,80 @@ pub struct ApplicationSettings {
     pub host: String,
 }
 
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct DatabaseSettings {
+    /// where the clients and their balances are stored
+    #[serde(default)]
+    pub backend: StorageBackend,
+    /// directory where the write-ahead log and the snapshots (or the SQLite file) are stored
+    pub path: String,
+    /// number of records written to the log before taking a new snapshot
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub snapshot_interval: usize,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// the in memory database saved with a write-ahead log and snapshots
+    #[default]
+    Log,
+    /// an embedded SQLite database
+    Sqlite,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct CountrySettings {
+    /// yaml or json file with the countries of our clients
+    pub path: String,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct OnboardingSettings {
+    /// the minimum age of the clients of the countries that are not in `minimum_age_by_country`
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub minimum_age: u32,
+    /// by the name or the ISO code of the country
+    #[serde(default)]
+    pub minimum_age_by_country: HashMap<String, u32>,
+    /// an older client is surely a mistake in the birth date
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub maximum_age: u32,
+    /// `verified` to skip the manual verification of the clients
+    pub kyc_status_of_new_clients: KycStatus,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct ExchangeSettings {
+    /// yaml or json file with the exchange rates
+    pub rates_path: String,
+    /// seconds between two checks of the rates file
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub reload_interval_seconds: u64,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct SettlementSettings {
+    /// directory where the settlement files are written
+    pub output_directory: String,
+    /// cron expressions (with seconds) of the automatic settlement runs, in the local time
+    #[serde(default)]
+    pub schedules: Vec<String>,
+    /// days in which the automatic settlement runs are skipped
+    #[serde(default)]
+    pub holidays: Vec<chrono::NaiveDate>,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct HoldSettings {
+    /// seconds that a hold reserves the money if nobody captures or voids it
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub ttl_seconds: u64,
+    /// seconds between two checks of the expired holds
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub expiration_interval_seconds: u64,
+}
+
 pub fn get_configuration() -> Result<ServiceSettings, config::ConfigError> {
     let base_path = std::env::current_dir().expect("Failed to determine the current directory");
 
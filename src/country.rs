@@ -0,0 +1,230 @@
+use crate::currency::Currency;
+use crate::document::{DocumentRule, DocumentType};
+use crate::user::{CountryName, CreateUserError};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CountryError {
+    #[error("error reading the countries: {0}")]
+    Load(#[from] config::ConfigError),
+    #[error("{0} is not a valid ISO 3166 code of the country {1}")]
+    InvalidCode(String, String),
+    #[error("the name or code {0} is used by more than one country")]
+    Duplicated(String),
+}
+
+/// a country where we have clients, as it is written in the countries file
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct Country {
+    name: String,
+    /// ISO 3166-1 alpha-2 code
+    alpha2: String,
+    /// ISO 3166-1 alpha-3 code
+    alpha3: String,
+    /// the currency of the first account of the clients
+    currency: Currency,
+    /// the rule that validates every type of document issued by the country
+    #[serde(default)]
+    documents: HashMap<DocumentType, DocumentRule>,
+}
+
+impl Country {
+    /// the name that we store for the clients of the country
+    pub fn name(&self) -> CountryName {
+        CountryName::from_stored(self.name.clone())
+    }
+
+    pub fn alpha2(&self) -> &str {
+        &self.alpha2
+    }
+
+    pub fn alpha3(&self) -> &str {
+        &self.alpha3
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    /// the rule of the documents of `document_type` issued by the country
+    pub fn document_rule(&self, document_type: DocumentType) -> DocumentRule {
+        match (self.documents.get(&document_type), document_type) {
+            (Some(rule), _) => *rule,
+            (None, DocumentType::Passport) => DocumentRule::Passport,
+            (None, _) => DocumentRule::Generic,
+        }
+    }
+
+    fn is_called(&self, s: &str) -> bool {
+        [&self.name, &self.alpha2, &self.alpha3]
+            .into_iter()
+            .any(|name| name.eq_ignore_ascii_case(s))
+    }
+}
+
+/// all the countries where we have clients, loaded from a yaml or json file
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct CountryRegistry {
+    countries: Vec<Country>,
+}
+
+impl CountryRegistry {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CountryError> {
+        config::Config::builder()
+            .add_source(config::File::from(path.as_ref()))
+            .build()?
+            .try_deserialize::<CountryRegistry>()?
+            .validate()
+    }
+
+    fn validate(self) -> Result<Self, CountryError> {
+        let mut names = HashSet::new();
+        for country in &self.countries {
+            for (code, length) in [(&country.alpha2, 2), (&country.alpha3, 3)] {
+                if code.len() != length || !code.chars().all(|c| c.is_ascii_uppercase()) {
+                    return Err(CountryError::InvalidCode(
+                        code.clone(),
+                        country.name.clone(),
+                    ));
+                }
+            }
+            for name in [&country.name, &country.alpha2, &country.alpha3] {
+                if !names.insert(name.to_lowercase()) {
+                    return Err(CountryError::Duplicated(name.clone()));
+                }
+            }
+        }
+        Ok(self)
+    }
+
+    /// the country with the name or ISO code `s`, ignoring the case
+    pub fn parse_and_validate(&self, s: &str) -> Result<&Country, CreateUserError> {
+        let s = s.trim();
+        self.countries
+            .iter()
+            .find(|country| !s.is_empty() && country.is_called(s))
+            .ok_or_else(|| CreateUserError::InvalidCountryName(s.to_string()))
+    }
+
+    pub fn countries(&self) -> &[Country] {
+        &self.countries
+    }
+}
+
+/// a country of the countries file of the repository, to build the users of the tests
+#[cfg(test)]
+pub(crate) fn test_country(name: &str) -> Country {
+    CountryRegistry::load("configuration/countries.yaml")
+        .expect("error loading the countries")
+        .parse_and_validate(name)
+        .expect("unknown country")
+        .clone()
+}
+
+//-------------------------------------------------------------------------
+//                        unit tests
+//-------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::country::{CountryError, CountryRegistry, test_country};
+    use crate::currency::Currency;
+    use crate::document::{DocumentRule, DocumentType};
+    use claims::{assert_err, assert_matches, assert_ok};
+
+    fn write_countries(content: &str) -> std::path::PathBuf {
+        let directory = std::env::temp_dir().join(format!("mini-payment-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("countries.yaml");
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn countries_are_found_by_name_or_code() {
+        let registry = CountryRegistry::load("configuration/countries.yaml").unwrap();
+        for s in ["Argentina", "argentina", "AR", "ar", "ARG", " Argentina "] {
+            let country = assert_ok!(registry.parse_and_validate(s));
+            assert_eq!(country.name().inner_ref(), "Argentina");
+        }
+    }
+
+    #[test]
+    fn blank_and_unknown_countries_are_rejected() {
+        let registry = CountryRegistry::load("configuration/countries.yaml").unwrap();
+        for s in ["", "   ", "Narnia", "ZZ", "Argentin"] {
+            assert_err!(registry.parse_and_validate(s));
+        }
+    }
+
+    #[test]
+    fn every_configured_country_has_a_currency_and_rules() {
+        let registry = CountryRegistry::load("configuration/countries.yaml").unwrap();
+        assert_eq!(registry.countries().len(), 7);
+        assert_eq!(test_country("Ecuador").currency(), Currency::Usd);
+        for country in registry.countries() {
+            assert_ne!(
+                country.document_rule(DocumentType::Dni),
+                DocumentRule::Generic
+            );
+        }
+    }
+
+    #[test]
+    fn the_rule_depends_on_the_type_of_document() {
+        let argentina = test_country("Argentina");
+        let chile = test_country("Chile");
+        assert_eq!(
+            argentina.document_rule(DocumentType::TaxId),
+            DocumentRule::ArgentinaCuit
+        );
+        assert_eq!(
+            chile.document_rule(DocumentType::TaxId),
+            DocumentRule::ChileRut
+        );
+        assert_eq!(
+            chile.document_rule(DocumentType::Passport),
+            DocumentRule::Passport
+        );
+    }
+
+    #[test]
+    fn the_types_without_a_rule_accept_any_document() {
+        let path = write_countries(
+            "countries:\n  - {name: Bolivia, alpha2: BO, alpha3: BOL, currency: USD}\n",
+        );
+        let registry = CountryRegistry::load(path).unwrap();
+        let bolivia = registry.parse_and_validate("BOL").unwrap();
+        assert_eq!(
+            bolivia.document_rule(DocumentType::Dni),
+            DocumentRule::Generic
+        );
+        assert_eq!(
+            bolivia.document_rule(DocumentType::Passport),
+            DocumentRule::Passport
+        );
+    }
+
+    #[test]
+    fn invalid_files_are_rejected() {
+        let path = write_countries(
+            "countries:\n  - {name: Bolivia, alpha2: BOL, alpha3: BOL, currency: USD}\n",
+        );
+        assert_matches!(
+            CountryRegistry::load(path),
+            Err(CountryError::InvalidCode(_, _))
+        );
+        let path = write_countries(
+            "countries:\n  - {name: Bolivia, alpha2: BO, alpha3: BOL, currency: USD}\n  - {name: Bolivia, alpha2: BX, alpha3: BXX, currency: USD}\n",
+        );
+        assert_matches!(
+            CountryRegistry::load(path),
+            Err(CountryError::Duplicated(_))
+        );
+        let path = write_countries(
+            "countries:\n  - {name: Bolivia, alpha2: BO, alpha3: BOL, currency: BOB}\n",
+        );
+        assert_matches!(CountryRegistry::load(path), Err(CountryError::Load(_)));
+    }
+}
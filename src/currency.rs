@@ -0,0 +1,115 @@
+use rust_decimal::{Decimal, RoundingStrategy};
+use std::fmt;
+use std::str::FromStr;
+
+/// ISO 4217 code of the currencies that our clients can hold
+#[derive(
+    Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize, serde::Serialize,
+)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Currency {
+    /// argentine peso
+    Ars,
+    /// brazilian real
+    Brl,
+    /// chilean peso
+    Clp,
+    /// paraguayan guarani
+    Pyg,
+    /// peruvian sol
+    Pen,
+    /// us dollar (the currency of Ecuador)
+    Usd,
+    /// uruguayan peso
+    Uyu,
+}
+
+impl Currency {
+    pub const ALL: [Currency; 7] = [
+        Self::Ars,
+        Self::Brl,
+        Self::Clp,
+        Self::Pyg,
+        Self::Pen,
+        Self::Usd,
+        Self::Uyu,
+    ];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Ars => "ARS",
+            Self::Brl => "BRL",
+            Self::Clp => "CLP",
+            Self::Pyg => "PYG",
+            Self::Pen => "PEN",
+            Self::Usd => "USD",
+            Self::Uyu => "UYU",
+        }
+    }
+
+    /// number of digits after the decimal separator
+    pub fn minor_units(self) -> u32 {
+        match self {
+            Self::Clp | Self::Pyg => 0,
+            _ => 2,
+        }
+    }
+
+    /// round `amount` to the minor units of the currency, the fractions are always dropped so we
+    /// never give more money than we have
+    pub fn round(self, amount: Decimal) -> Decimal {
+        amount.round_dp_with_strategy(self.minor_units(), RoundingStrategy::ToZero)
+    }
+
+    /// `true` if `amount` don't have more decimals than the minor units of the currency
+    pub fn is_representable(self, amount: Decimal) -> bool {
+        amount.normalize().scale() <= self.minor_units()
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl FromStr for Currency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|currency| currency.code().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| format!("{s} is not a supported currency"))
+    }
+}
+
+//-------------------------------------------------------------------------
+//                        unit tests
+//-------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::currency::Currency;
+    use claims::{assert_err, assert_ok_eq};
+    use rust_decimal::dec;
+
+    #[test]
+    fn codes_are_parsed_ignoring_the_case() {
+        assert_ok_eq!("ARS".parse::<Currency>(), Currency::Ars);
+        assert_ok_eq!("clp".parse::<Currency>(), Currency::Clp);
+        assert_err!("EUR".parse::<Currency>());
+    }
+
+    #[test]
+    fn amounts_are_rounded_to_the_minor_units() {
+        assert_eq!(Currency::Ars.round(dec!(10.129)), dec!(10.12));
+        assert_eq!(Currency::Clp.round(dec!(999.99)), dec!(999));
+        assert!(Currency::Clp.is_representable(dec!(10.00)));
+        assert!(!Currency::Clp.is_representable(dec!(10.5)));
+    }
+
+    #[test]
+    fn currencies_are_serialized_with_the_iso_code() {
+        assert_eq!(serde_json::to_string(&Currency::Pyg).unwrap(), "\"PYG\"");
+    }
+}
@@ -0,0 +1,372 @@
+use std::str::FromStr;
+
+/// the kind of identity document of a client
+#[derive(
+    Debug, Copy, Clone, Default, Hash, PartialEq, Eq, serde::Deserialize, serde::Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentType {
+    /// the national identity document of the issuing country
+    #[default]
+    Dni,
+    Passport,
+    /// the number that the tax agency of the issuing country gives to the person
+    TaxId,
+}
+
+impl DocumentType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Dni => "dni",
+            Self::Passport => "passport",
+            Self::TaxId => "tax_id",
+        }
+    }
+}
+
+impl FromStr for DocumentType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dni" => Ok(Self::Dni),
+            "passport" => Ok(Self::Passport),
+            "tax_id" => Ok(Self::TaxId),
+            _ => Err(format!("unknown document type: {s}")),
+        }
+    }
+}
+
+/// How the identity documents of a country are validated. The numbers are checked after
+/// `normalize`, so the clients can send them with or without the usual dots and dashes
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentRule {
+    /// DNI (7 or 8 digits, without check digit) or CUIT/CUIL (11 digits with a mod 11 check digit)
+    ArgentinaDni,
+    /// CUIT/CUIL: 11 digits with a mod 11 check digit
+    ArgentinaCuit,
+    /// CPF: 11 digits, the last two are check digits
+    BrazilCpf,
+    /// RUT: up to 8 digits and a mod 11 verifier that can be a `K`
+    ChileRut,
+    /// cedula: 10 digits with the province in the first two and a mod 10 check digit
+    EcuadorCedula,
+    /// RUC of a person: the cedula and `001`
+    EcuadorRuc,
+    /// cedula de identidad: 5 to 8 digits, it has no check digit
+    ParaguayCi,
+    /// RUC: the cedula and a mod 11 check digit
+    ParaguayRuc,
+    /// DNI: 8 digits
+    PeruDni,
+    /// RUC: 11 digits with a mod 11 check digit
+    PeruRuc,
+    /// cedula de identidad: up to 7 digits and a check digit
+    UruguayCi,
+    /// RUT: 12 digits with a mod 11 check digit
+    UruguayRut,
+    /// the number of a passport of any country: 6 to 9 letters or digits
+    Passport,
+    /// any alphanumeric document, for the countries without a rule
+    Generic,
+}
+
+impl DocumentRule {
+    /// `number` must be already normalized
+    pub fn is_valid(self, number: &str) -> bool {
+        match self {
+            Self::ArgentinaDni => match number.len() {
+                7 | 8 => is_numeric(number),
+                11 => is_valid_cuit(number),
+                _ => false,
+            },
+            Self::ArgentinaCuit => is_valid_cuit(number),
+            Self::BrazilCpf => is_valid_cpf(number),
+            Self::ChileRut => is_valid_rut(number),
+            Self::EcuadorCedula => is_valid_ecuador_cedula(number),
+            Self::EcuadorRuc => number
+                .strip_suffix("001")
+                .is_some_and(is_valid_ecuador_cedula),
+            Self::ParaguayCi => (5..=8).contains(&number.len()) && is_numeric(number),
+            Self::ParaguayRuc => is_valid_paraguay_ruc(number),
+            Self::PeruDni => number.len() == 8 && is_numeric(number),
+            Self::PeruRuc => is_valid_peru_ruc(number),
+            Self::UruguayCi => is_valid_uruguay_ci(number),
+            Self::UruguayRut => is_valid_uruguay_rut(number),
+            Self::Passport => {
+                (6..=9).contains(&number.len()) && number.chars().all(|c| c.is_ascii_alphanumeric())
+            }
+            Self::Generic => {
+                (4..=20).contains(&number.len())
+                    && number.chars().all(|c| c.is_ascii_alphanumeric())
+            }
+        }
+    }
+}
+
+/// drop the separators that people use when they write a document (`20-12345678-6`,
+/// `12.345.678-5`) and use upper case letters
+pub fn normalize(raw: &str) -> String {
+    raw.chars()
+        .filter(|c| !matches!(c, '.' | '-' | ' '))
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+fn is_numeric(number: &str) -> bool {
+    !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// the digits of a number that was checked with `is_numeric`
+fn digits(number: &str) -> Vec<u32> {
+    number.bytes().map(|b| u32::from(b - b'0')).collect()
+}
+
+/// the check digit of the CUIT is `11 - (sum % 11)` with the weights 5432765432, a `10` is never
+/// assigned
+fn is_valid_cuit(number: &str) -> bool {
+    if number.len() != 11 || !is_numeric(number) {
+        return false;
+    }
+    let digits = digits(number);
+    let sum: u32 = digits
+        .iter()
+        .zip([5, 4, 3, 2, 7, 6, 5, 4, 3, 2])
+        .map(|(digit, weight)| digit * weight)
+        .sum();
+    match 11 - sum % 11 {
+        11 => digits[10] == 0,
+        10 => false,
+        check => digits[10] == check,
+    }
+}
+
+/// the two check digits of the CPF, the numbers with all the digits equal are not valid
+fn is_valid_cpf(number: &str) -> bool {
+    if number.len() != 11 || !is_numeric(number) {
+        return false;
+    }
+    let digits = digits(number);
+    if digits.iter().all(|digit| *digit == digits[0]) {
+        return false;
+    }
+    let check = |len: usize| {
+        let sum: u32 = digits[..len]
+            .iter()
+            .zip((2..=len as u32 + 1).rev())
+            .map(|(digit, weight)| digit * weight)
+            .sum();
+        (sum * 10) % 11 % 10
+    };
+    check(9) == digits[9] && check(10) == digits[10]
+}
+
+/// the verifier of the RUT: the digits from the right multiplied by 2, 3, 4, 5, 6, 7, 2, 3, ...
+/// and `11 - (sum % 11)`, where `11` is a `0` and `10` is a `K`
+fn is_valid_rut(number: &str) -> bool {
+    let Some((body, verifier)) = number.split_at_checked(number.len().saturating_sub(1)) else {
+        return false;
+    };
+    if !(1..=8).contains(&body.len()) || !is_numeric(body) {
+        return false;
+    }
+    let sum: u32 = digits(body)
+        .iter()
+        .rev()
+        .zip([2, 3, 4, 5, 6, 7].into_iter().cycle())
+        .map(|(digit, weight)| digit * weight)
+        .sum();
+    let expected = match 11 - sum % 11 {
+        11 => "0".to_string(),
+        10 => "K".to_string(),
+        check => check.to_string(),
+    };
+    verifier == expected
+}
+
+/// the first two digits are the province (1 to 24, or 30 for the ones born abroad), the third is
+/// less than 6 for the persons and the last one is a mod 10 check digit with the weights 212121212
+fn is_valid_ecuador_cedula(number: &str) -> bool {
+    if number.len() != 10 || !is_numeric(number) {
+        return false;
+    }
+    let digits = digits(number);
+    let province = digits[0] * 10 + digits[1];
+    if !((1..=24).contains(&province) || province == 30) || digits[2] >= 6 {
+        return false;
+    }
+    let sum: u32 = digits[..9]
+        .iter()
+        .zip([2, 1].into_iter().cycle())
+        .map(|(digit, weight)| {
+            let product = digit * weight;
+            if product > 9 { product - 9 } else { product }
+        })
+        .sum();
+    (10 - sum % 10) % 10 == digits[9]
+}
+
+/// the body is padded to 7 digits and the check digit is `(10 - sum % 10) % 10` with the weights
+/// 2987634
+fn is_valid_uruguay_ci(number: &str) -> bool {
+    if !(2..=8).contains(&number.len()) || !is_numeric(number) {
+        return false;
+    }
+    let digits = digits(number);
+    let (body, check) = digits.split_at(digits.len() - 1);
+    let padding = 7 - body.len();
+    let sum: u32 = [2, 9, 8, 7, 6, 3, 4]
+        .iter()
+        .skip(padding)
+        .zip(body)
+        .map(|(weight, digit)| weight * digit)
+        .sum();
+    (10 - sum % 10) % 10 == check[0]
+}
+
+/// the check digit of the RUC is computed over the cedula with the weights 2, 3, 4, ... from the
+/// right, it is `11 - (sum % 11)` or `0` when the rest is 0 or 1
+fn is_valid_paraguay_ruc(number: &str) -> bool {
+    if !(6..=9).contains(&number.len()) || !is_numeric(number) {
+        return false;
+    }
+    let digits = digits(number);
+    let (body, check) = digits.split_at(digits.len() - 1);
+    let sum: u32 = body
+        .iter()
+        .rev()
+        .zip(2..)
+        .map(|(digit, weight)| digit * weight)
+        .sum();
+    let expected = match sum % 11 {
+        0 | 1 => 0,
+        rest => 11 - rest,
+    };
+    expected == check[0]
+}
+
+/// `11 - (sum % 11)` with the weights 5432765432, where `10` is a `0` and `11` is a `1`
+fn is_valid_peru_ruc(number: &str) -> bool {
+    if number.len() != 11 || !is_numeric(number) {
+        return false;
+    }
+    let digits = digits(number);
+    let sum: u32 = digits
+        .iter()
+        .zip([5, 4, 3, 2, 7, 6, 5, 4, 3, 2])
+        .map(|(digit, weight)| digit * weight)
+        .sum();
+    let expected = match 11 - sum % 11 {
+        10 => 0,
+        11 => 1,
+        check => check,
+    };
+    expected == digits[10]
+}
+
+/// `11 - (sum % 11)` with the weights 43298765432, a `10` is never assigned and `11` is a `0`
+fn is_valid_uruguay_rut(number: &str) -> bool {
+    if number.len() != 12 || !is_numeric(number) {
+        return false;
+    }
+    let digits = digits(number);
+    let sum: u32 = digits
+        .iter()
+        .zip([4, 3, 2, 9, 8, 7, 6, 5, 4, 3, 2])
+        .map(|(digit, weight)| digit * weight)
+        .sum();
+    match 11 - sum % 11 {
+        11 => digits[11] == 0,
+        10 => false,
+        check => digits[11] == check,
+    }
+}
+
+//-------------------------------------------------------------------------
+//                        unit tests
+//-------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::document::{DocumentRule, normalize};
+
+    fn is_valid(rule: DocumentRule, raw: &str) -> bool {
+        rule.is_valid(&normalize(raw))
+    }
+
+    #[test]
+    fn argentine_dni_and_cuit() {
+        assert!(is_valid(DocumentRule::ArgentinaDni, "29.653.164"));
+        assert!(is_valid(DocumentRule::ArgentinaDni, "1234567"));
+        assert!(is_valid(DocumentRule::ArgentinaDni, "20-12345678-6"));
+        assert!(!is_valid(DocumentRule::ArgentinaDni, "20-12345678-5"));
+        assert!(!is_valid(DocumentRule::ArgentinaDni, "123456"));
+        assert!(!is_valid(DocumentRule::ArgentinaDni, "2965316A"));
+    }
+
+    #[test]
+    fn brazilian_cpf() {
+        assert!(is_valid(DocumentRule::BrazilCpf, "529.982.247-25"));
+        assert!(!is_valid(DocumentRule::BrazilCpf, "529.982.247-26"));
+        assert!(!is_valid(DocumentRule::BrazilCpf, "111.111.111-11"));
+        assert!(!is_valid(DocumentRule::BrazilCpf, "5299822472"));
+    }
+
+    #[test]
+    fn chilean_rut() {
+        assert!(is_valid(DocumentRule::ChileRut, "12.345.678-5"));
+        assert!(is_valid(DocumentRule::ChileRut, "10.000.013-k"));
+        assert!(!is_valid(DocumentRule::ChileRut, "12.345.678-4"));
+        assert!(!is_valid(DocumentRule::ChileRut, "K"));
+        assert!(!is_valid(DocumentRule::ChileRut, ""));
+    }
+
+    #[test]
+    fn ecuadorian_cedula() {
+        assert!(is_valid(DocumentRule::EcuadorCedula, "1710034065"));
+        assert!(!is_valid(DocumentRule::EcuadorCedula, "1710034066"));
+        // there is no province 25
+        assert!(!is_valid(DocumentRule::EcuadorCedula, "2510034065"));
+    }
+
+    #[test]
+    fn uruguayan_ci() {
+        assert!(is_valid(DocumentRule::UruguayCi, "1.234.567-2"));
+        assert!(!is_valid(DocumentRule::UruguayCi, "1.234.567-3"));
+    }
+
+    #[test]
+    fn paraguayan_and_peruvian_documents_only_check_the_length() {
+        assert!(is_valid(DocumentRule::ParaguayCi, "1234567"));
+        assert!(!is_valid(DocumentRule::ParaguayCi, "1234"));
+        assert!(is_valid(DocumentRule::PeruDni, "45678912"));
+        assert!(!is_valid(DocumentRule::PeruDni, "4567891"));
+    }
+
+    #[test]
+    fn tax_ids() {
+        assert!(is_valid(DocumentRule::ArgentinaCuit, "20-12345678-6"));
+        assert!(!is_valid(DocumentRule::ArgentinaCuit, "12345678"));
+        assert!(is_valid(DocumentRule::EcuadorRuc, "1710034065001"));
+        assert!(!is_valid(DocumentRule::EcuadorRuc, "1710034065"));
+        assert!(is_valid(DocumentRule::ParaguayRuc, "1234567-9"));
+        assert!(!is_valid(DocumentRule::ParaguayRuc, "1234567-8"));
+        assert!(is_valid(DocumentRule::PeruRuc, "20100047218"));
+        assert!(!is_valid(DocumentRule::PeruRuc, "20100047219"));
+        assert!(is_valid(DocumentRule::UruguayRut, "211003420017"));
+        assert!(!is_valid(DocumentRule::UruguayRut, "211003420018"));
+    }
+
+    #[test]
+    fn passports_have_letters_and_digits() {
+        assert!(is_valid(DocumentRule::Passport, "AAB123456"));
+        assert!(!is_valid(DocumentRule::Passport, "AAB1234567"));
+    }
+
+    #[test]
+    fn generic_documents_can_have_letters() {
+        assert!(is_valid(DocumentRule::Generic, "ab123456"));
+        assert!(!is_valid(DocumentRule::Generic, "ab1"));
+        assert!(!is_valid(DocumentRule::Generic, "ab12/3456"));
+    }
+}
@@ -0,0 +1,209 @@
+use crate::currency::Currency;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ExchangeError {
+    #[error("error reading the exchange rates: {0}")]
+    Load(#[from] config::ConfigError),
+    #[error("error reading the exchange rates: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("the exchange rate of {0} must be positive")]
+    InvalidRate(Currency),
+}
+
+/// the rates of every currency against a `base` currency as they are written in the rates file
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ExchangeRates {
+    base: Currency,
+    rates: HashMap<Currency, Decimal>,
+}
+
+impl ExchangeRates {
+    /// number of decimals of the rates that we apply
+    const RATE_SCALE: u32 = 8;
+
+    pub fn parse_and_validate(mut self) -> Result<Self, ExchangeError> {
+        self.rates.entry(self.base).or_insert(Decimal::ONE);
+        match self.rates.iter().find(|(_, rate)| **rate <= Decimal::ZERO) {
+            Some((currency, _)) => Err(ExchangeError::InvalidRate(*currency)),
+            None => Ok(self),
+        }
+    }
+
+    /// how many units of `to` we give for one unit of `from`, `None` if we don't know the rate of
+    /// some of them
+    pub fn rate(&self, from: Currency, to: Currency) -> Option<Decimal> {
+        if from == to {
+            return Some(Decimal::ONE);
+        }
+        let from_rate = self.rates.get(&from)?;
+        let to_rate = self.rates.get(&to)?;
+        Some((to_rate / from_rate).round_dp(Self::RATE_SCALE))
+    }
+}
+
+/// the exchange rates loaded from a yaml or json file, the file is read again when it changes
+#[derive(Debug)]
+pub struct ExchangeRateTable {
+    path: PathBuf,
+    state: RwLock<(ExchangeRates, SystemTime)>,
+}
+
+impl ExchangeRateTable {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ExchangeError> {
+        let path = path.as_ref().to_path_buf();
+        let modified = std::fs::metadata(&path)?.modified()?;
+        let rates = read_rates(&path)?;
+        Ok(Self {
+            path,
+            state: RwLock::new((rates, modified)),
+        })
+    }
+
+    pub fn rate(&self, from: Currency, to: Currency) -> Option<Decimal> {
+        self.state
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .0
+            .rate(from, to)
+    }
+
+    /// read the file again if it was modified since the last load, return if the rates changed.
+    /// If the new file is not valid we keep using the old rates
+    pub fn reload_if_changed(&self) -> Result<bool, ExchangeError> {
+        let modified = std::fs::metadata(&self.path)?.modified()?;
+        if modified == self.state.read().unwrap_or_else(PoisonError::into_inner).1 {
+            return Ok(false);
+        }
+        let rates = read_rates(&self.path)?;
+        // the rates are replaced at once, a panic while the lock was taken can not leave them half
+        // written so we keep using them
+        *self.state.write().unwrap_or_else(PoisonError::into_inner) = (rates, modified);
+        Ok(true)
+    }
+}
+
+fn read_rates(path: &Path) -> Result<ExchangeRates, ExchangeError> {
+    config::Config::builder()
+        .add_source(config::File::from(path))
+        .build()?
+        .try_deserialize::<ExchangeRates>()?
+        .parse_and_validate()
+}
+
+/// check the rates file every `interval` and reload it when it changes
+pub fn spawn_rates_reloader(
+    table: Arc<ExchangeRateTable>,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            match table.reload_if_changed() {
+                Ok(true) => log::info!("exchange rates reloaded from {:?}", table.path),
+                Ok(false) => {}
+                Err(e) => log::error!("failed to reload the exchange rates: {e}"),
+            }
+        }
+    })
+}
+
+//-------------------------------------------------------------------------
+//                        unit tests
+//-------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::currency::Currency;
+    use crate::exchange::ExchangeRateTable;
+    use claims::{assert_err, assert_ok, assert_some_eq};
+    use rust_decimal::dec;
+
+    fn write_rates(content: &str) -> std::path::PathBuf {
+        let directory = std::env::temp_dir().join(format!("mini-payment-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("rates.yaml");
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn rates_are_crossed_through_the_base_currency() {
+        let path = write_rates("base: USD\nrates:\n  ARS: \"1000\"\n  BRL: \"5\"\n");
+        let table = ExchangeRateTable::load(path).expect("error loading the rates");
+
+        assert_some_eq!(table.rate(Currency::Usd, Currency::Ars), dec!(1000));
+        assert_some_eq!(table.rate(Currency::Brl, Currency::Ars), dec!(200));
+        assert_some_eq!(table.rate(Currency::Ars, Currency::Brl), dec!(0.005));
+        assert_eq!(table.rate(Currency::Ars, Currency::Clp), None);
+    }
+
+    #[test]
+    fn json_files_are_supported() {
+        let directory = write_rates("").parent().unwrap().to_path_buf();
+        let path = directory.join("rates.json");
+        std::fs::write(&path, r#"{"base": "USD", "rates": {"CLP": "900"}}"#).unwrap();
+
+        let table = ExchangeRateTable::load(path).expect("error loading the rates");
+        assert_some_eq!(table.rate(Currency::Clp, Currency::Usd), dec!(0.00111111));
+    }
+
+    #[test]
+    fn non_positive_rates_are_rejected() {
+        let path = write_rates("base: USD\nrates:\n  ARS: \"0\"\n");
+        assert_err!(ExchangeRateTable::load(path));
+    }
+
+    #[test]
+    fn the_rates_are_reloaded_when_the_file_changes() {
+        let path = write_rates("base: USD\nrates:\n  ARS: \"1000\"\n");
+        let table = ExchangeRateTable::load(&path).expect("error loading the rates");
+
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        std::fs::write(&path, "base: USD\nrates:\n  ARS: \"1100\"\n").unwrap();
+        // NOTE(elsuizo: 2025-08-02): force a different modification time, some filesystems have
+        // a coarse resolution
+        file.set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(1))
+            .unwrap();
+
+        assert_ok!(table.reload_if_changed());
+        assert_some_eq!(table.rate(Currency::Usd, Currency::Ars), dec!(1100));
+    }
+
+    #[test]
+    fn an_invalid_file_keeps_the_old_rates() {
+        let path = write_rates("base: USD\nrates:\n  ARS: \"1000\"\n");
+        let table = ExchangeRateTable::load(&path).expect("error loading the rates");
+
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        std::fs::write(&path, "base: USD\nrates:\n  ARS: \"-1\"\n").unwrap();
+        file.set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(1))
+            .unwrap();
+
+        assert_err!(table.reload_if_changed());
+        assert_some_eq!(table.rate(Currency::Usd, Currency::Ars), dec!(1000));
+    }
+
+    #[test]
+    fn a_poisoned_lock_keeps_the_rates() {
+        let path = write_rates("base: USD\nrates:\n  ARS: \"1000\"\n");
+        let table = ExchangeRateTable::load(&path).expect("error loading the rates");
+
+        std::thread::scope(|scope| {
+            let poisoner = scope.spawn(|| {
+                let _state = table.state.write().unwrap();
+                panic!("poison the lock");
+            });
+            assert_err!(poisoner.join());
+        });
+        assert!(table.state.is_poisoned());
+
+        assert_some_eq!(table.rate(Currency::Usd, Currency::Ars), dec!(1000));
+        assert_ok!(table.reload_if_changed());
+    }
+}
@@ -0,0 +1,279 @@
+use crate::currency::Currency;
+use crate::storage::SharedStorage;
+use crate::user::DatabaseError;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HoldStatus {
+    /// the money is reserved, it can be captured or voided
+    Active,
+    Captured,
+    Voided,
+    /// nobody captured the hold before its `expires_at`
+    Expired,
+}
+
+impl HoldStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::Captured => "captured",
+            Self::Voided => "voided",
+            Self::Expired => "expired",
+        }
+    }
+}
+
+impl fmt::Display for HoldStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for HoldStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "active" => Ok(Self::Active),
+            "captured" => Ok(Self::Captured),
+            "voided" => Ok(Self::Voided),
+            "expired" => Ok(Self::Expired),
+            _ => Err(format!("unknown hold status: {s}")),
+        }
+    }
+}
+
+/// An authorization over an account: the `amount` is still in the balance of the client but he
+/// can not use it until the hold is captured (the money is debited), voided or it expires. A
+/// capture can take less than the `amount`, the rest is released
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct Hold {
+    pub id: Uuid,
+    pub client_id: Uuid,
+    pub currency: Currency,
+    /// the amount reserved when the hold was placed
+    pub amount: Decimal,
+    /// the amount that was debited, only in the captured holds
+    pub captured_amount: Option<Decimal>,
+    pub status: HoldStatus,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// when the hold was captured, voided or expired
+    pub closed_at: Option<DateTime<Utc>>,
+}
+
+impl Hold {
+    /// a new active hold, the `amount` must be positive and fit in the minor units of `currency`
+    pub fn new(
+        client_id: Uuid,
+        currency: Currency,
+        amount: Decimal,
+        created_at: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Self, DatabaseError> {
+        if amount <= Decimal::ZERO || !currency.is_representable(amount) {
+            return Err(DatabaseError::InvalidAmount(amount));
+        }
+        Ok(Self {
+            id: Uuid::new_v4(),
+            client_id,
+            currency,
+            amount,
+            captured_amount: None,
+            status: HoldStatus::Active,
+            created_at,
+            expires_at,
+            closed_at: None,
+        })
+    }
+
+    // NOTE(elsuizo: 2025-10-18): the expired holds are marked by a background task, until then
+    // they are still `Active` in the storage but they don't hold anything
+    /// the status of the hold at the time `now`
+    pub fn status_at(&self, now: DateTime<Utc>) -> HoldStatus {
+        if self.is_expired_at(now) {
+            HoldStatus::Expired
+        } else {
+            self.status
+        }
+    }
+
+    /// `true` if the money of the hold is still reserved at the time `now`
+    pub fn is_outstanding(&self, now: DateTime<Utc>) -> bool {
+        self.status_at(now) == HoldStatus::Active
+    }
+
+    fn check_active(&self, now: DateTime<Utc>) -> Result<(), DatabaseError> {
+        match self.status_at(now) {
+            HoldStatus::Active => Ok(()),
+            status => Err(DatabaseError::HoldNotActive(self.id, status)),
+        }
+    }
+
+    /// capture `amount` (all the hold when is `None`) at the time `at` and return the amount
+    /// that must be debited from the account
+    pub fn capture(
+        &mut self,
+        amount: Option<Decimal>,
+        at: DateTime<Utc>,
+    ) -> Result<Decimal, DatabaseError> {
+        self.check_active(at)?;
+        let amount = amount.unwrap_or(self.amount);
+        if amount <= Decimal::ZERO
+            || amount > self.amount
+            || !self.currency.is_representable(amount)
+        {
+            return Err(DatabaseError::InvalidAmount(amount));
+        }
+        self.captured_amount = Some(amount);
+        self.status = HoldStatus::Captured;
+        self.closed_at = Some(at);
+        Ok(amount)
+    }
+
+    /// release all the money of the hold without moving it
+    pub fn void(&mut self, at: DateTime<Utc>) -> Result<(), DatabaseError> {
+        self.check_active(at)?;
+        self.status = HoldStatus::Voided;
+        self.closed_at = Some(at);
+        Ok(())
+    }
+
+    /// `true` if the hold is still marked as active but it expired before `now`
+    pub fn is_expired_at(&self, now: DateTime<Utc>) -> bool {
+        self.status == HoldStatus::Active && self.expires_at <= now
+    }
+
+    /// mark the hold as expired if `is_expired_at(now)`, return `false` if nothing changed
+    pub fn expire(&mut self, now: DateTime<Utc>) -> bool {
+        if !self.is_expired_at(now) {
+            return false;
+        }
+        self.status = HoldStatus::Expired;
+        self.closed_at = Some(self.expires_at);
+        true
+    }
+}
+
+/// the money that the `holds` reserve at the time `now` by currency
+pub fn held_amounts<'a>(
+    holds: impl IntoIterator<Item = &'a Hold>,
+    now: DateTime<Utc>,
+) -> BTreeMap<Currency, Decimal> {
+    let mut held = BTreeMap::new();
+    for hold in holds.into_iter().filter(|hold| hold.is_outstanding(now)) {
+        *held.entry(hold.currency).or_default() += hold.amount;
+    }
+    held
+}
+
+/// mark the expired holds every `interval`
+pub fn spawn_hold_expirer(
+    storage: SharedStorage,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            let storage = Arc::clone(&storage);
+            let expired = tokio::task::spawn_blocking(move || storage.expire_holds(Utc::now()));
+            match expired.await {
+                Ok(Ok(0)) => {}
+                Ok(Ok(count)) => log::info!("{count} holds expired"),
+                Ok(Err(e)) => log::error!("failed to expire the holds: {e}"),
+                Err(e) => log::error!("the expiration of the holds panicked: {e}"),
+            }
+        }
+    })
+}
+
+//-------------------------------------------------------------------------
+//                        unit tests
+//-------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::currency::Currency;
+    use crate::hold::{Hold, HoldStatus, held_amounts};
+    use crate::user::DatabaseError;
+    use chrono::{Duration, Utc};
+    use claims::{assert_err, assert_ok};
+    use rust_decimal::dec;
+    use uuid::Uuid;
+
+    fn hold(amount: rust_decimal::Decimal) -> Hold {
+        let now = Utc::now();
+        Hold::new(
+            Uuid::new_v4(),
+            Currency::Ars,
+            amount,
+            now,
+            now + Duration::minutes(10),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn only_positive_amounts_can_be_held() {
+        let now = Utc::now();
+        for amount in [dec!(0), dec!(-1), dec!(0.001)] {
+            assert_err!(Hold::new(Uuid::new_v4(), Currency::Ars, amount, now, now));
+        }
+    }
+
+    #[test]
+    fn a_capture_can_take_less_than_the_hold() {
+        let mut hold = hold(dec!(10));
+        assert_err!(hold.clone().capture(Some(dec!(10.01)), Utc::now()));
+        assert_err!(hold.clone().capture(Some(dec!(0)), Utc::now()));
+        assert_eq!(hold.clone().capture(None, Utc::now()).unwrap(), dec!(10));
+
+        assert_eq!(hold.capture(Some(dec!(4)), Utc::now()).unwrap(), dec!(4));
+        assert_eq!(hold.status, HoldStatus::Captured);
+        assert_eq!(hold.captured_amount, Some(dec!(4)));
+        assert!(!hold.is_outstanding(Utc::now()));
+        assert!(matches!(
+            hold.void(Utc::now()),
+            Err(DatabaseError::HoldNotActive(_, HoldStatus::Captured))
+        ));
+    }
+
+    #[test]
+    fn the_expired_holds_can_not_be_used() {
+        let mut hold = hold(dec!(10));
+        let later = hold.expires_at;
+        assert!(hold.is_outstanding(Utc::now()));
+        assert_eq!(hold.status_at(later), HoldStatus::Expired);
+        assert!(matches!(
+            hold.clone().capture(None, later),
+            Err(DatabaseError::HoldNotActive(_, HoldStatus::Expired))
+        ));
+
+        assert!(!hold.expire(Utc::now()));
+        assert!(hold.expire(later));
+        assert_eq!(hold.closed_at, Some(later));
+        assert!(!hold.expire(later));
+    }
+
+    #[test]
+    fn only_the_outstanding_holds_are_held() {
+        let mut voided = hold(dec!(1));
+        assert_ok!(voided.void(Utc::now()));
+        let mut usd = hold(dec!(2));
+        usd.currency = Currency::Usd;
+        let holds = [hold(dec!(10)), hold(dec!(5.5)), voided, usd];
+        let held = held_amounts(&holds, Utc::now());
+        assert_eq!(held[&Currency::Ars], dec!(15.5));
+        assert_eq!(held[&Currency::Usd], dec!(2));
+        assert!(held_amounts(&holds, Utc::now() + Duration::hours(1)).is_empty());
+    }
+}
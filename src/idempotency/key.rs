@@ -0,0 +1,65 @@
+use crate::user::DatabaseError;
+
+/// the value of the `Idempotency-Key` header
+#[derive(Debug, Clone, Hash, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct IdempotencyKey(String);
+
+impl IdempotencyKey {
+    /// key length upper limit threshold
+    const UPPER_LIMIT: usize = 64;
+
+    pub fn inner_ref(&self) -> &str {
+        &self.0
+    }
+
+    pub fn parse_and_validate(s: &str) -> Result<Self, DatabaseError> {
+        let is_empty_or_whitespace = s.trim().is_empty();
+        let is_too_long = s.len() > Self::UPPER_LIMIT;
+        let is_printable = s.chars().all(|c| c.is_ascii_graphic());
+
+        if is_empty_or_whitespace || is_too_long || !is_printable {
+            Err(DatabaseError::InvalidIdempotencyKey(s.to_string()))
+        } else {
+            Ok(Self(s.to_string()))
+        }
+    }
+}
+
+impl AsRef<str> for IdempotencyKey {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+//-------------------------------------------------------------------------
+//                        unit tests
+//-------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::idempotency::IdempotencyKey;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn a_uuid_is_a_valid_key() {
+        assert_ok!(IdempotencyKey::parse_and_validate(
+            "514a89b1-dad9-4cd7-a43f-9aa1854a67bf"
+        ));
+    }
+
+    #[test]
+    fn empty_keys_are_rejected() {
+        assert_err!(IdempotencyKey::parse_and_validate(""));
+        assert_err!(IdempotencyKey::parse_and_validate("   "));
+    }
+
+    #[test]
+    fn a_key_longer_than_64_characters_is_rejected() {
+        assert_err!(IdempotencyKey::parse_and_validate(&"a".repeat(65)));
+    }
+
+    #[test]
+    fn keys_with_spaces_or_control_characters_are_rejected() {
+        assert_err!(IdempotencyKey::parse_and_validate("a key"));
+        assert_err!(IdempotencyKey::parse_and_validate("key\n"));
+    }
+}
@@ -0,0 +1,151 @@
+mod key;
+mod pending;
+
+pub use key::IdempotencyKey;
+pub use pending::take_pending_response;
+
+use crate::locks::StripedLocks;
+use crate::storage::{SharedStorage, Storage};
+use crate::user::DatabaseError;
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse, web};
+use pending::{PendingResponse, with_pending_response};
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, LazyLock};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+/// the response that we send the first time that a key was used, with the fingerprint of the
+/// request that produce it
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct SavedResponse {
+    pub fingerprint: String,
+    pub status: u16,
+    pub body: String,
+}
+
+impl SavedResponse {
+    fn to_http_response(&self) -> HttpResponse {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK);
+        HttpResponse::build(status)
+            .content_type("application/json")
+            .insert_header((IDEMPOTENT_REPLAYED_HEADER, "true"))
+            .body(self.body.clone())
+    }
+}
+
+/// read the `Idempotency-Key` header, the header is optional
+pub fn get_idempotency_key(request: &HttpRequest) -> Result<Option<IdempotencyKey>, DatabaseError> {
+    request
+        .headers()
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .map(|value| {
+            let value = value
+                .to_str()
+                .map_err(|_| DatabaseError::InvalidIdempotencyKey(format!("{value:?}")))?;
+            IdempotencyKey::parse_and_validate(value)
+        })
+        .transpose()
+}
+
+/// hash of the route and the body, two requests with the same key must have the same fingerprint
+pub fn fingerprint<T: serde::Serialize>(request: &HttpRequest, body: &T) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(request.path().as_bytes());
+    hasher.update(b"\n");
+    // NOTE(elsuizo: 2025-07-27): we hash the parsed body and not the raw bytes so the order of
+    // the fields or the whitespaces don't change the fingerprint
+    hasher.update(serde_json::to_vec(body).unwrap_or_default());
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// what happened with a request, the `HttpResponse` is built outside of the blocking pool
+enum Outcome {
+    /// the body of the response
+    Executed(String),
+    Replayed(SavedResponse),
+}
+
+/// the requests with the same key run one at a time, so a retry that arrives while the first
+/// request is still running waits for it and gets the saved response
+static KEY_LOCKS: LazyLock<StripedLocks> = LazyLock::new(StripedLocks::default);
+
+/// run `operation` only if the request carry a key that we never see before, a retry of the same
+/// request get the first response again and the operation is not executed twice. The response is
+/// rendered by `render` from what `operation` returns, the storage saves it in the same commit as
+/// the changes of the operation (see `take_pending_response`).
+/// Only the successful responses are saved, a failed operation don't change anything so the
+/// client can retry it with the same key.
+///
+/// The storage does blocking io, so all the work runs in the blocking thread pool of actix and
+/// the workers are free to serve other requests in the meantime
+pub async fn execute_idempotent<B, M, T, E, F, R>(
+    request: &HttpRequest,
+    storage: &SharedStorage,
+    body: B,
+    operation: F,
+    render: R,
+) -> Result<HttpResponse, E>
+where
+    B: serde::Serialize + Send + 'static,
+    M: Send + 'static,
+    T: serde::Serialize + 'static,
+    E: From<DatabaseError> + Send + 'static,
+    F: FnOnce(&dyn Storage, B) -> Result<M, E> + Send + 'static,
+    R: Fn(&M) -> T + Send + Sync + 'static,
+{
+    let key = get_idempotency_key(request)?;
+    let fingerprint = fingerprint(request, &body);
+    let storage = Arc::clone(storage);
+    let render = Arc::new(render);
+    let outcome = web::block(move || -> Result<Outcome, E> {
+        let render_body =
+            |out: &M| serde_json::to_string(&render(out)).map_err(|_| DatabaseError::Other);
+        let Some(key) = key else {
+            let out = operation(&*storage, body)?;
+            return Ok(Outcome::Executed(render_body(&out)?));
+        };
+        let _key = KEY_LOCKS.lock(&key);
+        if let Some(saved) = storage.get_saved_response(&key)? {
+            return if saved.fingerprint == fingerprint {
+                Ok(Outcome::Replayed(saved))
+            } else {
+                Err(DatabaseError::IdempotencyKeyReused(key.inner_ref().to_string()).into())
+            };
+        }
+
+        let pending = {
+            let render = Arc::clone(&render);
+            PendingResponse::new(key.clone(), fingerprint.clone(), move |out: &M| render(out))
+        };
+        let (out, saved) = with_pending_response(pending, || operation(&*storage, body))?;
+        let body = render_body(&out)?;
+        if !saved {
+            log::error!(
+                "the response of the key {} was not saved with the changes",
+                key.inner_ref()
+            );
+            let status = StatusCode::OK.as_u16();
+            let response = SavedResponse {
+                fingerprint,
+                status,
+                body: body.clone(),
+            };
+            storage.save_response(key, response)?;
+        }
+        Ok(Outcome::Executed(body))
+    })
+    .await
+    .map_err(|_| DatabaseError::Other)??;
+    Ok(match outcome {
+        Outcome::Executed(body) => HttpResponse::Ok()
+            .content_type("application/json")
+            .body(body),
+        Outcome::Replayed(saved) => saved.to_http_response(),
+    })
+}
@@ -0,0 +1,129 @@
+use crate::idempotency::{IdempotencyKey, SavedResponse};
+use crate::user::DatabaseError;
+use actix_web::http::StatusCode;
+use std::any::Any;
+use std::cell::RefCell;
+
+/// renders the body of the response from what the storage returns to the request, `None` if the
+/// storage returns other type
+type Render = Box<dyn Fn(&dyn Any) -> Option<serde_json::Result<String>>>;
+
+/// the response of the idempotent request that runs in this thread, it is waiting for the commit
+/// of the changes of the request
+pub struct PendingResponse {
+    key: IdempotencyKey,
+    fingerprint: String,
+    render: Render,
+}
+
+impl PendingResponse {
+    pub fn new<M: Any, T: serde::Serialize>(
+        key: IdempotencyKey,
+        fingerprint: String,
+        render: impl Fn(&M) -> T + 'static,
+    ) -> Self {
+        Self {
+            key,
+            fingerprint,
+            render: Box::new(move |output| {
+                output
+                    .downcast_ref::<M>()
+                    .map(|output| serde_json::to_string(&render(output)))
+            }),
+        }
+    }
+}
+
+thread_local! {
+    static PENDING_RESPONSE: RefCell<Option<PendingResponse>> = const { RefCell::new(None) };
+}
+
+/// the threads of the pool run other requests later, so the pending response is removed even if
+/// the operation panics
+struct ClearOnDrop;
+
+impl Drop for ClearOnDrop {
+    fn drop(&mut self) {
+        PENDING_RESPONSE.take();
+    }
+}
+
+/// run `operation` with the `pending` response waiting to be saved by the storage, the second
+/// value is `false` if the storage never took it
+pub fn with_pending_response<M, E>(
+    pending: PendingResponse,
+    operation: impl FnOnce() -> Result<M, E>,
+) -> Result<(M, bool), E> {
+    let _clear = ClearOnDrop;
+    PENDING_RESPONSE.set(Some(pending));
+    let out = operation()?;
+    Ok((out, PENDING_RESPONSE.take().is_none()))
+}
+
+/// The response of the idempotent request that runs in this thread rendered from `output`, what
+/// the storage returns to the request. The storage calls this when it commits the changes of the
+/// request and writes the response in the same commit, so a crash can not leave the changes
+/// without the response and a retry can not run the request again.
+/// `None` if the request has no idempotency key
+pub fn take_pending_response<T: Any>(
+    output: &T,
+) -> Result<Option<(IdempotencyKey, SavedResponse)>, DatabaseError> {
+    let Some(pending) = PENDING_RESPONSE.take() else {
+        return Ok(None);
+    };
+    let body = (pending.render)(output)
+        .ok_or(DatabaseError::Other)?
+        .map_err(|_| DatabaseError::Other)?;
+    let response = SavedResponse {
+        fingerprint: pending.fingerprint,
+        status: StatusCode::OK.as_u16(),
+        body,
+    };
+    Ok(Some((pending.key, response)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::idempotency::IdempotencyKey;
+    use crate::idempotency::pending::{
+        PendingResponse, take_pending_response, with_pending_response,
+    };
+    use claims::{assert_err, assert_none, assert_ok};
+
+    fn pending() -> PendingResponse {
+        let key = IdempotencyKey::parse_and_validate("key-1").unwrap();
+        PendingResponse::new(key, "fingerprint".into(), |out: &u32| out + 1)
+    }
+
+    #[test]
+    fn the_storage_takes_the_rendered_response() {
+        let (_, saved) = assert_ok!(with_pending_response(pending(), || {
+            let (key, response) = take_pending_response(&41u32)?.unwrap();
+            assert_eq!("key-1", key.inner_ref());
+            assert_eq!("fingerprint", response.fingerprint);
+            assert_eq!("42", response.body);
+            // the response is written only once
+            assert_none!(take_pending_response(&41u32)?);
+            Ok::<_, crate::user::DatabaseError>(())
+        }));
+        assert!(saved);
+    }
+
+    #[test]
+    fn an_output_of_other_type_is_an_error() {
+        let (_, saved) = assert_ok!(with_pending_response(pending(), || {
+            assert_err!(take_pending_response(&"41"));
+            Ok::<_, ()>(())
+        }));
+        assert!(saved);
+    }
+
+    #[test]
+    fn a_response_that_is_not_taken_is_not_left_for_the_next_request() {
+        let (_, saved) = assert_ok!(with_pending_response(pending(), || Ok::<_, ()>(())));
+        assert!(!saved);
+        assert_none!(assert_ok!(take_pending_response(&41u32)));
+        assert_err!(with_pending_response(pending(), || Err::<(), _>(())));
+        assert_none!(assert_ok!(take_pending_response(&41u32)));
+    }
+}
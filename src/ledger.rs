@@ -0,0 +1,261 @@
+use crate::currency::Currency;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// the counterpart account of every movement that comes from (or goes to) outside of the service,
+/// it balance in every currency is the negative of all the money that we hold for the clients
+pub const EXTERNAL_ACCOUNT: Uuid = Uuid::nil();
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Credit,
+    Debit,
+}
+
+impl Direction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Credit => "credit",
+            Self::Debit => "debit",
+        }
+    }
+
+    /// the amount with the sign that it has over the balance
+    pub fn signed(self, amount: Decimal) -> Decimal {
+        match self {
+            Self::Credit => amount,
+            Self::Debit => -amount,
+        }
+    }
+}
+
+impl FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "credit" => Ok(Self::Credit),
+            "debit" => Ok(Self::Debit),
+            other => Err(format!("{other} is not a valid direction")),
+        }
+    }
+}
+
+/// one immutable line of the ledger, every transaction is made of at least two entries that sum
+/// zero in every currency
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct LedgerEntry {
+    pub id: Uuid,
+    pub transaction_id: Uuid,
+    pub client_id: Uuid,
+    pub currency: Currency,
+    pub direction: Direction,
+    pub amount: Decimal,
+    /// the balance of the account (client and currency) after applying this entry
+    pub balance: Decimal,
+    pub timestamp: DateTime<Utc>,
+    /// the rate applied when the transaction is a conversion between currencies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exchange_rate: Option<Decimal>,
+}
+
+/// one side of a transaction before it is written in the ledger
+#[derive(Debug, Copy, Clone)]
+pub struct Leg {
+    pub client_id: Uuid,
+    pub currency: Currency,
+    pub direction: Direction,
+    pub amount: Decimal,
+}
+
+impl Leg {
+    pub fn credit(client_id: Uuid, currency: Currency, amount: Decimal) -> Self {
+        Self {
+            client_id,
+            currency,
+            direction: Direction::Credit,
+            amount,
+        }
+    }
+
+    pub fn debit(client_id: Uuid, currency: Currency, amount: Decimal) -> Self {
+        Self {
+            client_id,
+            currency,
+            direction: Direction::Debit,
+            amount,
+        }
+    }
+}
+
+#[derive(Debug, Default, serde::Deserialize, serde::Serialize)]
+pub struct Ledger {
+    accounts: HashMap<Uuid, Vec<LedgerEntry>>,
+}
+
+impl Ledger {
+    /// the balance after the last entry of the account
+    pub fn balance(&self, client_id: Uuid, currency: Currency) -> Decimal {
+        self.entries(client_id)
+            .iter()
+            .rev()
+            .find(|entry| entry.currency == currency)
+            .map_or(Decimal::ZERO, |entry| entry.balance)
+    }
+
+    /// the balance computed from scratch adding all the entries of the account
+    pub fn derive_balance(&self, client_id: Uuid, currency: Currency) -> Decimal {
+        self.entries(client_id)
+            .iter()
+            .filter(|entry| entry.currency == currency)
+            .map(|entry| entry.direction.signed(entry.amount))
+            .sum()
+    }
+
+    /// all the entries of the client, in every currency
+    pub fn entries(&self, client_id: Uuid) -> &[LedgerEntry] {
+        self.accounts.get(&client_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// build the entries of a new transaction, nothing is written until `append` is called.
+    /// A leg against the `EXTERNAL_ACCOUNT` is added for every currency in which the legs don't
+    /// sum zero
+    pub fn prepare(&self, legs: &[Leg]) -> Vec<LedgerEntry> {
+        prepare_entries(legs, |client_id, currency| {
+            self.balance(client_id, currency)
+        })
+    }
+
+    pub fn append(&mut self, entry: LedgerEntry) {
+        self.accounts
+            .entry(entry.client_id)
+            .or_default()
+            .push(entry);
+    }
+}
+
+/// like `Ledger::prepare` for the ledgers that are not in memory, `current_balance` gives the
+/// balance after the last entry of an account
+pub fn prepare_entries(
+    legs: &[Leg],
+    mut current_balance: impl FnMut(Uuid, Currency) -> Decimal,
+) -> Vec<LedgerEntry> {
+    let transaction_id = Uuid::new_v4();
+    let timestamp = Utc::now();
+    let mut balances: HashMap<(Uuid, Currency), Decimal> = HashMap::new();
+
+    let mut unbalanced: BTreeMap<Currency, Decimal> = BTreeMap::new();
+    for leg in legs {
+        *unbalanced.entry(leg.currency).or_default() += leg.direction.signed(leg.amount);
+    }
+    let counterparts: Vec<Leg> = unbalanced
+        .into_iter()
+        .filter(|(_, amount)| !amount.is_zero())
+        .map(|(currency, amount)| Leg {
+            client_id: EXTERNAL_ACCOUNT,
+            currency,
+            direction: if amount > Decimal::ZERO {
+                Direction::Debit
+            } else {
+                Direction::Credit
+            },
+            amount: amount.abs(),
+        })
+        .collect();
+
+    legs.iter()
+        .chain(counterparts.iter())
+        .map(|leg| {
+            let balance = balances
+                .entry((leg.client_id, leg.currency))
+                .or_insert_with(|| current_balance(leg.client_id, leg.currency));
+            *balance += leg.direction.signed(leg.amount);
+            LedgerEntry {
+                id: Uuid::new_v4(),
+                transaction_id,
+                client_id: leg.client_id,
+                currency: leg.currency,
+                direction: leg.direction,
+                amount: leg.amount,
+                balance: *balance,
+                timestamp,
+                exchange_rate: None,
+            }
+        })
+        .collect()
+}
+
+//-------------------------------------------------------------------------
+//                        unit tests
+//-------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::currency::Currency;
+    use crate::ledger::{Direction, EXTERNAL_ACCOUNT, Ledger, Leg};
+    use rust_decimal::{Decimal, dec};
+    use uuid::Uuid;
+
+    #[test]
+    fn every_transaction_sums_zero() {
+        let ledger = Ledger::default();
+        let client = Uuid::new_v4();
+        let entries = ledger.prepare(&[Leg::credit(client, Currency::Ars, dec!(10))]);
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].client_id, EXTERNAL_ACCOUNT);
+        assert_eq!(entries[1].direction, Direction::Debit);
+        let total: Decimal = entries
+            .iter()
+            .map(|entry| entry.direction.signed(entry.amount))
+            .sum();
+        assert!(total.is_zero());
+    }
+
+    #[test]
+    fn every_currency_is_balanced_on_its_own() {
+        let ledger = Ledger::default();
+        let client = Uuid::new_v4();
+        let entries = ledger.prepare(&[
+            Leg::debit(client, Currency::Ars, dec!(1000)),
+            Leg::credit(client, Currency::Usd, dec!(1)),
+        ]);
+
+        assert_eq!(entries.len(), 4);
+        for currency in [Currency::Ars, Currency::Usd] {
+            let total: Decimal = entries
+                .iter()
+                .filter(|entry| entry.currency == currency)
+                .map(|entry| entry.direction.signed(entry.amount))
+                .sum();
+            assert!(total.is_zero());
+        }
+    }
+
+    #[test]
+    fn balances_can_be_derived_from_the_entries() {
+        let mut ledger = Ledger::default();
+        let client = Uuid::new_v4();
+        for leg in [
+            Leg::credit(client, Currency::Ars, dec!(10)),
+            Leg::debit(client, Currency::Ars, dec!(2.5)),
+            Leg::credit(client, Currency::Ars, dec!(1)),
+            Leg::credit(client, Currency::Usd, dec!(3)),
+        ] {
+            ledger
+                .prepare(&[leg])
+                .into_iter()
+                .for_each(|e| ledger.append(e));
+        }
+
+        assert_eq!(ledger.balance(client, Currency::Ars), dec!(8.5));
+        assert_eq!(ledger.derive_balance(client, Currency::Ars), dec!(8.5));
+        assert_eq!(ledger.balance(client, Currency::Usd), dec!(3));
+        assert_eq!(ledger.entries(client).len(), 4);
+        assert_eq!(ledger.balance(EXTERNAL_ACCOUNT, Currency::Ars), dec!(-8.5));
+        assert_eq!(ledger.balance(EXTERNAL_ACCOUNT, Currency::Usd), dec!(-3));
+    }
+}
@@ -1,5 +1,20 @@
 pub mod configuration;
+pub mod country;
+pub mod currency;
+pub mod document;
+pub mod exchange;
+pub mod hold;
+pub mod idempotency;
+pub mod ledger;
+pub mod listing;
 pub mod local_database;
+pub mod locks;
+pub mod onboarding;
 pub mod routes;
+pub mod scheduler;
 pub mod service;
+pub mod settlement;
+pub mod sqlite_database;
+pub mod statement;
+pub mod storage;
 pub mod user;
@@ -0,0 +1,374 @@
+use crate::currency::Currency;
+use crate::onboarding::KycStatus;
+use crate::user::{CountryName, DatabaseError, User};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::cmp::Ordering;
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// the field used to order the list of clients
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientSort {
+    #[default]
+    Name,
+    /// the balance in the `currency` of the listing
+    Balance,
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientStatus {
+    Open,
+    Closed,
+}
+
+/// the value of the sort field of a client, the ties are broken with the id of the client
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+enum SortKey {
+    /// the name in lower case
+    Name(String),
+    Balance(Decimal),
+}
+
+/// Where the next page starts: the sort key and the id of the last client of the previous page.
+/// The next page has the clients that come after it, so the clients that are created (or
+/// removed) in the middle don't move the rest of the pages
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct Cursor {
+    sort: ClientSort,
+    order: SortOrder,
+    key: SortKey,
+    id: Uuid,
+}
+
+impl Cursor {
+    /// the cursor as an opaque string for the clients of the API
+    pub fn encode(&self) -> String {
+        serde_json::to_vec(self)
+            .unwrap_or_default()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = DatabaseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || DatabaseError::InvalidCursor(s.to_string());
+        if !s.len().is_multiple_of(2) || !s.is_ascii() {
+            return Err(invalid());
+        }
+        let bytes = (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| invalid())?;
+        serde_json::from_slice(&bytes).map_err(|_| invalid())
+    }
+}
+
+/// The filters, the order and the page of a listing of clients, all the filters are optional
+#[derive(Debug, Clone, Default)]
+pub struct ClientListing {
+    pub country: Option<CountryName>,
+    pub kyc_status: Option<KycStatus>,
+    pub status: Option<ClientStatus>,
+    /// the balances of the filters and the sort are the ones in this currency, the clients without
+    /// an account in it are not listed
+    pub currency: Option<Currency>,
+    pub min_balance: Option<Decimal>,
+    pub max_balance: Option<Decimal>,
+    /// the clients created since this day (inclusive), the clients created before we saved the
+    /// creation date are never in a listing with dates
+    pub created_from: Option<NaiveDate>,
+    /// the clients created until this day (inclusive)
+    pub created_to: Option<NaiveDate>,
+    pub sort: ClientSort,
+    pub order: SortOrder,
+    /// the cursor of the previous page, `None` for the first page
+    pub after: Option<Cursor>,
+    /// the size of the page, it is always between 1 and `MAX_LIMIT`
+    pub limit: usize,
+}
+
+/// one page of a listing of clients
+#[derive(Debug)]
+pub struct ClientPage {
+    pub clients: Vec<(Uuid, User)>,
+    /// `None` in the last page
+    pub next_cursor: Option<Cursor>,
+}
+
+impl ClientListing {
+    pub const DEFAULT_LIMIT: usize = 50;
+    pub const MAX_LIMIT: usize = 200;
+
+    /// the page of `users` that this listing selects
+    pub fn page<'a>(
+        &self,
+        users: impl IntoIterator<Item = (Uuid, &'a User)>,
+    ) -> Result<ClientPage, DatabaseError> {
+        self.check()?;
+        let mut rows = users
+            .into_iter()
+            .filter(|(_, user)| self.matches(user))
+            .filter_map(|(id, user)| Some((self.key(user)?, id, user)))
+            .filter(|(key, id, _)| match &self.after {
+                Some(after) => self.compare((key, id), (&after.key, &after.id)).is_gt(),
+                None => true,
+            })
+            .collect::<Vec<_>>();
+        rows.sort_by(|(a, a_id, _), (b, b_id, _)| self.compare((a, a_id), (b, b_id)));
+
+        let limit = self.limit.clamp(1, Self::MAX_LIMIT);
+        let next_cursor = (rows.len() > limit).then(|| {
+            let (key, id, _) = &rows[limit - 1];
+            Cursor {
+                sort: self.sort,
+                order: self.order,
+                key: key.clone(),
+                id: *id,
+            }
+        });
+        let clients = rows
+            .into_iter()
+            .take(limit)
+            .map(|(_, id, user)| (id, user.clone()))
+            .collect();
+        Ok(ClientPage {
+            clients,
+            next_cursor,
+        })
+    }
+
+    /// the balances need a currency and the cursor must be of the same order of the listing
+    fn check(&self) -> Result<(), DatabaseError> {
+        let uses_balance = self.min_balance.is_some()
+            || self.max_balance.is_some()
+            || self.sort == ClientSort::Balance;
+        if uses_balance && self.currency.is_none() {
+            return Err(DatabaseError::CurrencyRequired);
+        }
+        match &self.after {
+            Some(after) if after.sort != self.sort || after.order != self.order => Err(
+                DatabaseError::InvalidCursor(format!("{:?} {:?}", after.sort, after.order)),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    fn matches(&self, user: &User) -> bool {
+        let balance = self
+            .currency
+            .map(|currency| user.get_actual_credit(currency));
+        let created = user.created_at().map(|at| at.date_naive());
+        self.country
+            .as_ref()
+            .is_none_or(|country| user.get_country_name() == country.inner_ref())
+            && self.kyc_status.is_none_or(|s| user.kyc_status() == s)
+            && self.status.is_none_or(|status| match status {
+                ClientStatus::Open => user.closed_at().is_none(),
+                ClientStatus::Closed => user.closed_at().is_some(),
+            })
+            && balance.is_none_or(|balance| {
+                balance.is_some_and(|balance| {
+                    self.min_balance.is_none_or(|min| balance >= min)
+                        && self.max_balance.is_none_or(|max| balance <= max)
+                })
+            })
+            && self
+                .created_from
+                .is_none_or(|from| created.is_some_and(|created| created >= from))
+            && self
+                .created_to
+                .is_none_or(|to| created.is_some_and(|created| created <= to))
+    }
+
+    fn key(&self, user: &User) -> Option<SortKey> {
+        match self.sort {
+            ClientSort::Name => Some(SortKey::Name(user.client_name.inner_ref().to_lowercase())),
+            ClientSort::Balance => user.get_actual_credit(self.currency?).map(SortKey::Balance),
+        }
+    }
+
+    fn compare(&self, a: (&SortKey, &Uuid), b: (&SortKey, &Uuid)) -> Ordering {
+        match self.order {
+            SortOrder::Asc => a.cmp(&b),
+            SortOrder::Desc => b.cmp(&a),
+        }
+    }
+}
+
+//-------------------------------------------------------------------------
+//                        unit tests
+//-------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::country::test_country;
+    use crate::currency::Currency;
+    use crate::document::DocumentType;
+    use crate::listing::{ClientListing, ClientSort, ClientStatus, Cursor, SortOrder};
+    use crate::user::{DatabaseError, Document, User, UserName};
+    use chrono::{NaiveDate, Utc};
+    use claims::{assert_err, assert_ok};
+    use rust_decimal::{Decimal, dec};
+    use uuid::Uuid;
+
+    fn user(name: &str, document: &str, balance: Decimal) -> (Uuid, User) {
+        let argentina = test_country("Argentina");
+        let mut user = User::new(
+            UserName::parse_and_validate(name).unwrap(),
+            NaiveDate::from_ymd_opt(1982, 9, 27).unwrap().into(),
+            Document::parse_and_validate(DocumentType::Dni, &argentina, document).unwrap(),
+            &argentina,
+        );
+        user.increase_credit(Currency::Ars, balance);
+        (Uuid::new_v4(), user)
+    }
+
+    fn users() -> Vec<(Uuid, User)> {
+        vec![
+            user("carla", "30000001", dec!(5)),
+            user("Ana", "30000002", dec!(20)),
+            user("bruno", "30000003", dec!(5)),
+            user("Dario", "30000004", dec!(0)),
+        ]
+    }
+
+    fn names(listing: &ClientListing, users: &[(Uuid, User)]) -> Vec<String> {
+        let page = listing
+            .page(users.iter().map(|(id, user)| (*id, user)))
+            .unwrap();
+        page.clients
+            .iter()
+            .map(|(_, user)| user.client_name.inner_ref().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn the_pages_follow_the_cursor() {
+        let mut users = users();
+        let mut listing = ClientListing {
+            limit: 2,
+            ..Default::default()
+        };
+        let page = listing
+            .page(users.iter().map(|(id, user)| (*id, user)))
+            .unwrap();
+        assert_eq!(page.clients.len(), 2);
+        assert_eq!(page.clients[0].1.client_name.inner_ref(), "Ana");
+
+        // a client created between the pages before the cursor doesn't move the next page
+        users.push(user("Aaron", "30000005", dec!(1)));
+        listing.after = page.next_cursor;
+        assert_eq!(names(&listing, &users), ["carla", "Dario"]);
+    }
+
+    #[test]
+    fn the_last_page_has_no_cursor() {
+        let users = users();
+        let listing = ClientListing {
+            limit: 4,
+            ..Default::default()
+        };
+        let page = listing
+            .page(users.iter().map(|(id, user)| (*id, user)))
+            .unwrap();
+        assert_eq!(page.clients.len(), 4);
+        assert!(page.next_cursor.is_none());
+    }
+
+    #[test]
+    fn the_clients_can_be_sorted_and_filtered_by_balance() {
+        let users = users();
+        let mut listing = ClientListing {
+            currency: Some(Currency::Ars),
+            sort: ClientSort::Balance,
+            order: SortOrder::Desc,
+            min_balance: Some(dec!(1)),
+            limit: 10,
+            ..Default::default()
+        };
+        let listed = names(&listing, &users);
+        assert_eq!(listed[0], "Ana");
+        assert_eq!(listed.len(), 3);
+
+        listing.max_balance = Some(dec!(5));
+        assert_eq!(names(&listing, &users).len(), 2);
+
+        listing.currency = Some(Currency::Usd);
+        assert!(names(&listing, &users).is_empty());
+    }
+
+    #[test]
+    fn the_balances_need_a_currency() {
+        let users = users();
+        let listing = ClientListing {
+            sort: ClientSort::Balance,
+            ..Default::default()
+        };
+        assert!(matches!(
+            listing.page(users.iter().map(|(id, user)| (*id, user))),
+            Err(DatabaseError::CurrencyRequired)
+        ));
+    }
+
+    #[test]
+    fn the_clients_can_be_filtered_by_status_and_creation_date() {
+        let mut users = users();
+        let (id, closed) = &mut users[3];
+        assert_ok!(closed.close(*id, Utc::now()));
+        let today = Utc::now().date_naive();
+        let mut listing = ClientListing {
+            status: Some(ClientStatus::Closed),
+            limit: 10,
+            ..Default::default()
+        };
+        assert_eq!(names(&listing, &users), ["Dario"]);
+
+        listing.status = Some(ClientStatus::Open);
+        listing.created_from = today.pred_opt();
+        assert_eq!(names(&listing, &users).len(), 3);
+        listing.created_from = today.succ_opt();
+        assert!(names(&listing, &users).is_empty());
+    }
+
+    #[test]
+    fn a_cursor_is_only_valid_for_the_same_order() {
+        let users = users();
+        let listing = ClientListing {
+            limit: 1,
+            ..Default::default()
+        };
+        let page = listing
+            .page(users.iter().map(|(id, user)| (*id, user)))
+            .unwrap();
+        let cursor = page.next_cursor.unwrap();
+        assert_eq!(cursor.encode().parse::<Cursor>().unwrap(), cursor);
+        assert_err!("not a cursor".parse::<Cursor>());
+        assert_err!("7b7d".parse::<Cursor>());
+
+        let listing = ClientListing {
+            order: SortOrder::Desc,
+            after: Some(cursor),
+            ..Default::default()
+        };
+        assert!(matches!(
+            listing.page(users.iter().map(|(id, user)| (*id, user))),
+            Err(DatabaseError::InvalidCursor(_))
+        ));
+    }
+}
@@ -0,0 +1,1486 @@
+mod settlements;
+mod wal;
+
+pub use settlements::SettlementRun;
+
+use crate::currency::Currency;
+use crate::hold::{self, Hold};
+use crate::idempotency::{self, IdempotencyKey, SavedResponse};
+use crate::ledger::{Direction, EXTERNAL_ACCOUNT, Ledger, LedgerEntry, Leg};
+use crate::listing::{ClientListing, ClientPage};
+use crate::locks::StripedLocks;
+use crate::onboarding::KycStatus;
+use crate::settlement::{self, ScheduledRun, SettlementFile, SettlementRecord, SettlementSummary};
+use crate::storage::{Storage, validate_amount};
+use crate::user::{CreateUserError, DatabaseError, Document, ProfileChange, User};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use settlements::SettlementRegistry;
+use std::any::Any;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError, RwLock, RwLockReadGuard};
+use uuid::Uuid;
+use wal::Wal;
+
+/// every mutation of the database is first written in the log as one of this records, replaying
+/// them in order over the last snapshot gives us the same state
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+enum Record {
+    NewUser {
+        id: Uuid,
+        user: User,
+    },
+    NewAccount {
+        id: Uuid,
+        currency: Currency,
+    },
+    KycStatusChanged {
+        id: Uuid,
+        status: KycStatus,
+    },
+    ProfileChanged {
+        id: Uuid,
+        change: ProfileChange,
+    },
+    UserClosed {
+        id: Uuid,
+        at: DateTime<Utc>,
+    },
+    Transaction {
+        entries: Vec<LedgerEntry>,
+    },
+    HoldPlaced {
+        hold: Hold,
+    },
+    /// the hold is captured and the entries debit the captured amount
+    HoldCaptured {
+        hold: Hold,
+        entries: Vec<LedgerEntry>,
+    },
+    /// the holds were voided or they expired, the money is available again
+    HoldsReleased {
+        holds: Vec<Hold>,
+    },
+    /// we are about to write the settlement file, nothing changes until `BalancesStored`
+    SettlementStarted(PendingSettlement),
+    /// the settlement file is safe in the disk, the entries leave all the balances in zero
+    BalancesStored {
+        run: SettlementRun,
+        entries: Vec<LedgerEntry>,
+    },
+    /// the settlement file could not be written, the balances were not touched
+    SettlementAborted {
+        settlement_id: Uuid,
+    },
+    SavedResponse {
+        key: IdempotencyKey,
+        response: SavedResponse,
+    },
+    /// the changes of an idempotent request with its response, so we never keep one without the
+    /// other
+    Responded {
+        record: Box<Record>,
+        key: IdempotencyKey,
+        response: SavedResponse,
+    },
+}
+
+/// a settlement that was started but we don't know yet if the file was written
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct PendingSettlement {
+    id: Uuid,
+    sequence: u64,
+    directory: PathBuf,
+    file_name: String,
+    #[serde(default)]
+    scheduled_for: Option<DateTime<Utc>>,
+}
+
+/// everything that the database knows, this is what we save in the snapshots
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct State {
+    users: HashMap<Uuid, User>,
+    ledger: Ledger,
+    idempotency: HashMap<IdempotencyKey, SavedResponse>,
+    #[serde(default)]
+    holds: HashMap<Uuid, Hold>,
+    /// sequence number of the last settlement file
+    settlement_sequence: u64,
+    pending_settlement: Option<PendingSettlement>,
+    settlements: SettlementRegistry,
+    /// the id of every user by his document, it is not saved in the snapshots because it is built
+    /// from `users` when the database is opened
+    #[serde(skip)]
+    documents: HashMap<Document, Uuid>,
+    /// the ids of the holds of every user (the oldest first), it is built like `documents`
+    #[serde(skip)]
+    holds_by_client: HashMap<Uuid, Vec<Uuid>>,
+}
+
+impl State {
+    fn index_documents(&mut self) {
+        self.documents = self
+            .users
+            .iter()
+            .map(|(id, user)| (user.document(), *id))
+            .collect();
+    }
+
+    fn index_holds(&mut self) {
+        let mut holds = self.holds.values().collect::<Vec<_>>();
+        holds.sort_by_key(|hold| hold.created_at);
+        self.holds_by_client = HashMap::new();
+        for hold in holds {
+            self.holds_by_client
+                .entry(hold.client_id)
+                .or_default()
+                .push(hold.id);
+        }
+    }
+
+    /// `Err` if the `record` can not be applied over this state. The records are checked before
+    /// they are written to the log, so `apply` never fails in the middle of a record
+    fn check(&self, record: &Record) -> Result<(), DatabaseError> {
+        match record {
+            Record::NewAccount { id, .. }
+            | Record::KycStatusChanged { id, .. }
+            | Record::ProfileChanged { id, .. } => self.user(*id).map(drop),
+            Record::UserClosed { id, at } => self.user(*id)?.clone().close(*id, *at),
+            Record::Transaction { entries }
+            | Record::HoldCaptured { entries, .. }
+            | Record::BalancesStored { entries, .. } => self.check_entries(entries),
+            Record::Responded { record, .. } => self.check(record),
+            _ => Ok(()),
+        }
+    }
+
+    /// every account of the `entries` exists and no balance ends below zero
+    fn check_entries(&self, entries: &[LedgerEntry]) -> Result<(), DatabaseError> {
+        let mut balances = HashMap::new();
+        for entry in entries.iter().filter(|e| e.client_id != EXTERNAL_ACCOUNT) {
+            let balance = match balances.entry((entry.client_id, entry.currency)) {
+                Entry::Occupied(balance) => balance.into_mut(),
+                Entry::Vacant(balance) => {
+                    balance.insert(self.balance(entry.client_id, entry.currency)?)
+                }
+            };
+            if entry.direction == Direction::Debit && *balance < entry.amount {
+                return Err(DatabaseError::InsufficientBalance(*balance));
+            }
+            *balance += entry.direction.signed(entry.amount);
+        }
+        Ok(())
+    }
+
+    /// the caller must `check` the record first
+    fn apply(&mut self, record: Record) {
+        match record {
+            Record::NewUser { id, user } => {
+                self.documents.insert(user.document(), id);
+                self.users.insert(id, user);
+            }
+            Record::NewAccount { id, currency } => {
+                if let Some(user) = self.users.get_mut(&id) {
+                    user.open_account(currency);
+                }
+            }
+            Record::KycStatusChanged { id, status } => {
+                if let Some(user) = self.users.get_mut(&id) {
+                    user.set_kyc_status(status);
+                }
+            }
+            Record::ProfileChanged { id, change } => {
+                if let Some(user) = self.users.get_mut(&id) {
+                    user.change_profile(&change);
+                }
+            }
+            Record::UserClosed { id, at } => {
+                if let Some(user) = self.users.get_mut(&id) {
+                    user.set_closed_at(Some(at));
+                }
+            }
+            Record::Transaction { entries } => self.apply_entries(entries),
+            Record::HoldPlaced { hold } => self.save_hold(hold),
+            Record::HoldCaptured { hold, entries } => {
+                self.save_hold(hold);
+                self.apply_entries(entries);
+            }
+            Record::HoldsReleased { holds } => {
+                holds.into_iter().for_each(|hold| self.save_hold(hold))
+            }
+            Record::SettlementStarted(pending) => {
+                self.pending_settlement = Some(pending);
+            }
+            Record::BalancesStored { run, entries } => {
+                self.settlement_sequence += 1;
+                self.pending_settlement = None;
+                self.settlements.push(run);
+                self.apply_entries(entries);
+            }
+            Record::SettlementAborted { .. } => {
+                self.pending_settlement = None;
+            }
+            Record::SavedResponse { key, response } => {
+                self.idempotency.insert(key, response);
+            }
+            Record::Responded {
+                record,
+                key,
+                response,
+            } => {
+                self.apply(*record);
+                self.idempotency.insert(key, response);
+            }
+        }
+    }
+
+    /// the entries were checked with `check_entries`
+    fn apply_entries(&mut self, entries: Vec<LedgerEntry>) {
+        for entry in entries {
+            if let Some(user) = self.users.get_mut(&entry.client_id) {
+                user.increase_credit(entry.currency, entry.direction.signed(entry.amount));
+            }
+            self.ledger.append(entry);
+        }
+    }
+
+    /// the balance of the account of the user `id` in `currency`
+    fn balance(&self, id: Uuid, currency: Currency) -> Result<Decimal, DatabaseError> {
+        self.user(id)?
+            .get_actual_credit(currency)
+            .ok_or(DatabaseError::UnknownAccount(id, currency))
+    }
+
+    fn user(&self, id: Uuid) -> Result<&User, DatabaseError> {
+        self.users.get(&id).ok_or(DatabaseError::UnknownUser(id))
+    }
+
+    /// insert a new hold or replace the old version of it
+    fn save_hold(&mut self, hold: Hold) {
+        if !self.holds.contains_key(&hold.id) {
+            self.holds_by_client
+                .entry(hold.client_id)
+                .or_default()
+                .push(hold.id);
+        }
+        self.holds.insert(hold.id, hold);
+    }
+
+    fn hold(&self, hold_id: Uuid) -> Result<&Hold, DatabaseError> {
+        self.holds
+            .get(&hold_id)
+            .ok_or(DatabaseError::UnknownHold(hold_id))
+    }
+
+    fn client_holds(&self, id: Uuid) -> impl Iterator<Item = &Hold> {
+        self.holds_by_client
+            .get(&id)
+            .into_iter()
+            .flatten()
+            .filter_map(|hold_id| self.holds.get(hold_id))
+    }
+
+    /// the money of the account that the holds reserve at the time `now`
+    fn held(&self, id: Uuid, currency: Currency, now: DateTime<Utc>) -> Decimal {
+        hold::held_amounts(self.client_holds(id), now)
+            .remove(&currency)
+            .unwrap_or_default()
+    }
+
+    /// the balance of the account that is not held by some hold at the time `now`
+    fn available(
+        &self,
+        id: Uuid,
+        currency: Currency,
+        now: DateTime<Utc>,
+    ) -> Result<Decimal, DatabaseError> {
+        Ok(self.balance(id, currency)? - self.held(id, currency, now))
+    }
+}
+
+/// The clients live in memory and every change is saved in a write-ahead log.
+///
+/// Every operation over an account takes the lock of the account (see `StripedLocks`) while it
+/// checks the balances and writes the movement, so the operations over different accounts don't
+/// wait for each other. Only the write to the log is done one at a time, and the readers never
+/// wait for the disk
+#[derive(Debug, Default)]
+pub struct Database {
+    state: RwLock<State>,
+    /// `None` for the in memory database, every commit holds it while the record is written
+    wal: Mutex<Option<Wal>>,
+    accounts: StripedLocks,
+}
+
+impl Database {
+    /// in memory database, nothing is saved to disk
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// open the database stored in the directory `path` replaying the write-ahead log over the
+    /// last snapshot, if the directory is empty we start from scratch. A settlement that was
+    /// interrupted by a crash is completed or aborted here
+    pub fn open(path: impl AsRef<Path>, snapshot_interval: usize) -> Result<Self, DatabaseError> {
+        let (wal, recovered) = Wal::open(path, snapshot_interval)?;
+        let mut state: State = recovered.state.unwrap_or_default();
+        state.index_documents();
+        state.index_holds();
+        for record in recovered.records {
+            // NOTE(elsuizo: 2025-10-18): the old versions wrote the record before checking it, a
+            // record that failed to apply was never acknowledged so we can drop it
+            if let Err(e) = state.check(&record) {
+                log::warn!("dropping a record of the log that can not be applied: {e}");
+                continue;
+            }
+            state.apply(record);
+        }
+        let pending = state.pending_settlement.clone();
+        let database = Self {
+            state: RwLock::new(state),
+            wal: Mutex::new(Some(wal)),
+            accounts: StripedLocks::default(),
+        };
+        if let Some(pending) = pending {
+            database.recover_settlement(pending)?;
+        }
+        Ok(database)
+    }
+
+    fn read(&self) -> Result<RwLockReadGuard<'_, State>, DatabaseError> {
+        self.state.read().map_err(|_| DatabaseError::Poisoned)
+    }
+
+    /// the caller must hold the lock of the account, so the status can not change in the middle
+    /// of the operation
+    fn check_may_transact(&self, id: Uuid) -> Result<(), DatabaseError> {
+        self.read()?.user(id)?.check_may_transact(id)
+    }
+
+    /// the balance of the account that can be debited, without the money of the active holds
+    fn available(&self, id: Uuid, currency: Currency) -> Result<Decimal, DatabaseError> {
+        self.read()?.available(id, currency, Utc::now())
+    }
+
+    /// check the record built by `build`, write it to the log (if any) and then apply it. The
+    /// commits run one at a time, so the state that `build` sees is the one where the record is
+    /// applied
+    fn commit_with<T, E: From<DatabaseError>>(
+        &self,
+        build: impl FnOnce(&State) -> Result<(Record, T), E>,
+    ) -> Result<T, E> {
+        let mut wal = self.wal.lock().map_err(|_| DatabaseError::Poisoned)?;
+        let (record, out) = {
+            let state = self.read()?;
+            let (record, out) = build(&state)?;
+            state.check(&record)?;
+            (record, out)
+        };
+        if let Some(wal) = wal.as_mut() {
+            wal.append(&record).map_err(DatabaseError::from)?;
+        }
+        // the record is already in the log, the state must have it too
+        self.state
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .apply(record);
+        if let Some(wal) = wal.as_mut().filter(|wal| wal.needs_snapshot()) {
+            // NOTE(elsuizo: 2025-07-20): the record is already safe in the log so a failed
+            // snapshot is not an error for the caller, we just try again in the next commit
+            if let Err(e) = wal.snapshot(&*self.read()?) {
+                log::error!("failed to write the database snapshot: {e}");
+            }
+        }
+        Ok(out)
+    }
+
+    /// write a record that was already validated
+    fn commit(&self, record: Record) -> Result<(), DatabaseError> {
+        self.commit_with(|_| Ok((record, ())))
+    }
+
+    /// like `commit_with` for the last change of a request, `out` is what we return to the
+    /// request. The response of the idempotent request that runs in this thread (if any) is
+    /// written in the same record
+    fn commit_request<T: Any, E: From<DatabaseError>>(
+        &self,
+        build: impl FnOnce(&State) -> Result<(Record, T), E>,
+    ) -> Result<T, E> {
+        self.commit_with(|state| {
+            let (record, out) = build(state)?;
+            let record = match idempotency::take_pending_response(&out)? {
+                Some((key, response)) => Record::Responded {
+                    record: Box::new(record),
+                    key,
+                    response,
+                },
+                None => record,
+            };
+            Ok((record, out))
+        })
+    }
+
+    /// write a validated transaction (with the legs against the external account), `edit` can
+    /// change the entries before they are written and `output` picks what we return from them.
+    /// The caller must hold the locks of the accounts
+    fn commit_legs<T: Any>(
+        &self,
+        legs: &[Leg],
+        edit: impl FnMut(&mut LedgerEntry),
+        output: impl FnOnce(&[LedgerEntry]) -> Result<T, DatabaseError>,
+    ) -> Result<T, DatabaseError> {
+        self.commit_request(|state| {
+            let mut entries = state.ledger.prepare(legs);
+            entries.iter_mut().for_each(edit);
+            let out = output(&entries)?;
+            Ok((Record::Transaction { entries }, out))
+        })
+    }
+
+    /// the balance of the user in `currency` computed only from his ledger entries
+    pub fn rebuild_balance(&self, id: Uuid, currency: Currency) -> Result<Decimal, DatabaseError> {
+        let state = self.read()?;
+        state.balance(id, currency)?;
+        Ok(state.ledger.derive_balance(id, currency))
+    }
+
+    fn settle(
+        &self,
+        directory: &Path,
+        scheduled: Option<ScheduledRun>,
+    ) -> Result<SettlementSummary, DatabaseError> {
+        // NOTE(elsuizo: 2025-08-09): the settlement touches every account, so nobody can move
+        // money until the file is written and the balances are in zero
+        let _accounts = self.accounts.lock_everything();
+        if let Some(run) = scheduled {
+            let settlements = self.settlements()?;
+            if let Some(done) = run.already_settled(&settlements) {
+                return Err(DatabaseError::AlreadySettled(done.id));
+            }
+        }
+        let scheduled_for = scheduled.map(|run| run.fire_time);
+        let (pending, settlement) = self.start_settlement(directory, scheduled_for)?;
+        if let Err(e) = settlement.write_atomically(&pending.directory) {
+            let _ = fs::remove_file(settlement::tmp_path(&pending.directory, &pending.file_name));
+            self.commit(Record::SettlementAborted {
+                settlement_id: pending.id,
+            })?;
+            return Err(e.into());
+        }
+        self.finish_settlement(&pending, &settlement)
+    }
+
+    fn start_settlement(
+        &self,
+        directory: &Path,
+        scheduled_for: Option<DateTime<Utc>>,
+    ) -> Result<(PendingSettlement, SettlementFile), DatabaseError> {
+        let now = Utc::now();
+        self.commit_with(|state| {
+            let records = state
+                .users
+                .iter()
+                .flat_map(|(id, user)| {
+                    user.balances()
+                        .iter()
+                        .map(|(currency, credit)| SettlementRecord {
+                            client_id: *id,
+                            currency: *currency,
+                            amount: *credit - state.held(*id, *currency, now),
+                        })
+                })
+                .collect();
+            let settlement = SettlementFile::new(state.settlement_sequence + 1, records);
+            let pending = PendingSettlement {
+                id: Uuid::new_v4(),
+                sequence: settlement.sequence,
+                directory: directory.to_path_buf(),
+                file_name: settlement.file_name(),
+                scheduled_for,
+            };
+            Ok((
+                Record::SettlementStarted(pending.clone()),
+                (pending, settlement),
+            ))
+        })
+    }
+
+    /// zero the balances that were written in the settlement file, the ledger entries use the
+    /// settlement id as the transaction id
+    fn finish_settlement(
+        &self,
+        pending: &PendingSettlement,
+        settlement: &SettlementFile,
+    ) -> Result<SettlementSummary, DatabaseError> {
+        let legs: Vec<Leg> = settlement
+            .records
+            .iter()
+            .filter(|record| !record.amount.is_zero())
+            .map(|record| Leg::debit(record.client_id, record.currency, record.amount))
+            .collect();
+        self.commit_request(|state| {
+            let mut entries = state.ledger.prepare(&legs);
+            for entry in entries.iter_mut() {
+                entry.transaction_id = pending.id;
+            }
+            let summary = settlement.summary(pending.id, pending.scheduled_for);
+            let run = SettlementRun {
+                summary: summary.clone(),
+                path: pending.directory.join(&pending.file_name),
+            };
+            Ok((Record::BalancesStored { run, entries }, summary))
+        })
+    }
+
+    /// we crash in the middle of a settlement: if the file was written we finish the settlement
+    /// with the balances of the file, if not we abort it and the balances stay as they are
+    fn recover_settlement(&self, pending: PendingSettlement) -> Result<(), DatabaseError> {
+        match SettlementFile::recover(&pending.directory, &pending.file_name, pending.sequence) {
+            Some(settlement) => {
+                log::warn!(
+                    "completing the interrupted settlement {} ({})",
+                    pending.id,
+                    pending.file_name
+                );
+                self.finish_settlement(&pending, &settlement).map(drop)
+            }
+            None => {
+                log::warn!(
+                    "aborting the interrupted settlement {} ({}), the file was not written",
+                    pending.id,
+                    pending.file_name
+                );
+                self.commit(Record::SettlementAborted {
+                    settlement_id: pending.id,
+                })
+            }
+        }
+    }
+}
+
+impl Storage for Database {
+    fn insert_new_user(&self, new_user: &User) -> Result<Uuid, CreateUserError> {
+        // NOTE(elsuizo: 2025-08-09): the check of the document is inside the commit, so two
+        // clients with the same document created at the same time can not both get in
+        self.commit_request(|state| {
+            if state.documents.contains_key(&new_user.document()) {
+                return Err(CreateUserError::UserAlreadyExistsError(new_user.document()));
+            }
+            let id = Uuid::new_v4();
+            // TODO(elsuizo: 2025-07-12): get rid of this clone
+            let user = new_user.clone();
+            Ok((Record::NewUser { id, user }, id))
+        })
+    }
+
+    fn set_kyc_status(&self, id: Uuid, status: KycStatus) -> Result<(), DatabaseError> {
+        let _account = self.accounts.lock(&id);
+        self.commit_request(|state| {
+            // a closed client is frozen, not even the compliance team can change it
+            state.user(id)?.check_open(id)?;
+            Ok((Record::KycStatusChanged { id, status }, ()))
+        })
+    }
+
+    fn list_users(&self, listing: &ClientListing) -> Result<ClientPage, DatabaseError> {
+        listing.page(self.read()?.users.iter().map(|(id, user)| (*id, user)))
+    }
+
+    fn update_profile(&self, id: Uuid, change: &ProfileChange) -> Result<User, DatabaseError> {
+        let _account = self.accounts.lock(&id);
+        self.commit_with(|state| {
+            let mut user = state.user(id)?.clone();
+            user.check_open(id)?;
+            user.change_profile(change);
+            let change = change.clone();
+            Ok((Record::ProfileChanged { id, change }, user))
+        })
+    }
+
+    fn close_user(&self, id: Uuid, at: DateTime<Utc>) -> Result<User, DatabaseError> {
+        // NOTE(elsuizo: 2025-09-27): with the lock of the account nobody can put money in the
+        // account between the check of the balances and the close
+        let _account = self.accounts.lock(&id);
+        self.commit_request(|state| {
+            let mut user = state.user(id)?.clone();
+            user.close(id, at)?;
+            Ok((Record::UserClosed { id, at }, user))
+        })
+    }
+
+    fn get_balance(&self, id: Uuid, currency: Currency) -> Result<Decimal, DatabaseError> {
+        self.read()?.balance(id, currency)
+    }
+
+    fn open_account(&self, id: Uuid, currency: Currency) -> Result<(), DatabaseError> {
+        let _account = self.accounts.lock(&id);
+        self.read()?.user(id)?.check_open(id)?;
+        match self.get_balance(id, currency) {
+            Ok(_) => Err(DatabaseError::AccountAlreadyExists(id, currency)),
+            Err(DatabaseError::UnknownAccount(_, _)) => {
+                self.commit_request(|_| Ok((Record::NewAccount { id, currency }, ())))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn find_user_and_increase_balance(
+        &self,
+        id: Uuid,
+        currency: Currency,
+        amount: Decimal,
+    ) -> Result<LedgerEntry, DatabaseError> {
+        validate_amount(currency, amount)?;
+        let _account = self.accounts.lock(&id);
+        self.get_balance(id, currency)?;
+        self.check_may_transact(id)?;
+        self.commit_legs(
+            &[Leg::credit(id, currency, amount)],
+            |_| {},
+            |entries| find_entry(entries, id, currency),
+        )
+    }
+
+    fn find_user_and_decrease_balance(
+        &self,
+        id: Uuid,
+        currency: Currency,
+        amount: Decimal,
+    ) -> Result<LedgerEntry, DatabaseError> {
+        validate_amount(currency, amount)?;
+        let _account = self.accounts.lock(&id);
+        let balance = self.available(id, currency)?;
+        self.check_may_transact(id)?;
+        if balance < amount {
+            return Err(DatabaseError::InsufficientBalance(balance));
+        }
+        self.commit_legs(
+            &[Leg::debit(id, currency, amount)],
+            |_| {},
+            |entries| find_entry(entries, id, currency),
+        )
+    }
+
+    fn get_user(&self, id: Uuid) -> Result<User, DatabaseError> {
+        match self.read()?.users.get(&id) {
+            // TODO(elsuizo: 2025-07-13): no clone pleaseee...
+            Some(user) => Ok(user.clone()),
+            None => Err(DatabaseError::UnknownUser(id)),
+        }
+    }
+
+    fn find_user_by_document(&self, document: &Document) -> Result<(Uuid, User), DatabaseError> {
+        let state = self.read()?;
+        state
+            .documents
+            .get(document)
+            .and_then(|id| Some((*id, state.users.get(id)?.clone())))
+            .ok_or_else(|| DatabaseError::UnknownDocument(document.clone()))
+    }
+
+    fn transfer(
+        &self,
+        from: Uuid,
+        to: Uuid,
+        currency: Currency,
+        amount: Decimal,
+    ) -> Result<(LedgerEntry, LedgerEntry), DatabaseError> {
+        if from == to {
+            return Err(DatabaseError::SelfTransfer(from));
+        }
+        validate_amount(currency, amount)?;
+        let _accounts = self.accounts.lock_all([&from, &to]);
+        let balance = self.available(from, currency)?;
+        self.get_balance(to, currency)?;
+        self.check_may_transact(from)?;
+        self.check_may_transact(to)?;
+        if balance < amount {
+            return Err(DatabaseError::InsufficientBalance(balance));
+        }
+        self.commit_legs(
+            &[
+                Leg::debit(from, currency, amount),
+                Leg::credit(to, currency, amount),
+            ],
+            |_| {},
+            |entries| {
+                Ok((
+                    find_entry(entries, from, currency)?,
+                    find_entry(entries, to, currency)?,
+                ))
+            },
+        )
+    }
+
+    fn convert(
+        &self,
+        id: Uuid,
+        from: Currency,
+        to: Currency,
+        amount: Decimal,
+        rate: Decimal,
+    ) -> Result<(LedgerEntry, LedgerEntry), DatabaseError> {
+        if from == to {
+            return Err(DatabaseError::SameCurrencyConversion(from));
+        }
+        if amount <= Decimal::ZERO || !from.is_representable(amount) {
+            return Err(DatabaseError::InvalidAmount(amount));
+        }
+        let _account = self.accounts.lock(&id);
+        let balance = self.available(id, from)?;
+        self.get_balance(id, to)?;
+        self.check_may_transact(id)?;
+        if balance < amount {
+            return Err(DatabaseError::InsufficientBalance(balance));
+        }
+        let converted = to.round(amount * rate);
+        if converted <= Decimal::ZERO {
+            return Err(DatabaseError::InvalidAmount(amount));
+        }
+        self.commit_legs(
+            &[Leg::debit(id, from, amount), Leg::credit(id, to, converted)],
+            |entry| entry.exchange_rate = Some(rate),
+            |entries| Ok((find_entry(entries, id, from)?, find_entry(entries, id, to)?)),
+        )
+    }
+
+    fn place_hold(
+        &self,
+        id: Uuid,
+        currency: Currency,
+        amount: Decimal,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Hold, DatabaseError> {
+        let _account = self.accounts.lock(&id);
+        let available = self.available(id, currency)?;
+        self.check_may_transact(id)?;
+        let hold = Hold::new(id, currency, amount, Utc::now(), expires_at)?;
+        if available < amount {
+            return Err(DatabaseError::InsufficientBalance(available));
+        }
+        self.commit_request(|_| Ok((Record::HoldPlaced { hold: hold.clone() }, hold)))
+    }
+
+    fn get_hold(&self, hold_id: Uuid) -> Result<Hold, DatabaseError> {
+        self.read()?.hold(hold_id).cloned()
+    }
+
+    fn client_holds(&self, id: Uuid) -> Result<Vec<Hold>, DatabaseError> {
+        let state = self.read()?;
+        state.user(id)?;
+        Ok(state.client_holds(id).cloned().collect())
+    }
+
+    fn capture_hold(
+        &self,
+        hold_id: Uuid,
+        amount: Option<Decimal>,
+    ) -> Result<(Hold, LedgerEntry), DatabaseError> {
+        let id = self.get_hold(hold_id)?.client_id;
+        let _account = self.accounts.lock(&id);
+        self.check_may_transact(id)?;
+        self.commit_request(|state| {
+            let mut hold = state.hold(hold_id)?.clone();
+            let amount = hold.capture(amount, Utc::now())?;
+            let balance = state.balance(id, hold.currency)?;
+            if balance < amount {
+                return Err(DatabaseError::InsufficientBalance(balance));
+            }
+            let mut entries = state
+                .ledger
+                .prepare(&[Leg::debit(id, hold.currency, amount)]);
+            for entry in entries.iter_mut() {
+                entry.transaction_id = hold.id;
+            }
+            let entry = find_entry(&entries, id, hold.currency)?;
+            let record = Record::HoldCaptured {
+                hold: hold.clone(),
+                entries,
+            };
+            Ok((record, (hold, entry)))
+        })
+    }
+
+    fn void_hold(&self, hold_id: Uuid) -> Result<Hold, DatabaseError> {
+        let id = self.get_hold(hold_id)?.client_id;
+        let _account = self.accounts.lock(&id);
+        self.commit_request(|state| {
+            let mut hold = state.hold(hold_id)?.clone();
+            hold.void(Utc::now())?;
+            let holds = vec![hold.clone()];
+            Ok((Record::HoldsReleased { holds }, hold))
+        })
+    }
+
+    fn expire_holds(&self, now: DateTime<Utc>) -> Result<usize, DatabaseError> {
+        let expired = |state: &State| {
+            state
+                .holds
+                .values()
+                .filter(|hold| hold.is_expired_at(now))
+                .map(|hold| {
+                    let mut hold = hold.clone();
+                    hold.expire(now);
+                    hold
+                })
+                .collect::<Vec<_>>()
+        };
+        if !self
+            .read()?
+            .holds
+            .values()
+            .any(|hold| hold.is_expired_at(now))
+        {
+            return Ok(0);
+        }
+        // NOTE(elsuizo: 2025-10-18): an expired hold can not be captured or voided any more, so
+        // we don't need the locks of the accounts here
+        self.commit_with(|state| {
+            let holds = expired(state);
+            let count = holds.len();
+            Ok((Record::HoldsReleased { holds }, count))
+        })
+    }
+
+    fn ledger_entries(&self, id: Uuid) -> Result<Vec<LedgerEntry>, DatabaseError> {
+        let state = self.read()?;
+        if state.users.contains_key(&id) {
+            Ok(state.ledger.entries(id).to_vec())
+        } else {
+            Err(DatabaseError::UnknownUser(id))
+        }
+    }
+
+    fn get_saved_response(
+        &self,
+        key: &IdempotencyKey,
+    ) -> Result<Option<SavedResponse>, DatabaseError> {
+        Ok(self.read()?.idempotency.get(key).cloned())
+    }
+
+    fn save_response(
+        &self,
+        key: IdempotencyKey,
+        response: SavedResponse,
+    ) -> Result<(), DatabaseError> {
+        self.commit(Record::SavedResponse { key, response })
+    }
+
+    fn store_balances(&self, directory: &Path) -> Result<SettlementSummary, DatabaseError> {
+        self.settle(directory, None)
+    }
+
+    fn store_scheduled_balances(
+        &self,
+        directory: &Path,
+        run: ScheduledRun,
+    ) -> Result<SettlementSummary, DatabaseError> {
+        self.settle(directory, Some(run))
+    }
+
+    fn settlements(&self) -> Result<Vec<SettlementSummary>, DatabaseError> {
+        Ok(self.read()?.settlements.summaries())
+    }
+
+    fn get_settlement(&self, id: Uuid) -> Result<SettlementRun, DatabaseError> {
+        self.read()?
+            .settlements
+            .find(id)
+            .cloned()
+            .ok_or(DatabaseError::UnknownSettlement(id))
+    }
+}
+
+fn find_entry(
+    entries: &[LedgerEntry],
+    id: Uuid,
+    currency: Currency,
+) -> Result<LedgerEntry, DatabaseError> {
+    entries
+        .iter()
+        .find(|entry| entry.client_id == id && entry.currency == currency)
+        .cloned()
+        .ok_or(DatabaseError::Other)
+}
+
+//-------------------------------------------------------------------------
+//                        unit tests
+//-------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::country::test_country;
+    use crate::currency::Currency;
+    use crate::document::DocumentType;
+    use crate::hold::HoldStatus;
+    use crate::ledger::{Direction, Leg};
+    use crate::local_database::Database;
+    use crate::onboarding::KycStatus;
+    use crate::settlement::ScheduledRun;
+    use crate::storage::Storage;
+    use crate::user::DatabaseError;
+    use crate::user::Document;
+    use crate::user::DocumentNumber;
+    use crate::user::User;
+    use crate::user::UserName;
+    use chrono::{NaiveDate, Utc};
+    use claims::{assert_err, assert_ok};
+    use rust_decimal::dec;
+
+    const ARS: Currency = Currency::Ars;
+
+    #[test]
+    fn insert_new_user() {
+        let name1 = UserName::parse_and_validate("Martin Noblia").expect("error parsing name");
+        let date1 = NaiveDate::parse_from_str("1982-9-27", "%Y-%m-%d").expect("error parsing date");
+        let country1 = test_country("Argentina");
+        // a valid DNI and a valid RUT at the same time
+        let doc1 = Document::parse_and_validate(DocumentType::Dni, &country1, "30111222")
+            .expect("error parsing doc number");
+        let user1 = User::new(name1, date1.into(), doc1, &country1);
+
+        let name2 = UserName::parse_and_validate("Juan Perez").expect("error parsing name");
+        let date2 = NaiveDate::parse_from_str("1982-9-27", "%Y-%m-%d").expect("error parsing date");
+        let country2 = test_country("Chile");
+        let doc2 = Document::parse_and_validate(DocumentType::Dni, &country2, "30111222")
+            .expect("error parsing doc number");
+        let user2 = User::new(name2, date2.into(), doc2, &country2);
+
+        let db = Database::new();
+
+        let result1 = db.insert_new_user(&user1);
+        let result2 = db.insert_new_user(&user2);
+        let result3 = db.insert_new_user(&user1.clone());
+
+        println!("{result1:?}");
+        println!("{result2:?}");
+
+        assert!(result1.is_ok());
+        // the same number in other country is other document
+        assert!(result2.is_ok());
+        assert!(result3.is_err());
+    }
+
+    fn test_user() -> User {
+        let name = UserName::parse_and_validate("Martin Noblia").expect("error parsing name");
+        let date = NaiveDate::parse_from_str("1982-9-27", "%Y-%m-%d").expect("error parsing date");
+        let country = test_country("Argentina");
+        let doc = Document::parse_and_validate(DocumentType::Dni, &country, "29653164")
+            .expect("error parsing doc number");
+        let mut user = User::new(name, date.into(), doc, &country);
+        user.set_kyc_status(KycStatus::Verified);
+        user
+    }
+
+    fn other_test_user() -> User {
+        let name = UserName::parse_and_validate("Juan Perez").expect("error parsing name");
+        let date = NaiveDate::parse_from_str("1990-1-2", "%Y-%m-%d").expect("error parsing date");
+        let country = test_country("Chile");
+        let doc = Document::parse_and_validate(DocumentType::Dni, &country, "3.011.122-2")
+            .expect("error parsing doc number");
+        let mut user = User::new(name, date.into(), doc, &country);
+        user.set_kyc_status(KycStatus::Verified);
+        user
+    }
+
+    fn test_directory() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("mini-payment-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn the_log_is_replayed_on_open() {
+        let path = test_directory();
+        let db = Database::open(&path, 1000).expect("error opening the database");
+        let id = db
+            .insert_new_user(&test_user())
+            .expect("error inserting user");
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(10.5)));
+        assert_ok!(db.find_user_and_decrease_balance(id, ARS, dec!(0.5)));
+        assert_err!(db.find_user_and_decrease_balance(id, ARS, dec!(100)));
+        drop(db);
+
+        let db = Database::open(&path, 1000).expect("error opening the database");
+        let user = db.get_user(id).expect("the user was not recovered");
+        assert_eq!(user.get_actual_credit(ARS).unwrap(), dec!(10));
+    }
+
+    #[test]
+    fn only_positive_amounts_are_credited_or_debited() {
+        let db = Database::new();
+        let id = db.insert_new_user(&test_user()).unwrap();
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(10)));
+
+        for amount in [dec!(0), dec!(-50)] {
+            assert!(matches!(
+                db.find_user_and_increase_balance(id, ARS, amount),
+                Err(DatabaseError::InvalidAmount(_))
+            ));
+            assert!(matches!(
+                db.find_user_and_decrease_balance(id, ARS, amount),
+                Err(DatabaseError::InvalidAmount(_))
+            ));
+        }
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(10));
+        assert_eq!(db.ledger_entries(id).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn the_amounts_must_fit_in_the_minor_units_of_the_currency() {
+        let db = Database::new();
+        let id = db.insert_new_user(&test_user()).unwrap();
+        let other = db.insert_new_user(&other_test_user()).unwrap();
+        assert_ok!(db.open_account(other, ARS));
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(30)));
+
+        for amount in [dec!(0.0001), dec!(1.001)] {
+            assert_err!(db.find_user_and_increase_balance(id, ARS, amount));
+            assert_err!(db.find_user_and_decrease_balance(id, ARS, amount));
+            assert_err!(db.transfer(id, other, ARS, amount));
+        }
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(0.010)));
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(30.01));
+        // the currencies without minor units only take whole amounts
+        assert_err!(db.find_user_and_increase_balance(other, Currency::Clp, dec!(0.5)));
+    }
+
+    #[test]
+    fn snapshots_and_log_are_combined_on_open() {
+        let path = test_directory();
+        let db = Database::open(&path, 2).expect("error opening the database");
+        let id = db
+            .insert_new_user(&test_user())
+            .expect("error inserting user");
+        for _ in 0..5 {
+            assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(1)));
+        }
+        drop(db);
+
+        let db = Database::open(&path, 2).expect("error opening the database");
+        let user = db.get_user(id).expect("the user was not recovered");
+        assert_eq!(user.get_actual_credit(ARS).unwrap(), dec!(5));
+    }
+
+    #[test]
+    fn the_document_index_is_rebuilt_on_open() {
+        let path = test_directory();
+        // the first user ends in the snapshot and the second only in the log
+        let db = Database::open(&path, 1).expect("error opening the database");
+        let id = db.insert_new_user(&test_user()).unwrap();
+        let other_id = db.insert_new_user(&other_test_user()).unwrap();
+        drop(db);
+
+        let db = Database::open(&path, 100).expect("error opening the database");
+        let (found, _) = db.find_user_by_document(&test_user().document()).unwrap();
+        assert_eq!(found, id);
+        let document = other_test_user().document();
+        let (found, _) = db.find_user_by_document(&document).unwrap();
+        assert_eq!(found, other_id);
+        assert_err!(db.insert_new_user(&test_user()));
+        let unknown = Document::from_stored(
+            document.document_type(),
+            document.issuing_country().clone(),
+            DocumentNumber::from_stored("1-9".into()),
+        );
+        assert!(matches!(
+            db.find_user_by_document(&unknown),
+            Err(DatabaseError::UnknownDocument(_))
+        ));
+    }
+
+    #[test]
+    fn only_the_verified_clients_move_money() {
+        let path = test_directory();
+        let db = Database::open(&path, 1000).expect("error opening the database");
+        let verified = db.insert_new_user(&test_user()).unwrap();
+        let mut pending = other_test_user();
+        pending.set_kyc_status(KycStatus::Pending);
+        let pending = db.insert_new_user(&pending).unwrap();
+        assert_ok!(db.open_account(pending, ARS));
+        assert_ok!(db.find_user_and_increase_balance(verified, ARS, dec!(10)));
+
+        assert!(matches!(
+            db.find_user_and_increase_balance(pending, ARS, dec!(1)),
+            Err(DatabaseError::ClientNotVerified(_, KycStatus::Pending))
+        ));
+        assert_err!(db.transfer(verified, pending, ARS, dec!(1)));
+        assert_ok!(db.set_kyc_status(pending, KycStatus::Verified));
+        assert_ok!(db.transfer(verified, pending, ARS, dec!(1)));
+        assert_ok!(db.set_kyc_status(verified, KycStatus::Rejected));
+        assert_err!(db.find_user_and_decrease_balance(verified, ARS, dec!(1)));
+        drop(db);
+
+        let db = Database::open(&path, 1000).expect("error opening the database");
+        assert_eq!(
+            db.get_user(verified).unwrap().kyc_status(),
+            KycStatus::Rejected
+        );
+        assert_err!(db.set_kyc_status(uuid::Uuid::new_v4(), KycStatus::Verified));
+    }
+
+    #[test]
+    fn the_same_number_with_other_type_is_other_document() {
+        let db = Database::new();
+        let argentina = test_country("Argentina");
+        let passport =
+            Document::parse_and_validate(DocumentType::Passport, &argentina, "29653164").unwrap();
+        let name = UserName::parse_and_validate("Martin Noblia").unwrap();
+        let date = NaiveDate::parse_from_str("1982-9-27", "%Y-%m-%d").unwrap();
+        // he lives in Chile with his argentinian passport
+        let chile = test_country("Chile");
+        let traveller = User::new(name, date.into(), passport.clone(), &chile);
+
+        let id = db.insert_new_user(&test_user()).unwrap();
+        let traveller_id = db.insert_new_user(&traveller).unwrap();
+        assert_err!(db.insert_new_user(&traveller));
+
+        let (found, user) = db.find_user_by_document(&passport).unwrap();
+        assert_eq!(found, traveller_id);
+        assert_eq!(user.get_issuing_country(), &argentina.name());
+        let (found, _) = db.find_user_by_document(&test_user().document()).unwrap();
+        assert_eq!(found, id);
+    }
+
+    #[test]
+    fn balances_can_be_rebuilt_from_the_ledger() {
+        let db = Database::new();
+        let id = db
+            .insert_new_user(&test_user())
+            .expect("error inserting user");
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(10)));
+        let entry = db
+            .find_user_and_decrease_balance(id, ARS, dec!(4))
+            .expect("error decreasing the balance");
+        assert_eq!(entry.direction, Direction::Debit);
+        assert_eq!(entry.balance, dec!(6));
+
+        assert_eq!(db.ledger_entries(id).unwrap().len(), 2);
+        assert_eq!(db.rebuild_balance(id, ARS).unwrap(), dec!(6));
+        assert_err!(db.rebuild_balance(uuid::Uuid::new_v4(), ARS));
+    }
+
+    #[test]
+    fn transfers_move_money_between_users() {
+        let db = Database::new();
+        let from = db
+            .insert_new_user(&test_user())
+            .expect("error inserting user");
+        let to = db
+            .insert_new_user(&other_test_user())
+            .expect("error inserting user");
+        assert_ok!(db.find_user_and_increase_balance(from, ARS, dec!(10)));
+        // the user from Chile has only an account in CLP
+        assert!(matches!(
+            db.transfer(from, to, ARS, dec!(7)),
+            Err(DatabaseError::UnknownAccount(id, ARS)) if id == to
+        ));
+        assert_ok!(db.open_account(to, ARS));
+        assert_err!(db.open_account(to, ARS));
+
+        let (from_entry, to_entry) = db
+            .transfer(from, to, ARS, dec!(7))
+            .expect("error transferring");
+        assert_eq!(from_entry.balance, dec!(3));
+        assert_eq!(to_entry.balance, dec!(7));
+        assert_eq!(from_entry.transaction_id, to_entry.transaction_id);
+
+        // the failed ones don't touch the balances
+        assert_err!(db.transfer(from, to, ARS, dec!(4)));
+        assert_err!(db.transfer(from, from, ARS, dec!(1)));
+        assert_err!(db.transfer(from, to, ARS, dec!(-1)));
+        assert_err!(db.transfer(from, uuid::Uuid::new_v4(), ARS, dec!(1)));
+        assert_eq!(
+            db.get_user(from).unwrap().get_actual_credit(ARS).unwrap(),
+            dec!(3)
+        );
+        assert_eq!(
+            db.get_user(to).unwrap().get_actual_credit(ARS).unwrap(),
+            dec!(7)
+        );
+        assert_eq!(db.rebuild_balance(to, ARS).unwrap(), dec!(7));
+    }
+
+    #[test]
+    fn concurrent_transfers_in_both_directions_keep_the_total() {
+        let db = Database::new();
+        let a = db.insert_new_user(&test_user()).unwrap();
+        let b = db.insert_new_user(&other_test_user()).unwrap();
+        assert_ok!(db.open_account(b, ARS));
+        assert_ok!(db.find_user_and_increase_balance(a, ARS, dec!(100)));
+        assert_ok!(db.find_user_and_increase_balance(b, ARS, dec!(100)));
+
+        std::thread::scope(|scope| {
+            for (from, to) in [(a, b), (b, a), (a, b), (b, a)] {
+                let db = &db;
+                scope.spawn(move || {
+                    for _ in 0..200 {
+                        // some of them fail for the balance, that is fine
+                        let _ = db.transfer(from, to, ARS, dec!(1));
+                    }
+                });
+            }
+        });
+
+        let total = db.get_balance(a, ARS).unwrap() + db.get_balance(b, ARS).unwrap();
+        assert_eq!(total, dec!(200));
+        assert_eq!(
+            db.rebuild_balance(a, ARS).unwrap(),
+            db.get_balance(a, ARS).unwrap()
+        );
+        assert_eq!(
+            db.rebuild_balance(b, ARS).unwrap(),
+            db.get_balance(b, ARS).unwrap()
+        );
+    }
+
+    #[test]
+    fn conversions_apply_the_rate_and_round_down() {
+        let db = Database::new();
+        let id = db
+            .insert_new_user(&test_user())
+            .expect("error inserting user");
+        assert_ok!(db.open_account(id, Currency::Clp));
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(100)));
+
+        let (from_entry, to_entry) = db
+            .convert(id, ARS, Currency::Clp, dec!(10.55), dec!(0.752))
+            .expect("error converting");
+        assert_eq!(from_entry.balance, dec!(89.45));
+        // 10.55 * 0.752 = 7.9336 and CLP has no minor units
+        assert_eq!(to_entry.amount, dec!(7));
+        assert_eq!(to_entry.exchange_rate, Some(dec!(0.752)));
+        assert_eq!(from_entry.transaction_id, to_entry.transaction_id);
+
+        assert_err!(db.convert(id, ARS, ARS, dec!(1), dec!(1)));
+        assert_err!(db.convert(id, ARS, Currency::Clp, dec!(0.001), dec!(1)));
+        assert_err!(db.convert(id, ARS, Currency::Clp, dec!(1000), dec!(1)));
+        assert_err!(db.convert(id, ARS, Currency::Usd, dec!(1), dec!(1)));
+        assert_eq!(db.rebuild_balance(id, Currency::Clp).unwrap(), dec!(7));
+    }
+
+    #[test]
+    fn a_failed_settlement_does_not_touch_the_balances() {
+        let path = test_directory();
+        let db = Database::open(&path, 1000).expect("error opening the database");
+        let id = db
+            .insert_new_user(&test_user())
+            .expect("error inserting user");
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(3)));
+        // a file where the directory of the settlements should be
+        let not_a_directory = path.join("wal.log");
+
+        assert_err!(db.store_balances(&not_a_directory));
+
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(3));
+        let summary = db
+            .store_balances(&path.join("settlements"))
+            .expect("error storing the balances");
+        assert_eq!(summary.sequence, 1);
+        assert_eq!(db.settlements().unwrap(), vec![summary.clone()]);
+        assert!(db.get_settlement(summary.id).unwrap().path.exists());
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(0));
+        assert_eq!(db.ledger_entries(id).unwrap()[1].transaction_id, summary.id);
+    }
+
+    #[test]
+    fn a_scheduled_run_already_settled_by_hand_does_nothing() {
+        let path = test_directory();
+        let directory = path.join("settlements");
+        let db = Database::open(&path, 1000).expect("error opening the database");
+        let id = db
+            .insert_new_user(&test_user())
+            .expect("error inserting user");
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(3)));
+        let run = ScheduledRun {
+            window_start: Utc::now() - chrono::Duration::hours(1),
+            fire_time: Utc::now() + chrono::Duration::hours(1),
+        };
+
+        let manual = db.store_balances(&directory).unwrap();
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(2)));
+        assert!(matches!(
+            db.store_scheduled_balances(&directory, run),
+            Err(DatabaseError::AlreadySettled(settlement_id)) if settlement_id == manual.id
+        ));
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(2));
+        assert_eq!(db.settlements().unwrap(), vec![manual]);
+
+        // the next window is not settled yet
+        let next = ScheduledRun {
+            window_start: run.fire_time,
+            fire_time: run.fire_time + chrono::Duration::days(1),
+        };
+        let scheduled = db.store_scheduled_balances(&directory, next).unwrap();
+        assert_eq!(scheduled.scheduled_for, Some(next.fire_time));
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(0));
+    }
+
+    #[test]
+    fn an_interrupted_settlement_with_the_file_written_is_completed_on_open() {
+        let path = test_directory();
+        let directory = path.join("settlements");
+        let db = Database::open(&path, 1000).expect("error opening the database");
+        let id = db
+            .insert_new_user(&test_user())
+            .expect("error inserting user");
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(3)));
+        let fire_time = Utc::now();
+        let (pending, settlement) = db.start_settlement(&directory, Some(fire_time)).unwrap();
+        settlement.write_atomically(&directory).unwrap();
+        // crash before the balances are stored
+        drop(db);
+
+        let db = Database::open(&path, 1000).expect("error opening the database");
+        assert!(db.read().unwrap().pending_settlement.is_none());
+        assert_eq!(db.read().unwrap().settlement_sequence, 1);
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(0));
+        assert_eq!(db.ledger_entries(id).unwrap()[1].transaction_id, pending.id);
+        assert_eq!(db.settlements().unwrap()[0].id, pending.id);
+        assert_eq!(db.settlements().unwrap()[0].scheduled_for, Some(fire_time));
+    }
+
+    #[test]
+    fn an_interrupted_settlement_without_file_is_aborted_on_open() {
+        let path = test_directory();
+        let directory = path.join("settlements");
+        let db = Database::open(&path, 1000).expect("error opening the database");
+        let id = db
+            .insert_new_user(&test_user())
+            .expect("error inserting user");
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(3)));
+        let (pending, _) = db.start_settlement(&directory, None).unwrap();
+        // crash in the middle of the write
+        std::fs::create_dir_all(&directory).unwrap();
+        let tmp = crate::settlement::tmp_path(&directory, &pending.file_name);
+        std::fs::write(&tmp, "HDR|MPSF|1|").unwrap();
+        drop(db);
+
+        let db = Database::open(&path, 1000).expect("error opening the database");
+        assert!(db.read().unwrap().pending_settlement.is_none());
+        assert!(!tmp.exists());
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(3));
+        assert!(db.settlements().unwrap().is_empty());
+        // the sequence number was not used
+        let summary = db.store_balances(&directory).unwrap();
+        assert_eq!(summary.sequence, 1);
+    }
+
+    #[test]
+    fn a_record_that_can_not_be_applied_is_not_written() {
+        let path = test_directory();
+        let db = Database::open(&path, 1000).expect("error opening the database");
+        let id = db
+            .insert_new_user(&test_user())
+            .expect("error inserting user");
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(3)));
+
+        assert!(matches!(
+            db.commit_legs(&[Leg::debit(id, ARS, dec!(5))], |_| {}, |_| Ok(())),
+            Err(DatabaseError::InsufficientBalance(_))
+        ));
+        // the first leg could be applied but the second one not
+        assert!(matches!(
+            db.commit_legs(
+                &[
+                    Leg::credit(id, ARS, dec!(1)),
+                    Leg::debit(id, Currency::Usd, dec!(1))
+                ],
+                |_| {},
+                |_| Ok(())
+            ),
+            Err(DatabaseError::UnknownAccount(_, Currency::Usd))
+        ));
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(3));
+        assert_eq!(db.ledger_entries(id).unwrap().len(), 1);
+        drop(db);
+
+        let db = Database::open(&path, 1000).expect("error opening the database");
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(3));
+        assert_eq!(db.ledger_entries(id).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn a_torn_record_at_the_end_of_the_log_is_discarded() {
+        let path = test_directory();
+        let db = Database::open(&path, 1000).expect("error opening the database");
+        let id = db
+            .insert_new_user(&test_user())
+            .expect("error inserting user");
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(3)));
+        drop(db);
+
+        let mut log = std::fs::OpenOptions::new()
+            .append(true)
+            .open(path.join("wal.log"))
+            .unwrap();
+        std::io::Write::write_all(&mut log, br#"{"lsn":3,"record":{"Cre"#).unwrap();
+
+        let db = Database::open(&path, 1000).expect("error opening the database");
+        assert_eq!(
+            db.get_user(id).unwrap().get_actual_credit(ARS).unwrap(),
+            dec!(3)
+        );
+        // the log is usable again after the recovery
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(1)));
+        drop(db);
+        let db = Database::open(&path, 1000).expect("error opening the database");
+        assert_eq!(
+            db.get_user(id).unwrap().get_actual_credit(ARS).unwrap(),
+            dec!(4)
+        );
+    }
+
+    #[test]
+    fn the_holds_reserve_money_and_survive_a_restart() {
+        let path = test_directory();
+        let db = Database::open(&path, 1000).expect("error opening the database");
+        let id = db.insert_new_user(&test_user()).unwrap();
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(10)));
+        let expires_at = Utc::now() + chrono::Duration::hours(1);
+        let captured = db.place_hold(id, ARS, dec!(6), expires_at).unwrap();
+        let voided = db.place_hold(id, ARS, dec!(3), expires_at).unwrap();
+
+        assert!(matches!(
+            db.find_user_and_decrease_balance(id, ARS, dec!(2)),
+            Err(DatabaseError::InsufficientBalance(available)) if available == dec!(1)
+        ));
+        assert_err!(db.place_hold(id, ARS, dec!(2), expires_at));
+        let (hold, entry) = db.capture_hold(captured.id, Some(dec!(5))).unwrap();
+        assert_eq!(hold.status, HoldStatus::Captured);
+        assert_eq!(entry.balance, dec!(5));
+        assert_eq!(entry.transaction_id, captured.id);
+        assert_err!(db.capture_hold(captured.id, None));
+        assert_ok!(db.void_hold(voided.id));
+        assert_ok!(db.find_user_and_decrease_balance(id, ARS, dec!(2)));
+        drop(db);
+
+        let db = Database::open(&path, 1000).expect("error opening the database");
+        let holds = db.client_holds(id).unwrap();
+        assert_eq!(holds, vec![hold, db.get_hold(voided.id).unwrap()]);
+        assert_eq!(holds[1].status, HoldStatus::Voided);
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(3));
+        assert_eq!(db.rebuild_balance(id, ARS).unwrap(), dec!(3));
+    }
+
+    #[test]
+    fn the_expired_holds_release_the_money_and_are_not_settled_before() {
+        let path = test_directory();
+        let db = Database::open(&path, 1000).expect("error opening the database");
+        let id = db.insert_new_user(&test_user()).unwrap();
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(10)));
+        let expires_at = Utc::now() + chrono::Duration::hours(1);
+        let hold = db.place_hold(id, ARS, dec!(4), expires_at).unwrap();
+
+        let summary = db.store_balances(&path.join("settlements")).unwrap();
+        assert_eq!(summary.totals[&ARS].total, dec!(6));
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(4));
+        assert_err!(db.find_user_and_decrease_balance(id, ARS, dec!(1)));
+
+        assert_eq!(db.expire_holds(Utc::now()).unwrap(), 0);
+        assert_eq!(db.expire_holds(expires_at).unwrap(), 1);
+        assert_eq!(db.expire_holds(expires_at).unwrap(), 0);
+        assert_eq!(db.get_hold(hold.id).unwrap().status, HoldStatus::Expired);
+        assert_ok!(db.find_user_and_decrease_balance(id, ARS, dec!(4)));
+    }
+}
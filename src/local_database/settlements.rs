@@ -0,0 +1,30 @@
+use crate::settlement::SettlementSummary;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// one settlement that finished, with the place where the file was written
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SettlementRun {
+    pub summary: SettlementSummary,
+    pub path: PathBuf,
+}
+
+/// history of all the settlements in the order that they were done
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct SettlementRegistry {
+    runs: Vec<SettlementRun>,
+}
+
+impl SettlementRegistry {
+    pub fn push(&mut self, run: SettlementRun) {
+        self.runs.push(run);
+    }
+
+    pub fn summaries(&self) -> Vec<SettlementSummary> {
+        self.runs.iter().map(|run| run.summary.clone()).collect()
+    }
+
+    pub fn find(&self, id: Uuid) -> Option<&SettlementRun> {
+        self.runs.iter().find(|run| run.summary.id == id)
+    }
+}
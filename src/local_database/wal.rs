@@ -0,0 +1,233 @@
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+const LOG_FILE_NAME: &str = "wal.log";
+const SNAPSHOT_FILE_NAME: &str = "snapshot.json";
+const SNAPSHOT_TMP_FILE_NAME: &str = "snapshot.json.tmp";
+
+/// one line of the log: the record plus its log sequence number
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Entry<R> {
+    lsn: u64,
+    record: R,
+}
+
+/// a full copy of the state at the moment that the record `lsn` was applied
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Snapshot<T> {
+    lsn: u64,
+    state: T,
+}
+
+/// what we found on disk when the log was opened
+pub struct Recovered<T, R> {
+    pub state: Option<T>,
+    pub records: Vec<R>,
+}
+
+/// Append only log of json lines, every record is fsync'ed before `append` returns so once a
+/// mutation is acknowledged it survives a crash. Every `snapshot_interval` records the whole state
+/// is written to a snapshot file and the log is truncated.
+#[derive(Debug)]
+pub struct Wal {
+    directory: PathBuf,
+    log: File,
+    /// length of the log up to the end of the last acknowledged record
+    len: u64,
+    /// an append failed and we could not remove what it wrote, see `discard_unacknowledged`
+    failed: bool,
+    next_lsn: u64,
+    records_since_snapshot: usize,
+    snapshot_interval: usize,
+}
+
+impl Wal {
+    /// open (or create) the log living in `directory` and return the last snapshot plus all the
+    /// records that were written after it
+    pub fn open<T, R>(
+        directory: impl AsRef<Path>,
+        snapshot_interval: usize,
+    ) -> io::Result<(Self, Recovered<T, R>)>
+    where
+        T: DeserializeOwned,
+        R: DeserializeOwned,
+    {
+        let directory = directory.as_ref().to_path_buf();
+        fs::create_dir_all(&directory)?;
+        // NOTE(elsuizo: 2025-07-20): a leftover tmp file means that we crash in the middle of a
+        // snapshot, the previous snapshot and the log are still valid so we just drop it
+        let _ = fs::remove_file(directory.join(SNAPSHOT_TMP_FILE_NAME));
+
+        let (snapshot_lsn, state) = match fs::read(directory.join(SNAPSHOT_FILE_NAME)) {
+            Ok(bytes) => {
+                let snapshot: Snapshot<T> = serde_json::from_slice(&bytes).map_err(invalid_data)?;
+                (snapshot.lsn, Some(snapshot.state))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (0, None),
+            Err(e) => return Err(e),
+        };
+
+        let mut log = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .append(true)
+            .open(directory.join(LOG_FILE_NAME))?;
+        let mut content = String::new();
+        log.read_to_string(&mut content)?;
+
+        let mut records = Vec::new();
+        let mut next_lsn = snapshot_lsn + 1;
+        let mut valid_len = 0;
+        for line in content.split_inclusive('\n') {
+            if !line.ends_with('\n') {
+                // the last write was torn by a crash, it was never acknowledged so we drop it
+                break;
+            }
+            let entry: Entry<R> = serde_json::from_str(line).map_err(invalid_data)?;
+            valid_len += line.len();
+            // records already included in the snapshot (we crash before truncating the log)
+            if entry.lsn <= snapshot_lsn {
+                continue;
+            }
+            next_lsn = entry.lsn + 1;
+            records.push(entry.record);
+        }
+        if valid_len < content.len() {
+            log.set_len(valid_len as u64)?;
+            log.sync_all()?;
+        }
+
+        let wal = Self {
+            directory,
+            log,
+            len: valid_len as u64,
+            failed: false,
+            next_lsn,
+            records_since_snapshot: records.len(),
+            snapshot_interval,
+        };
+        Ok((wal, Recovered { state, records }))
+    }
+
+    /// write the record and wait until it reach the disk
+    pub fn append<R: Serialize>(&mut self, record: &R) -> io::Result<()> {
+        if self.failed {
+            return Err(io::Error::other(
+                "the log has a record that was not acknowledged",
+            ));
+        }
+        let entry = Entry {
+            lsn: self.next_lsn,
+            record,
+        };
+        let mut line = serde_json::to_vec(&entry).map_err(invalid_data)?;
+        line.push(b'\n');
+        if let Err(e) = self
+            .log
+            .write_all(&line)
+            .and_then(|()| self.log.sync_data())
+        {
+            self.discard_unacknowledged();
+            return Err(e);
+        }
+        self.len += line.len() as u64;
+        self.next_lsn += 1;
+        self.records_since_snapshot += 1;
+        Ok(())
+    }
+
+    // NOTE(elsuizo: 2025-10-18): a write that fails partway leaves half a line at the end of the
+    // log, the next records would be appended after it and the log could not be opened again
+    /// remove what a failed `append` wrote, if we can not do it the log refuses any other record
+    fn discard_unacknowledged(&mut self) {
+        if let Err(e) = self
+            .log
+            .set_len(self.len)
+            .and_then(|()| self.log.sync_data())
+        {
+            log::error!("failed to discard a record that was not acknowledged: {e}");
+            self.failed = true;
+        }
+    }
+
+    pub fn needs_snapshot(&self) -> bool {
+        self.records_since_snapshot >= self.snapshot_interval
+    }
+
+    /// persist the whole `state` and truncate the log, the snapshot is written in a tmp file and
+    /// renamed so a crash leaves either the old or the new snapshot on disk
+    pub fn snapshot<T: Serialize>(&mut self, state: &T) -> io::Result<()> {
+        let snapshot = Snapshot {
+            lsn: self.next_lsn - 1,
+            state,
+        };
+        let tmp_path = self.directory.join(SNAPSHOT_TMP_FILE_NAME);
+        let mut file = File::create(&tmp_path)?;
+        serde_json::to_writer(&mut file, &snapshot).map_err(invalid_data)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, self.directory.join(SNAPSHOT_FILE_NAME))?;
+        sync_directory(&self.directory)?;
+
+        self.log.set_len(0)?;
+        self.len = 0;
+        self.log.sync_all()?;
+        self.records_since_snapshot = 0;
+        Ok(())
+    }
+}
+
+fn invalid_data(e: serde_json::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// make the rename durable
+fn sync_directory(directory: &Path) -> io::Result<()> {
+    File::open(directory)?.sync_all()
+}
+
+//-------------------------------------------------------------------------
+//                        unit tests
+//-------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::local_database::wal::Wal;
+    use claims::{assert_err, assert_ok};
+    use std::io::Write;
+
+    fn test_directory() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("mini-payment-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn a_failed_append_does_not_leave_half_a_record() {
+        let path = test_directory();
+        let (mut wal, _) = Wal::open::<(), u32>(&path, 1000).expect("error opening the log");
+        assert_ok!(wal.append(&1u32));
+
+        // what a write that fails partway leaves in the log
+        wal.log.write_all(br#"{"lsn":2,"rec"#).unwrap();
+        wal.discard_unacknowledged();
+        assert!(!wal.failed);
+        assert_ok!(wal.append(&2u32));
+        drop(wal);
+
+        let (_, recovered) = Wal::open::<(), u32>(&path, 1000).expect("error opening the log");
+        assert_eq!(recovered.records, vec![1, 2]);
+    }
+
+    #[test]
+    fn the_log_refuses_records_after_an_append_that_could_not_be_discarded() {
+        let path = test_directory();
+        let (mut wal, _) = Wal::open::<(), u32>(&path, 1000).expect("error opening the log");
+        wal.failed = true;
+        assert_err!(wal.append(&1u32));
+        drop(wal);
+
+        let (_, recovered) = Wal::open::<(), u32>(&path, 1000).expect("error opening the log");
+        assert!(recovered.records.is_empty());
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// A fixed set of locks where every key is mapped to one of them (by its hash), so we can lock
+/// one client (or one idempotency key) without a lock for every one of them and without blocking
+/// the others. Two keys can share a stripe, that only means that they wait for each other.
+///
+/// When more than one key is locked the stripes are always taken in ascending order, so two
+/// operations that lock the same keys in different order can not deadlock
+#[derive(Debug)]
+pub struct StripedLocks {
+    stripes: Vec<Mutex<()>>,
+}
+
+/// the stripes locked by `StripedLocks::lock_all`, they are released when this is dropped
+#[must_use]
+pub struct StripedGuard<'a> {
+    _guards: Vec<MutexGuard<'a, ()>>,
+}
+
+impl StripedLocks {
+    pub const DEFAULT_STRIPES: usize = 64;
+
+    pub fn new(stripes: usize) -> Self {
+        Self {
+            stripes: (0..stripes.max(1)).map(|_| Mutex::new(())).collect(),
+        }
+    }
+
+    fn stripe_of(&self, key: &impl Hash) -> usize {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        (hasher.finish() % self.stripes.len() as u64) as usize
+    }
+
+    fn lock_stripe(&self, stripe: usize) -> MutexGuard<'_, ()> {
+        // NOTE(elsuizo: 2025-08-09): the locks don't protect any data, so a panic of other thread
+        // while it was holding one can not leave anything half written and we can keep using it
+        self.stripes[stripe]
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn lock<K: Hash>(&self, key: &K) -> StripedGuard<'_> {
+        self.lock_all([key])
+    }
+
+    /// lock all the `keys` at the same time
+    pub fn lock_all<'k, K: Hash + 'k>(
+        &self,
+        keys: impl IntoIterator<Item = &'k K>,
+    ) -> StripedGuard<'_> {
+        let mut stripes: Vec<usize> = keys.into_iter().map(|key| self.stripe_of(key)).collect();
+        stripes.sort_unstable();
+        stripes.dedup();
+        StripedGuard {
+            _guards: stripes
+                .into_iter()
+                .map(|stripe| self.lock_stripe(stripe))
+                .collect(),
+        }
+    }
+
+    /// lock every key, for the operations that touch all of them
+    pub fn lock_everything(&self) -> StripedGuard<'_> {
+        StripedGuard {
+            _guards: (0..self.stripes.len())
+                .map(|stripe| self.lock_stripe(stripe))
+                .collect(),
+        }
+    }
+}
+
+impl Default for StripedLocks {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_STRIPES)
+    }
+}
+
+//-------------------------------------------------------------------------
+//                        unit tests
+//-------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::locks::StripedLocks;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+    use uuid::Uuid;
+
+    #[test]
+    fn opposite_transfers_do_not_deadlock() {
+        let locks = Arc::new(StripedLocks::default());
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let barrier = Arc::new(Barrier::new(2));
+        let handles: Vec<_> = [(a, b), (b, a)]
+            .into_iter()
+            .map(|(from, to)| {
+                let locks = locks.clone();
+                let barrier = barrier.clone();
+                thread::spawn(move || {
+                    barrier.wait();
+                    for _ in 0..1000 {
+                        let _guard = locks.lock_all([&from, &to]);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn the_same_key_twice_is_locked_once() {
+        let locks = StripedLocks::new(4);
+        let id = Uuid::new_v4();
+        let _guard = locks.lock_all([&id, &id]);
+    }
+
+    #[test]
+    fn a_poisoned_stripe_can_be_locked_again() {
+        let locks = Arc::new(StripedLocks::new(1));
+        let poisoner = locks.clone();
+        let _ = thread::spawn(move || {
+            let _guard = poisoner.lock(&1);
+            panic!("poison the lock");
+        })
+        .join();
+
+        let _guard = locks.lock(&1);
+    }
+}
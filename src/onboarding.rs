@@ -0,0 +1,208 @@
+use crate::configuration::OnboardingSettings;
+use crate::country::{Country, CountryRegistry};
+use crate::user::{CountryName, CreateUserError};
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// where a client is in the know your customer process, only the verified clients can move money
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KycStatus {
+    /// we are still checking who the client is
+    Pending,
+    Verified,
+    Rejected,
+}
+
+impl KycStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Verified => "verified",
+            Self::Rejected => "rejected",
+        }
+    }
+
+    pub fn may_transact(self) -> bool {
+        self == Self::Verified
+    }
+
+    // NOTE(elsuizo: 2025-09-06): the clients created before the KYC were already moving money
+    pub(crate) fn of_old_clients() -> Self {
+        Self::Verified
+    }
+}
+
+impl fmt::Display for KycStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for KycStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(Self::Pending),
+            "verified" => Ok(Self::Verified),
+            "rejected" => Ok(Self::Rejected),
+            _ => Err(format!("unknown kyc status: {s}")),
+        }
+    }
+}
+
+/// The rules that a new client must pass before we save him, they run before
+/// `Storage::insert_new_user`
+#[derive(Debug, Clone)]
+pub struct OnboardingRules {
+    minimum_age: u32,
+    minimum_age_by_country: HashMap<CountryName, u32>,
+    maximum_age: u32,
+    kyc_status_of_new_clients: KycStatus,
+}
+
+impl OnboardingRules {
+    /// the countries of the settings can be written with the name or the code of the registry
+    pub fn from_settings(
+        settings: &OnboardingSettings,
+        countries: &CountryRegistry,
+    ) -> Result<Self, CreateUserError> {
+        let minimum_age_by_country = settings
+            .minimum_age_by_country
+            .iter()
+            .map(|(country, age)| Ok((countries.parse_and_validate(country)?.name(), *age)))
+            .collect::<Result<_, CreateUserError>>()?;
+        Ok(Self {
+            minimum_age: settings.minimum_age,
+            minimum_age_by_country,
+            maximum_age: settings.maximum_age,
+            kyc_status_of_new_clients: settings.kyc_status_of_new_clients,
+        })
+    }
+
+    /// the minimum age of the clients of `country`
+    pub fn minimum_age(&self, country: &Country) -> u32 {
+        self.minimum_age_by_country
+            .get(&country.name())
+            .copied()
+            .unwrap_or(self.minimum_age)
+    }
+
+    /// check that a client born in `birth_date` can be a client in `country` the day `today`,
+    /// return the kyc status in which the client starts
+    pub fn check(
+        &self,
+        birth_date: NaiveDate,
+        country: &Country,
+        today: NaiveDate,
+    ) -> Result<KycStatus, CreateUserError> {
+        let age = today
+            .years_since(birth_date)
+            .ok_or(CreateUserError::BirthDateInTheFuture(birth_date))?;
+        if age > self.maximum_age {
+            return Err(CreateUserError::ImplausibleBirthDate(birth_date));
+        }
+        let minimum_age = self.minimum_age(country);
+        if age < minimum_age {
+            return Err(CreateUserError::UnderMinimumAge(
+                minimum_age,
+                country.name().inner(),
+            ));
+        }
+        Ok(self.kyc_status_of_new_clients)
+    }
+}
+
+//-------------------------------------------------------------------------
+//                        unit tests
+//-------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::configuration::OnboardingSettings;
+    use crate::country::{CountryRegistry, test_country};
+    use crate::onboarding::{KycStatus, OnboardingRules};
+    use crate::user::CreateUserError;
+    use chrono::NaiveDate;
+    use claims::{assert_err, assert_matches, assert_ok_eq};
+
+    fn rules() -> OnboardingRules {
+        let settings = OnboardingSettings {
+            minimum_age: 18,
+            minimum_age_by_country: [("PY".to_string(), 21)].into(),
+            maximum_age: 120,
+            kyc_status_of_new_clients: KycStatus::Pending,
+        };
+        let countries = CountryRegistry::load("configuration/countries.yaml").unwrap();
+        OnboardingRules::from_settings(&settings, &countries).unwrap()
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn adults_start_with_the_status_of_the_settings() {
+        let today = date(2025, 9, 6);
+        assert_ok_eq!(
+            rules().check(date(1982, 9, 27), &test_country("Argentina"), today),
+            KycStatus::Pending
+        );
+        // the birthday is today
+        assert_ok_eq!(
+            rules().check(date(2007, 9, 6), &test_country("Argentina"), today),
+            KycStatus::Pending
+        );
+    }
+
+    #[test]
+    fn the_minimum_age_depends_on_the_country() {
+        let today = date(2025, 9, 6);
+        let argentina = test_country("Argentina");
+        let paraguay = test_country("Paraguay");
+        assert_matches!(
+            rules().check(date(2007, 9, 7), &argentina, today),
+            Err(CreateUserError::UnderMinimumAge(18, _))
+        );
+        assert_matches!(
+            rules().check(date(2005, 1, 1), &paraguay, today),
+            Err(CreateUserError::UnderMinimumAge(21, _))
+        );
+        assert_eq!(rules().minimum_age(&paraguay), 21);
+    }
+
+    #[test]
+    fn implausible_birth_dates_are_rejected() {
+        let today = date(2025, 9, 6);
+        let argentina = test_country("Argentina");
+        assert_matches!(
+            rules().check(date(2025, 9, 7), &argentina, today),
+            Err(CreateUserError::BirthDateInTheFuture(_))
+        );
+        assert_matches!(
+            rules().check(date(1900, 1, 1), &argentina, today),
+            Err(CreateUserError::ImplausibleBirthDate(_))
+        );
+    }
+
+    #[test]
+    fn the_countries_of_the_settings_must_exist() {
+        let settings = OnboardingSettings {
+            minimum_age: 18,
+            minimum_age_by_country: [("Narnia".to_string(), 21)].into(),
+            maximum_age: 120,
+            kyc_status_of_new_clients: KycStatus::Pending,
+        };
+        let countries = CountryRegistry::load("configuration/countries.yaml").unwrap();
+        assert_err!(OnboardingRules::from_settings(&settings, &countries));
+    }
+
+    #[test]
+    fn only_the_verified_clients_may_transact() {
+        assert!(KycStatus::Verified.may_transact());
+        assert!(!KycStatus::Pending.may_transact());
+        assert!(!KycStatus::Rejected.may_transact());
+    }
+}
@@ -1,6 +1,206 @@
 use crate::user::{CreateUserError, DatabaseError};
-use actix_web::ResponseError;
+use actix_web::error::{JsonPayloadError, PathError, QueryPayloadError, UrlencodedError};
 use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
+use serde_json::{Value, json};
+use std::fmt;
+
+/// The body of every error of the API, inside an `error` object:
+///
+/// `{"error": {"code": "insufficient_balance", "message": "...", "details": {...}, "request_id": "..."}}`
+///
+/// The `code` never changes for the same error, the `message` is only for humans. The `request_id`
+/// is added by the `request_id` middleware, so it is the same of the `X-Request-Id` header
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ErrorBody {
+    pub code: &'static str,
+    pub message: String,
+    pub details: Value,
+    pub request_id: Option<String>,
+}
+
+impl ErrorBody {
+    fn new(code: &'static str, message: impl fmt::Display, details: Value) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+            details,
+            request_id: None,
+        }
+    }
+
+    /// the errors of the service are not explained to the clients, they only go to the log
+    fn internal(error: &dyn fmt::Display) -> Self {
+        log::error!("internal error: {error}");
+        Self::new("internal_error", "internal error", json!({}))
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({ "error": self })
+    }
+
+    /// the response with the body, the body is also saved in the extensions of the response so
+    /// the middleware can add the request id
+    fn to_http_response(&self, status: StatusCode) -> HttpResponse {
+        let mut response = HttpResponse::build(status).json(self.to_json());
+        response.extensions_mut().insert(self.clone());
+        response
+    }
+}
+
+/// the errors that are not of our domain: a body, a path or a query that can not be read, or an
+/// unknown route
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    body: ErrorBody,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.body.message)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        self.body.to_http_response(self.status)
+    }
+}
+
+/// error handler of `web::JsonConfig`
+pub fn json_error(error: JsonPayloadError, _request: &HttpRequest) -> actix_web::Error {
+    let (status, body) = match &error {
+        JsonPayloadError::Deserialize(e) if e.is_data() => (
+            StatusCode::BAD_REQUEST,
+            ErrorBody::new(
+                "invalid_body",
+                e,
+                json!({"line": e.line(), "column": e.column()}),
+            ),
+        ),
+        JsonPayloadError::Deserialize(e) => (
+            StatusCode::BAD_REQUEST,
+            ErrorBody::new(
+                "malformed_json",
+                e,
+                json!({"line": e.line(), "column": e.column()}),
+            ),
+        ),
+        JsonPayloadError::ContentType => (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ErrorBody::new(
+                "unsupported_content_type",
+                &error,
+                json!({"expected": "application/json"}),
+            ),
+        ),
+        JsonPayloadError::Overflow { limit }
+        | JsonPayloadError::OverflowKnownLength { limit, .. } => (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorBody::new("payload_too_large", &error, json!({"limit": limit})),
+        ),
+        _ => (
+            StatusCode::BAD_REQUEST,
+            ErrorBody::new("malformed_json", &error, json!({})),
+        ),
+    };
+    ApiError { status, body }.into()
+}
+
+/// error handler of `web::PathConfig`, like an id that is not an uuid
+pub fn path_error(error: PathError, request: &HttpRequest) -> actix_web::Error {
+    ApiError {
+        status: StatusCode::BAD_REQUEST,
+        body: ErrorBody::new("invalid_path", &error, json!({"path": request.path()})),
+    }
+    .into()
+}
+
+/// error handler of `web::QueryConfig`
+pub fn query_error(error: QueryPayloadError, request: &HttpRequest) -> actix_web::Error {
+    ApiError {
+        status: StatusCode::BAD_REQUEST,
+        body: ErrorBody::new(
+            "invalid_query",
+            &error,
+            json!({"query": request.query_string()}),
+        ),
+    }
+    .into()
+}
+
+/// error handler of `web::FormConfig`
+pub fn form_error(error: UrlencodedError, _request: &HttpRequest) -> actix_web::Error {
+    ApiError {
+        status: StatusCode::BAD_REQUEST,
+        body: ErrorBody::new("invalid_form", &error, json!({})),
+    }
+    .into()
+}
+
+/// the default service, for the routes that don't exist
+pub async fn not_found(request: HttpRequest) -> Result<HttpResponse, ApiError> {
+    Err(ApiError {
+        status: StatusCode::NOT_FOUND,
+        body: ErrorBody::new(
+            "not_found",
+            format!(
+                "there is nothing in {} {}",
+                request.method(),
+                request.path()
+            ),
+            json!({"path": request.path()}),
+        ),
+    })
+}
+
+impl CreateUserError {
+    fn body(&self) -> ErrorBody {
+        let (code, details) = match self {
+            Self::InvalidName(name) => ("invalid_name", json!({"client_name": name})),
+            Self::InvalidCountryName(country) => ("invalid_country", json!({"country": country})),
+            Self::InvalidDocumentNumber(number) => {
+                ("invalid_document", json!({"document_number": number}))
+            }
+            Self::UserAlreadyExistsError(document) => {
+                ("client_already_exists", document_details(document))
+            }
+            Self::InvalidBirthDate(date) => ("invalid_birth_date", json!({"birth_date": date})),
+            Self::BirthDateInTheFuture(date) => {
+                ("birth_date_in_the_future", json!({"birth_date": date}))
+            }
+            Self::ImplausibleBirthDate(date) => {
+                ("implausible_birth_date", json!({"birth_date": date}))
+            }
+            Self::UnderMinimumAge(age, country) => (
+                "under_minimum_age",
+                json!({"minimum_age": age, "country": country}),
+            ),
+            Self::InvalidFields(errors) => {
+                let fields = errors
+                    .iter()
+                    .map(|(field, error)| {
+                        let body = error.body();
+                        let error = json!({
+                            "code": body.code,
+                            "message": body.message,
+                            "details": body.details,
+                        });
+                        (field.to_string(), error)
+                    })
+                    .collect::<serde_json::Map<_, _>>();
+                ("invalid_fields", json!({"fields": fields}))
+            }
+            Self::Database(e) => return e.body(),
+        };
+        ErrorBody::new(code, self, details)
+    }
+}
 
 impl ResponseError for CreateUserError {
     fn status_code(&self) -> actix_web::http::StatusCode {
@@ -8,17 +208,153 @@ impl ResponseError for CreateUserError {
             Self::InvalidName(_) => StatusCode::BAD_REQUEST,
             Self::InvalidCountryName(_) => StatusCode::BAD_REQUEST,
             Self::InvalidDocumentNumber(_) => StatusCode::BAD_REQUEST,
-            Self::UserAlreadyExistsError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::UserAlreadyExistsError(_) => StatusCode::CONFLICT,
+            Self::InvalidBirthDate(_) => StatusCode::BAD_REQUEST,
+            Self::BirthDateInTheFuture(_) => StatusCode::BAD_REQUEST,
+            Self::ImplausibleBirthDate(_) => StatusCode::BAD_REQUEST,
+            Self::UnderMinimumAge(_, _) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::InvalidFields(_) => StatusCode::BAD_REQUEST,
+            Self::Database(e) => e.status_code(),
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        self.body().to_http_response(self.status_code())
+    }
+}
+
+impl DatabaseError {
+    fn body(&self) -> ErrorBody {
+        let (code, details) = match self {
+            Self::UnknownUser(id) => ("unknown_client", json!({"client_id": id})),
+            Self::InsufficientBalance(balance) => {
+                ("insufficient_balance", json!({"balance": balance}))
+            }
+            Self::UnknownAccount(id, currency) => (
+                "unknown_account",
+                json!({"client_id": id, "currency": currency}),
+            ),
+            Self::AccountAlreadyExists(id, currency) => (
+                "account_already_exists",
+                json!({"client_id": id, "currency": currency}),
+            ),
+            Self::ClientNotVerified(id, status) => (
+                "client_not_verified",
+                json!({"client_id": id, "kyc_status": status}),
+            ),
+            Self::SelfTransfer(id) => ("self_transfer", json!({"client_id": id})),
+            Self::InvalidAmount(amount) => ("invalid_amount", json!({"amount": amount})),
+            Self::SameCurrencyConversion(currency) => {
+                ("same_currency_conversion", json!({"currency": currency}))
+            }
+            Self::UnknownExchangeRate(from, to) => {
+                ("unknown_exchange_rate", json!({"from": from, "to": to}))
+            }
+            Self::InvalidIdempotencyKey(key) => {
+                ("invalid_idempotency_key", json!({"idempotency_key": key}))
+            }
+            Self::IdempotencyKeyReused(key) => {
+                ("idempotency_key_reused", json!({"idempotency_key": key}))
+            }
+            Self::UnknownSettlement(id) => ("unknown_settlement", json!({"settlement_id": id})),
+            Self::AlreadySettled(id) => ("already_settled", json!({"settlement_id": id})),
+            Self::UnknownDocument(document) => ("unknown_document", document_details(document)),
+            Self::ClientClosed(id) => ("client_closed", json!({"client_id": id})),
+            Self::InvalidCursor(cursor) => ("invalid_cursor", json!({"cursor": cursor})),
+            Self::CurrencyRequired => ("currency_required", json!({})),
+            Self::UnknownHold(id) => ("unknown_hold", json!({"hold_id": id})),
+            Self::HoldNotActive(id, status) => {
+                ("hold_not_active", json!({"hold_id": id, "status": status}))
+            }
+            Self::BalanceNotZero(id, currency, balance) => (
+                "balance_not_zero",
+                json!({"client_id": id, "currency": currency, "balance": balance}),
+            ),
+            _ => return ErrorBody::internal(self),
+        };
+        ErrorBody::new(code, self, details)
+    }
 }
 
 impl ResponseError for DatabaseError {
     fn status_code(&self) -> actix_web::http::StatusCode {
         match self {
-            Self::UnknownUser(_) => StatusCode::BAD_REQUEST,
+            Self::UnknownUser(_) => StatusCode::NOT_FOUND,
+            Self::UnknownSettlement(_) => StatusCode::NOT_FOUND,
+            Self::UnknownDocument(_) => StatusCode::NOT_FOUND,
+            Self::UnknownHold(_) => StatusCode::NOT_FOUND,
             Self::InsufficientBalance(_) => StatusCode::BAD_REQUEST,
+            Self::UnknownAccount(_, _) => StatusCode::BAD_REQUEST,
+            Self::AccountAlreadyExists(_, _) => StatusCode::CONFLICT,
+            Self::ClientNotVerified(_, _) => StatusCode::FORBIDDEN,
+            Self::ClientClosed(_) => StatusCode::CONFLICT,
+            Self::BalanceNotZero(_, _, _) => StatusCode::CONFLICT,
+            Self::HoldNotActive(_, _) => StatusCode::CONFLICT,
+            Self::InvalidCursor(_) => StatusCode::BAD_REQUEST,
+            Self::CurrencyRequired => StatusCode::BAD_REQUEST,
+            Self::SelfTransfer(_) => StatusCode::BAD_REQUEST,
+            Self::InvalidAmount(_) => StatusCode::BAD_REQUEST,
+            Self::SameCurrencyConversion(_) => StatusCode::BAD_REQUEST,
+            Self::UnknownExchangeRate(_, _) => StatusCode::BAD_REQUEST,
+            Self::InvalidIdempotencyKey(_) => StatusCode::BAD_REQUEST,
+            Self::IdempotencyKeyReused(_) => StatusCode::CONFLICT,
+            Self::AlreadySettled(_) => StatusCode::CONFLICT,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        self.body().to_http_response(self.status_code())
+    }
+}
+
+fn document_details(document: &crate::user::Document) -> Value {
+    json!({
+        "document_type": document.document_type(),
+        "issuing_country": document.issuing_country(),
+        "document_number": document.number(),
+    })
+}
+
+//-------------------------------------------------------------------------
+//                        unit tests
+//-------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::currency::Currency;
+    use crate::user::{CreateUserError, DatabaseError};
+    use actix_web::ResponseError;
+    use actix_web::body::to_bytes;
+    use rust_decimal::dec;
+
+    async fn body_of(error: &dyn ResponseError) -> serde_json::Value {
+        let body = to_bytes(error.error_response().into_body()).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[actix_web::test]
+    async fn the_errors_have_a_code_a_message_and_details() {
+        let id = uuid::Uuid::new_v4();
+        let body = body_of(&DatabaseError::UnknownAccount(id, Currency::Brl)).await;
+        assert_eq!(body["error"]["code"], "unknown_account");
+        assert_eq!(body["error"]["details"]["client_id"], id.to_string());
+        assert_eq!(body["error"]["details"]["currency"], "BRL");
+        assert!(body["error"]["message"].as_str().unwrap().contains("BRL"));
+
+        let body = body_of(&CreateUserError::from(DatabaseError::InsufficientBalance(
+            dec!(12),
+        )))
+        .await;
+        assert_eq!(body["error"]["code"], "insufficient_balance");
+        assert_eq!(body["error"]["details"]["balance"], "12");
+    }
+
+    #[actix_web::test]
+    async fn the_internal_errors_are_not_explained() {
+        let error = DatabaseError::UnsupportedSchemaVersion(42);
+        let body = body_of(&error).await;
+        assert_eq!(body["error"]["code"], "internal_error");
+        assert_eq!(body["error"]["details"], serde_json::json!({}));
+        assert!(!body["error"]["message"].as_str().unwrap().contains("42"));
+    }
 }
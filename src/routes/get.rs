@@ -1,32 +1,438 @@
-use crate::local_database::Database;
-use crate::user::{DatabaseError, UserName};
-use actix_web::web;
+use crate::country::CountryRegistry;
+use crate::currency::Currency;
+use crate::document::DocumentType;
+use crate::hold::{self, Hold, HoldStatus};
+use crate::ledger::{Direction, LedgerEntry};
+use crate::listing::{ClientListing, ClientSort, ClientStatus, Cursor, SortOrder};
+use crate::onboarding::KycStatus;
+use crate::settlement::SettlementSummary;
+use crate::statement::{self, StatementQuery};
+use crate::storage::SharedStorage;
+use crate::user::{CreateUserError, DatabaseError, Document, User, UserName};
+use actix_files::NamedFile;
+use actix_web::http::header::ContentDisposition;
+use actix_web::{HttpRequest, HttpResponse, mime, web};
+use chrono::{DateTime, NaiveDate, Utc};
 use rust_decimal::Decimal;
-use std::sync::{Arc, Mutex};
+use std::collections::BTreeMap;
 use uuid::Uuid;
 
+//-------------------------------------------------------------------------
+//                        /clients/{id}/balance
+//-------------------------------------------------------------------------
 #[derive(serde::Deserialize)]
-pub struct UserIn {
-    client_id: Uuid,
+pub struct BalanceQuery {
+    /// only the account in this currency, all of them when is missing
+    currency: Option<Currency>,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct AccountOut {
+    currency: Currency,
+    balance: Decimal,
+    /// the part of the balance that can be used
+    available: Decimal,
+    /// the part of the balance that is reserved and can not be used
+    held: Decimal,
+    /// the time of the last movement of the account, `None` if it never had one
+    updated_at: Option<DateTime<Utc>>,
 }
 
 #[derive(serde::Serialize, Debug, Clone)]
 pub struct Out {
     client_id: Uuid,
-    balance: Decimal,
+    /// the balance of every account of the client by currency
+    balances: BTreeMap<Currency, Decimal>,
+    /// the same accounts of `balances` with all the details
+    accounts: Vec<AccountOut>,
     client_name: UserName,
 }
 
-/// extract form data using serde
-/// this handler gets called only if the content type is *x-www-form-urlencoded*
+/// the balances of the client `id`, like `/clients/{id}/balance?currency=ARS`
+pub async fn get_client_balance(
+    path: web::Path<Uuid>,
+    query: web::Query<BalanceQuery>,
+    database: web::Data<SharedStorage>,
+) -> Result<web::Json<Out>, DatabaseError> {
+    balance_of(path.into_inner(), query.currency, database).await
+}
+
+#[derive(serde::Deserialize)]
+pub struct UserIn {
+    client_id: Uuid,
+}
+
+// NOTE(elsuizo: 2025-09-20): the first version of this endpoint read the id from a form in the
+// body of the GET, we still accept it for the old clients but the id should go in the query
+/// the old `/client_balance?client_id=uuid`, use `/clients/{id}/balance`
 pub async fn get_balance(
-    data: web::Form<UserIn>,
-    database: web::Data<Arc<Mutex<Database>>>,
+    data: web::Either<web::Query<UserIn>, web::Form<UserIn>>,
+    database: web::Data<SharedStorage>,
+) -> Result<web::Json<Out>, DatabaseError> {
+    let client_id = match data {
+        web::Either::Left(query) => query.client_id,
+        web::Either::Right(form) => form.client_id,
+    };
+    balance_of(client_id, None, database).await
+}
+
+async fn balance_of(
+    client_id: Uuid,
+    currency: Option<Currency>,
+    database: web::Data<SharedStorage>,
 ) -> Result<web::Json<Out>, DatabaseError> {
-    let user = database.lock().unwrap().get_user(data.client_id)?;
+    let (user, entries, holds) = web::block(move || {
+        let user = database.get_user(client_id)?;
+        let entries = database.ledger_entries(client_id)?;
+        Ok::<_, DatabaseError>((user, entries, database.client_holds(client_id)?))
+    })
+    .await
+    .map_err(|_| DatabaseError::Other)??;
+    let held = hold::held_amounts(&holds, Utc::now());
+
+    let mut balances = user.balances().clone();
+    if let Some(currency) = currency {
+        let balance = user
+            .get_actual_credit(currency)
+            .ok_or(DatabaseError::UnknownAccount(client_id, currency))?;
+        balances = BTreeMap::from([(currency, balance)]);
+    }
+    let accounts = balances
+        .iter()
+        .map(|(&currency, &balance)| {
+            let held = held.get(&currency).copied().unwrap_or_default();
+            AccountOut {
+                currency,
+                balance,
+                available: balance - held,
+                held,
+                updated_at: entries
+                    .iter()
+                    .rev()
+                    .find(|entry| entry.currency == currency)
+                    .map(|entry| entry.timestamp),
+            }
+        })
+        .collect();
     Ok(web::Json(Out {
-        client_id: data.client_id,
-        balance: user.get_actual_credit(),
+        client_id,
+        balances,
+        accounts,
         client_name: user.client_name,
     }))
 }
+
+//-------------------------------------------------------------------------
+//                        /clients/{id}/holds
+//-------------------------------------------------------------------------
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HoldsQuery {
+    /// only the holds with this status, all of them when is missing
+    status: Option<HoldStatus>,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct HoldsOut {
+    client_id: Uuid,
+    holds: Vec<Hold>,
+}
+
+/// the holds of the client, the oldest first
+pub async fn list_client_holds(
+    path: web::Path<Uuid>,
+    query: web::Query<HoldsQuery>,
+    database: web::Data<SharedStorage>,
+) -> Result<web::Json<HoldsOut>, DatabaseError> {
+    let client_id = path.into_inner();
+    let mut holds = web::block(move || database.client_holds(client_id))
+        .await
+        .map_err(|_| DatabaseError::Other)??;
+    // NOTE(elsuizo: 2025-10-18): the expired holds that the background task didn't see yet are
+    // shown like expired
+    let now = Utc::now();
+    holds.iter_mut().for_each(|hold| {
+        hold.expire(now);
+    });
+    if let Some(status) = query.status {
+        holds.retain(|hold| hold.status == status);
+    }
+    Ok(web::Json(HoldsOut { client_id, holds }))
+}
+
+//-------------------------------------------------------------------------
+//                        /holds/{id}
+//-------------------------------------------------------------------------
+pub async fn get_hold(
+    path: web::Path<Uuid>,
+    database: web::Data<SharedStorage>,
+) -> Result<web::Json<Hold>, DatabaseError> {
+    let hold_id = path.into_inner();
+    let mut hold = web::block(move || database.get_hold(hold_id))
+        .await
+        .map_err(|_| DatabaseError::Other)??;
+    hold.expire(Utc::now());
+    Ok(web::Json(hold))
+}
+
+//-------------------------------------------------------------------------
+//                        /clients/{id}/transactions
+//-------------------------------------------------------------------------
+#[derive(serde::Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StatementFormat {
+    #[default]
+    Json,
+    /// all the entries of the filters in a csv file, without pages
+    Csv,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransactionsQuery {
+    currency: Option<Currency>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    direction: Option<Direction>,
+    min_amount: Option<Decimal>,
+    max_amount: Option<Decimal>,
+    /// the `next_cursor` of the previous page
+    cursor: Option<Uuid>,
+    limit: Option<usize>,
+    #[serde(default)]
+    format: StatementFormat,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct TransactionsOut {
+    client_id: Uuid,
+    /// the ledger entries of the client, every one with the balance of the account after it
+    transactions: Vec<LedgerEntry>,
+    /// `None` in the last page
+    next_cursor: Option<Uuid>,
+}
+
+/// the statement of the client, like `/clients/{id}/transactions?from=2025-10-01&direction=debit`
+/// or with `format=csv` for the accounting
+pub async fn get_transactions(
+    path: web::Path<Uuid>,
+    query: web::Query<TransactionsQuery>,
+    database: web::Data<SharedStorage>,
+) -> Result<HttpResponse, DatabaseError> {
+    let client_id = path.into_inner();
+    let query = query.into_inner();
+    let statement = StatementQuery {
+        currency: query.currency,
+        from: query.from,
+        to: query.to,
+        direction: query.direction,
+        min_amount: query.min_amount,
+        max_amount: query.max_amount,
+    };
+    let entries = web::block(move || database.ledger_entries(client_id))
+        .await
+        .map_err(|_| DatabaseError::Other)??;
+
+    if query.format == StatementFormat::Csv {
+        let csv = statement::to_csv(&statement.filter(entries));
+        return Ok(HttpResponse::Ok()
+            .content_type("text/csv; charset=utf-8")
+            .insert_header(ContentDisposition::attachment(format!(
+                "{client_id}_transactions.csv"
+            )))
+            .body(csv));
+    }
+    let page = statement.page(
+        entries,
+        query.cursor,
+        query.limit.unwrap_or(StatementQuery::DEFAULT_LIMIT),
+    )?;
+    Ok(HttpResponse::Ok().json(TransactionsOut {
+        client_id,
+        transactions: page.entries,
+        next_cursor: page.next_cursor,
+    }))
+}
+
+//-------------------------------------------------------------------------
+//                        /clients/by_document
+//-------------------------------------------------------------------------
+#[derive(serde::Deserialize)]
+pub struct DocumentQuery {
+    /// the country that issued the document
+    country: String,
+    document_number: String,
+    #[serde(default)]
+    document_type: DocumentType,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct ClientOut {
+    client_id: Uuid,
+    client_name: UserName,
+    birth_date: NaiveDate,
+    document_type: DocumentType,
+    issuing_country: String,
+    document_number: String,
+    country: String,
+    kyc_status: KycStatus,
+    /// the balance of every account of the client by currency
+    balances: BTreeMap<Currency, Decimal>,
+    /// `None` for the clients created before we saved it
+    created_at: Option<DateTime<Utc>>,
+    /// `None` while the client is open
+    closed_at: Option<DateTime<Utc>>,
+}
+
+impl ClientOut {
+    pub(crate) fn new(client_id: Uuid, user: User) -> Self {
+        Self {
+            client_id,
+            birth_date: user.get_bird_date(),
+            document_type: user.get_document_type(),
+            issuing_country: user.get_issuing_country().inner_ref().to_string(),
+            document_number: user.get_document_number().to_string(),
+            country: user.get_country_name().to_string(),
+            kyc_status: user.kyc_status(),
+            balances: user.balances().clone(),
+            created_at: user.created_at(),
+            closed_at: user.closed_at(),
+            client_name: user.client_name,
+        }
+    }
+}
+
+/// find a client by his document without knowing the id, like
+/// `/clients/by_document?country=Argentina&document_number=29653164`, without a
+/// `document_type` we look for a national id
+pub async fn get_client_by_document(
+    query: web::Query<DocumentQuery>,
+    database: web::Data<SharedStorage>,
+    countries: web::Data<CountryRegistry>,
+) -> Result<web::Json<ClientOut>, CreateUserError> {
+    let country = countries.parse_and_validate(&query.country)?;
+    let document =
+        Document::parse_and_validate(query.document_type, country, &query.document_number)?;
+    let (client_id, user) = web::block(move || database.find_user_by_document(&document))
+        .await
+        .map_err(|_| DatabaseError::Other)??;
+    Ok(web::Json(ClientOut::new(client_id, user)))
+}
+
+//-------------------------------------------------------------------------
+//                        /clients
+//-------------------------------------------------------------------------
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClientsQuery {
+    country: Option<String>,
+    kyc_status: Option<KycStatus>,
+    status: Option<ClientStatus>,
+    currency: Option<Currency>,
+    min_balance: Option<Decimal>,
+    max_balance: Option<Decimal>,
+    created_from: Option<NaiveDate>,
+    created_to: Option<NaiveDate>,
+    #[serde(default)]
+    sort: ClientSort,
+    #[serde(default)]
+    order: SortOrder,
+    /// the `next_cursor` of the previous page
+    cursor: Option<String>,
+    limit: Option<usize>,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct ClientsOut {
+    clients: Vec<ClientOut>,
+    /// `None` in the last page
+    next_cursor: Option<String>,
+}
+
+/// one page of the clients, like
+/// `/clients?country=Chile&currency=CLP&sort=balance&order=desc&limit=20`. The next page is
+/// asked with the same query and the `cursor` of the previous one
+pub async fn list_clients(
+    query: web::Query<ClientsQuery>,
+    database: web::Data<SharedStorage>,
+    countries: web::Data<CountryRegistry>,
+) -> Result<web::Json<ClientsOut>, CreateUserError> {
+    let query = query.into_inner();
+    let listing = ClientListing {
+        country: match &query.country {
+            Some(country) => Some(countries.parse_and_validate(country)?.name()),
+            None => None,
+        },
+        kyc_status: query.kyc_status,
+        status: query.status,
+        currency: query.currency,
+        min_balance: query.min_balance,
+        max_balance: query.max_balance,
+        created_from: query.created_from,
+        created_to: query.created_to,
+        sort: query.sort,
+        order: query.order,
+        after: query
+            .cursor
+            .as_deref()
+            .map(str::parse::<Cursor>)
+            .transpose()?,
+        limit: query.limit.unwrap_or(ClientListing::DEFAULT_LIMIT),
+    };
+    let page = web::block(move || database.list_users(&listing))
+        .await
+        .map_err(|_| DatabaseError::Other)??;
+    Ok(web::Json(ClientsOut {
+        clients: page
+            .clients
+            .into_iter()
+            .map(|(id, user)| ClientOut::new(id, user))
+            .collect(),
+        next_cursor: page.next_cursor.map(|cursor| cursor.encode()),
+    }))
+}
+
+//-------------------------------------------------------------------------
+//                        /clients/{id}
+//-------------------------------------------------------------------------
+/// the profile of the client, the closed clients are also returned
+pub async fn get_client(
+    path: web::Path<Uuid>,
+    database: web::Data<SharedStorage>,
+) -> Result<web::Json<ClientOut>, DatabaseError> {
+    let client_id = path.into_inner();
+    let user = web::block(move || database.get_user(client_id))
+        .await
+        .map_err(|_| DatabaseError::Other)??;
+    Ok(web::Json(ClientOut::new(client_id, user)))
+}
+
+//-------------------------------------------------------------------------
+//                        /settlements
+//-------------------------------------------------------------------------
+/// all the settlements done, the oldest first
+pub async fn list_settlements(
+    database: web::Data<SharedStorage>,
+) -> Result<web::Json<Vec<SettlementSummary>>, DatabaseError> {
+    let settlements = web::block(move || database.settlements())
+        .await
+        .map_err(|_| DatabaseError::Other)??;
+    Ok(web::Json(settlements))
+}
+
+/// stream the content of the settlement file
+pub async fn download_settlement(
+    request: HttpRequest,
+    path: web::Path<Uuid>,
+    database: web::Data<SharedStorage>,
+) -> Result<HttpResponse, DatabaseError> {
+    let id = path.into_inner();
+    let run = web::block(move || database.get_settlement(id))
+        .await
+        .map_err(|_| DatabaseError::Other)??;
+    let file = NamedFile::open_async(&run.path)
+        .await?
+        .set_content_type(mime::TEXT_PLAIN_UTF_8)
+        .set_content_disposition(ContentDisposition::attachment(run.summary.file_name));
+    Ok(file.into_response(&request))
+}
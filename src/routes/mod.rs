@@ -1,6 +1,19 @@
 mod error;
 mod get;
+mod patch;
 mod post;
+mod request_id;
 
-pub use get::get_balance;
-pub use post::{client_creation, decrease_balance, health_check, increase_balance, store_balances};
+pub use error::{ApiError, ErrorBody, form_error, json_error, not_found, path_error, query_error};
+
+pub use get::{
+    download_settlement, get_balance, get_client, get_client_balance, get_client_by_document,
+    get_hold, get_transactions, list_client_holds, list_clients, list_settlements,
+};
+pub use patch::update_client;
+pub use post::{
+    account_creation, capture_hold, client_creation, close_client, convert, decrease_balance,
+    health_check, increase_balance, place_hold, store_balances, transfer, update_kyc_status,
+    void_hold,
+};
+pub use request_id::{REQUEST_ID_HEADER, RequestId, request_id};
@@ -0,0 +1,46 @@
+use crate::country::CountryRegistry;
+use crate::routes::get::ClientOut;
+use crate::storage::SharedStorage;
+use crate::user::{CreateUserError, DatabaseError, FieldErrors, ProfileChange, UserName};
+use actix_web::web;
+use uuid::Uuid;
+
+//-------------------------------------------------------------------------
+//                        /clients/{id}
+//-------------------------------------------------------------------------
+/// the fields of the profile that can be changed, the missing ones stay as they are
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileData {
+    client_name: Option<String>,
+    country: Option<String>,
+}
+
+/// change the name or the country of the client, they are validated like in `/new_client`. The
+/// client gets an account in the currency of his new country
+pub async fn update_client(
+    path: web::Path<Uuid>,
+    data: web::Json<ProfileData>,
+    database: web::Data<SharedStorage>,
+    countries: web::Data<CountryRegistry>,
+) -> Result<web::Json<ClientOut>, CreateUserError> {
+    let client_id = path.into_inner();
+    let mut fields = FieldErrors::default();
+    let client_name = data
+        .client_name
+        .as_deref()
+        .and_then(|name| fields.check("client_name", UserName::parse_and_validate(name)));
+    let country = data
+        .country
+        .as_deref()
+        .and_then(|country| fields.check("country", countries.parse_and_validate(country)));
+    if !fields.is_empty() {
+        return Err(fields.into_error());
+    }
+
+    let change = ProfileChange::new(client_name, country);
+    let user = web::block(move || database.update_profile(client_id, &change))
+        .await
+        .map_err(|_| DatabaseError::Other)??;
+    Ok(web::Json(ClientOut::new(client_id, user)))
+}
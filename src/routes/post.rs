@@ -1,106 +1,504 @@
-use crate::local_database::Database;
-use crate::user::{CountryName, CreateUserError, DatabaseError, DocumentNumber, User, UserName};
-use actix_web::HttpResponse;
+use crate::configuration::{HoldSettings, SettlementSettings};
+use crate::country::CountryRegistry;
+use crate::currency::Currency;
+use crate::document::DocumentType;
+use crate::exchange::ExchangeRateTable;
+use crate::hold::Hold;
+use crate::idempotency::execute_idempotent;
+use crate::ledger::LedgerEntry;
+use crate::onboarding::{KycStatus, OnboardingRules};
+use crate::routes::get::ClientOut;
+use crate::settlement::SettlementSummary;
+use crate::storage::SharedStorage;
+use crate::user::{
+    BirthDate, CreateUserError, DatabaseError, Document, FieldErrors, User, UserName,
+};
 use actix_web::Responder;
 use actix_web::web;
-use chrono::NaiveDate;
+use actix_web::{HttpRequest, HttpResponse};
+use chrono::Utc;
 use log::info;
 use rust_decimal::Decimal;
-use std::error::Error;
-use std::sync::{Arc, Mutex};
+use serde_aux::field_attributes::deserialize_string_from_number;
+use std::path::PathBuf;
+use std::sync::Arc;
 use uuid::Uuid;
 
 //-------------------------------------------------------------------------
 //                        new user handler
 //-------------------------------------------------------------------------
-#[derive(serde::Deserialize, Debug, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
 pub struct UserData {
     client_name: String,
     birth_date: String,
-    document_number: usize,
+    /// the old clients send the document as a number
+    #[serde(deserialize_with = "deserialize_string_from_number")]
+    document_number: String,
+    /// the clients that do not send it are registered with their national id
+    #[serde(default)]
+    document_type: DocumentType,
+    /// the country of the client when is missing
+    issuing_country: Option<String>,
     country: String,
 }
 
 #[derive(serde::Serialize, Debug, Clone)]
 pub struct Out {
     client_id: Uuid,
+    kyc_status: KycStatus,
 }
 
 pub async fn client_creation(
+    request: HttpRequest,
     data: web::Json<UserData>,
-    database: web::Data<Arc<Mutex<Database>>>,
-) -> Result<web::Json<Out>, CreateUserError> {
-    let user_name = UserName::parse_and_validate(&data.client_name)?;
-    // TODO(elsuizo: 2025-07-13): better error for parsing `bird_date`
-    let bird_date = NaiveDate::parse_from_str(&data.birth_date, "%Y-%m-%d")
-        .expect("Error parsing the date, use the format: Y-m-d");
-    let document_number = DocumentNumber::parse_and_validate(data.document_number)?;
-    let country = CountryName::parse_and_validate(&data.country)?;
+    database: web::Data<SharedStorage>,
+    countries: web::Data<CountryRegistry>,
+    onboarding: web::Data<OnboardingRules>,
+) -> Result<HttpResponse, CreateUserError> {
+    // NOTE(elsuizo: 2025-09-13): we check all the fields before answering so the client can fix
+    // all of them at once
+    let mut fields = FieldErrors::default();
+    let user_name = fields.check(
+        "client_name",
+        UserName::parse_and_validate(&data.client_name),
+    );
+    let bird_date = fields.check(
+        "birth_date",
+        BirthDate::parse_and_validate(&data.birth_date),
+    );
+    let country = fields.check("country", countries.parse_and_validate(&data.country));
+    let issuing_country = match &data.issuing_country {
+        Some(issuing_country) => fields.check(
+            "issuing_country",
+            countries.parse_and_validate(issuing_country),
+        ),
+        None => country,
+    };
+    // the document can only be checked with the rules of a known country
+    let document = issuing_country.and_then(|issuing_country| {
+        fields.check(
+            "document_number",
+            Document::parse_and_validate(
+                data.document_type,
+                issuing_country,
+                &data.document_number,
+            ),
+        )
+    });
+    let (Some(user_name), Some(bird_date), Some(country), Some(document)) =
+        (user_name, bird_date, country, document)
+    else {
+        return Err(fields.into_error());
+    };
 
-    let user = User::new(user_name, bird_date, document_number, country);
+    let kyc_status = onboarding.check(bird_date.inner(), country, Utc::now().date_naive())?;
 
-    // TODO(elsuizo: 2025-07-12): no se que hacer con ese unwrap
-    let id = database.lock().unwrap().insert_new_user(&user)?;
+    let mut user = User::new(user_name, bird_date, document, country);
+    user.set_kyc_status(kyc_status);
 
-    info!("database state: {:?}", database);
+    execute_idempotent(
+        &request,
+        &database,
+        data.into_inner(),
+        move |database, _| {
+            let id = database.insert_new_user(&user)?;
+            info!("client {id} created");
+            Ok::<_, CreateUserError>(id)
+        },
+        move |id| Out {
+            client_id: *id,
+            kyc_status,
+        },
+    )
+    .await
+}
+
+//-------------------------------------------------------------------------
+//                        /clients/{id}/kyc
+//-------------------------------------------------------------------------
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+pub struct KycData {
+    status: KycStatus,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct KycOut {
+    client_id: Uuid,
+    kyc_status: KycStatus,
+}
+
+/// the result of the verification of the client, only the verified clients can move money
+pub async fn update_kyc_status(
+    request: HttpRequest,
+    path: web::Path<Uuid>,
+    data: web::Json<KycData>,
+    database: web::Data<SharedStorage>,
+) -> Result<HttpResponse, DatabaseError> {
+    let client_id = path.into_inner();
+    let kyc_status = data.status;
+    execute_idempotent(
+        &request,
+        &database,
+        data.into_inner(),
+        move |database, data| database.set_kyc_status(client_id, data.status),
+        move |_| KycOut {
+            client_id,
+            kyc_status,
+        },
+    )
+    .await
+}
+
+//-------------------------------------------------------------------------
+//                        /new_account
+//-------------------------------------------------------------------------
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+pub struct AccountData {
+    client_id: Uuid,
+    currency: Currency,
+}
 
-    Ok(web::Json(Out { client_id: id }))
+pub async fn account_creation(
+    request: HttpRequest,
+    data: web::Json<AccountData>,
+    database: web::Data<SharedStorage>,
+) -> Result<HttpResponse, DatabaseError> {
+    let out = data.clone();
+    execute_idempotent(
+        &request,
+        &database,
+        data.into_inner(),
+        |database, data| database.open_account(data.client_id, data.currency),
+        move |_| out.clone(),
+    )
+    .await
 }
 
 //-------------------------------------------------------------------------
 //                        /new_credit_transaction
 //-------------------------------------------------------------------------
-#[derive(serde::Deserialize, Debug, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
 pub struct BalancePlusMinus {
     client_id: Uuid,
+    currency: Currency,
     credit_amount: Decimal,
 }
 
 #[derive(serde::Serialize, Debug, Clone)]
 pub struct BalanceOut {
     actual_balance: Decimal,
+    currency: Currency,
+    transaction_id: Uuid,
+}
+
+impl BalanceOut {
+    fn new(entry: &LedgerEntry) -> Self {
+        Self {
+            actual_balance: entry.balance,
+            currency: entry.currency,
+            transaction_id: entry.transaction_id,
+        }
+    }
 }
 
 pub async fn increase_balance(
+    request: HttpRequest,
     data: web::Json<BalancePlusMinus>,
-    database: web::Data<Arc<Mutex<Database>>>,
-) -> Result<web::Json<BalanceOut>, DatabaseError> {
-    let new_balance = database
-        .lock()
-        .unwrap()
-        .find_user_and_increase_balance(data.client_id, data.credit_amount)?;
-
-    Ok(web::Json(BalanceOut {
-        actual_balance: new_balance,
-    }))
+    database: web::Data<SharedStorage>,
+) -> Result<HttpResponse, DatabaseError> {
+    execute_idempotent(
+        &request,
+        &database,
+        data.into_inner(),
+        |database, data| {
+            database.find_user_and_increase_balance(
+                data.client_id,
+                data.currency,
+                data.credit_amount,
+            )
+        },
+        BalanceOut::new,
+    )
+    .await
 }
 
 //-------------------------------------------------------------------------
 //                        /new_debit_transaction
 //-------------------------------------------------------------------------
 pub async fn decrease_balance(
+    request: HttpRequest,
     data: web::Json<BalancePlusMinus>,
-    database: web::Data<Arc<Mutex<Database>>>,
-) -> Result<web::Json<BalanceOut>, DatabaseError> {
-    let new_balance = database
-        .lock()
-        .unwrap()
-        .find_user_and_decrease_balance(data.client_id, data.credit_amount)?;
+    database: web::Data<SharedStorage>,
+) -> Result<HttpResponse, DatabaseError> {
+    execute_idempotent(
+        &request,
+        &database,
+        data.into_inner(),
+        |database, data| {
+            database.find_user_and_decrease_balance(
+                data.client_id,
+                data.currency,
+                data.credit_amount,
+            )
+        },
+        BalanceOut::new,
+    )
+    .await
+}
+
+//-------------------------------------------------------------------------
+//                        /transfer
+//-------------------------------------------------------------------------
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+pub struct TransferData {
+    from_client_id: Uuid,
+    to_client_id: Uuid,
+    currency: Currency,
+    amount: Decimal,
+}
 
-    Ok(web::Json(BalanceOut {
-        actual_balance: new_balance,
-    }))
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct TransferOut {
+    transaction_id: Uuid,
+    currency: Currency,
+    from_balance: Decimal,
+    to_balance: Decimal,
+}
+
+pub async fn transfer(
+    request: HttpRequest,
+    data: web::Json<TransferData>,
+    database: web::Data<SharedStorage>,
+) -> Result<HttpResponse, DatabaseError> {
+    execute_idempotent(
+        &request,
+        &database,
+        data.into_inner(),
+        |database, data| {
+            database.transfer(
+                data.from_client_id,
+                data.to_client_id,
+                data.currency,
+                data.amount,
+            )
+        },
+        |(from_entry, to_entry): &(LedgerEntry, LedgerEntry)| TransferOut {
+            transaction_id: from_entry.transaction_id,
+            currency: from_entry.currency,
+            from_balance: from_entry.balance,
+            to_balance: to_entry.balance,
+        },
+    )
+    .await
+}
+
+//-------------------------------------------------------------------------
+//                        /convert
+//-------------------------------------------------------------------------
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+pub struct ConvertData {
+    client_id: Uuid,
+    from_currency: Currency,
+    to_currency: Currency,
+    /// the amount to debit in `from_currency`
+    amount: Decimal,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct ConvertOut {
+    transaction_id: Uuid,
+    exchange_rate: Decimal,
+    from_currency: Currency,
+    debited_amount: Decimal,
+    from_balance: Decimal,
+    to_currency: Currency,
+    credited_amount: Decimal,
+    to_balance: Decimal,
+}
+
+pub async fn convert(
+    request: HttpRequest,
+    data: web::Json<ConvertData>,
+    database: web::Data<SharedStorage>,
+    exchange_rates: web::Data<Arc<ExchangeRateTable>>,
+) -> Result<HttpResponse, DatabaseError> {
+    let exchange_rates = Arc::clone(&exchange_rates);
+    execute_idempotent(
+        &request,
+        &database,
+        data.into_inner(),
+        move |database, data| {
+            let rate = exchange_rates
+                .rate(data.from_currency, data.to_currency)
+                .ok_or(DatabaseError::UnknownExchangeRate(
+                    data.from_currency,
+                    data.to_currency,
+                ))?;
+            database.convert(
+                data.client_id,
+                data.from_currency,
+                data.to_currency,
+                data.amount,
+                rate,
+            )
+        },
+        |(from_entry, to_entry): &(LedgerEntry, LedgerEntry)| ConvertOut {
+            transaction_id: from_entry.transaction_id,
+            // the storage saves the rate of the conversion in the entries
+            exchange_rate: from_entry.exchange_rate.unwrap_or_default(),
+            from_currency: from_entry.currency,
+            debited_amount: from_entry.amount,
+            from_balance: from_entry.balance,
+            to_currency: to_entry.currency,
+            credited_amount: to_entry.amount,
+            to_balance: to_entry.balance,
+        },
+    )
+    .await
+}
+
+//-------------------------------------------------------------------------
+//                        /clients/{id}/close
+//-------------------------------------------------------------------------
+/// close the client, he must have all his balances in zero. The client is not deleted, he can
+/// still be read but he can not move money any more
+pub async fn close_client(
+    request: HttpRequest,
+    path: web::Path<Uuid>,
+    database: web::Data<SharedStorage>,
+) -> Result<HttpResponse, DatabaseError> {
+    let client_id = path.into_inner();
+    execute_idempotent(
+        &request,
+        &database,
+        (),
+        move |database, _| {
+            let user = database.close_user(client_id, Utc::now())?;
+            info!("client {client_id} closed");
+            Ok::<_, DatabaseError>(user)
+        },
+        move |user| ClientOut::new(client_id, user.clone()),
+    )
+    .await
+}
+
+//-------------------------------------------------------------------------
+//                        /clients/{id}/holds
+//-------------------------------------------------------------------------
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct HoldData {
+    currency: Currency,
+    amount: Decimal,
+}
+
+/// reserve money of the client until the hold is captured, voided or it expires
+pub async fn place_hold(
+    request: HttpRequest,
+    path: web::Path<Uuid>,
+    data: web::Json<HoldData>,
+    database: web::Data<SharedStorage>,
+    settings: web::Data<HoldSettings>,
+) -> Result<HttpResponse, DatabaseError> {
+    let client_id = path.into_inner();
+    let ttl = chrono::Duration::seconds(settings.ttl_seconds as i64);
+    execute_idempotent(
+        &request,
+        &database,
+        data.into_inner(),
+        move |database, data| {
+            let expires_at = Utc::now() + ttl;
+            let hold = database.place_hold(client_id, data.currency, data.amount, expires_at)?;
+            info!(
+                "hold {} placed over {} {}",
+                hold.id, hold.amount, hold.currency
+            );
+            Ok::<_, DatabaseError>(hold)
+        },
+        Hold::clone,
+    )
+    .await
+}
+
+//-------------------------------------------------------------------------
+//                        /holds/{id}/capture
+//-------------------------------------------------------------------------
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CaptureData {
+    /// all the hold when is missing, the rest of the hold is released
+    amount: Option<Decimal>,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct CaptureOut {
+    hold: Hold,
+    actual_balance: Decimal,
+    transaction_id: Uuid,
+}
+
+pub async fn capture_hold(
+    request: HttpRequest,
+    path: web::Path<Uuid>,
+    data: web::Json<CaptureData>,
+    database: web::Data<SharedStorage>,
+) -> Result<HttpResponse, DatabaseError> {
+    let hold_id = path.into_inner();
+    execute_idempotent(
+        &request,
+        &database,
+        data.into_inner(),
+        move |database, data| database.capture_hold(hold_id, data.amount),
+        |(hold, entry): &(Hold, LedgerEntry)| CaptureOut {
+            hold: hold.clone(),
+            actual_balance: entry.balance,
+            transaction_id: entry.transaction_id,
+        },
+    )
+    .await
+}
+
+//-------------------------------------------------------------------------
+//                        /holds/{id}/void
+//-------------------------------------------------------------------------
+pub async fn void_hold(
+    request: HttpRequest,
+    path: web::Path<Uuid>,
+    database: web::Data<SharedStorage>,
+) -> Result<HttpResponse, DatabaseError> {
+    let hold_id = path.into_inner();
+    execute_idempotent(
+        &request,
+        &database,
+        (),
+        move |database, _| database.void_hold(hold_id),
+        Hold::clone,
+    )
+    .await
 }
 
 //-------------------------------------------------------------------------
 //                        /store_balances
 //-------------------------------------------------------------------------
 pub async fn store_balances(
-    database: web::Data<Arc<Mutex<Database>>>,
-) -> Result<(), Box<dyn Error>> {
+    request: HttpRequest,
+    database: web::Data<SharedStorage>,
+    settings: web::Data<SettlementSettings>,
+) -> Result<HttpResponse, DatabaseError> {
     info!("saving balances");
-    database.lock().unwrap().store_balances()?;
-    Ok(())
+    let directory = PathBuf::from(&settings.output_directory);
+    execute_idempotent(
+        &request,
+        &database,
+        (),
+        move |database, _| {
+            let settlement = database.store_balances(&directory)?;
+            info!("settlement file {} written", settlement.file_name);
+            Ok::<_, DatabaseError>(settlement)
+        },
+        SettlementSummary::clone,
+    )
+    .await
 }
 
 //-------------------------------------------------------------------------
@@ -0,0 +1,49 @@
+use crate::routes::error::ErrorBody;
+use actix_web::body::{BoxBody, EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpMessage};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// the id of a request, it is the one that the client sent in the `X-Request-Id` header or a new
+/// one. It can be read from the extensions of the request
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    /// longer ids are replaced by a new one
+    const MAX_LENGTH: usize = 128;
+
+    fn of_request(request: &ServiceRequest) -> Self {
+        request
+            .headers()
+            .get(&REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|id| !id.is_empty() && id.len() <= Self::MAX_LENGTH)
+            .map(|id| Self(id.to_string()))
+            .unwrap_or_else(|| Self(Uuid::new_v4().to_string()))
+    }
+}
+
+/// give an id to every request, the id is returned in the `X-Request-Id` header and in the body
+/// of the errors (see `ErrorBody`)
+pub async fn request_id(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody, BoxBody>>, Error> {
+    let id = RequestId::of_request(&request);
+    request.extensions_mut().insert(id.clone());
+    let mut response = next.call(request).await?;
+    if let Ok(value) = HeaderValue::from_str(&id.0) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    let error = response.response().extensions().get::<ErrorBody>().cloned();
+    let Some(mut error) = error else {
+        return Ok(response.map_into_left_body());
+    };
+    error.request_id = Some(id.0);
+    Ok(response.map_body(|_, _| EitherBody::right(BoxBody::new(error.to_json().to_string()))))
+}
@@ -0,0 +1,243 @@
+use crate::configuration::SettlementSettings;
+use crate::settlement::{ScheduledRun, SettlementSummary};
+use crate::storage::{SharedStorage, Storage};
+use crate::user::DatabaseError;
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use cron::Schedule;
+use std::collections::HashSet;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// what to do when one of the schedules fires
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunDecision {
+    Run,
+    Holiday,
+    /// a settlement was already done in this window (by hand or by the scheduler)
+    AlreadySettled(Uuid),
+}
+
+/// Runs the end of day settlement at the times given by cron expressions (with seconds:
+/// `sec min hour day_of_month month day_of_week`), in the local timezone of the server.
+#[derive(Debug, Clone)]
+pub struct SettlementScheduler {
+    schedules: Vec<Schedule>,
+    holidays: HashSet<NaiveDate>,
+    output_directory: String,
+}
+
+impl SettlementScheduler {
+    pub fn from_settings(settings: &SettlementSettings) -> Result<Self, cron::error::Error> {
+        let schedules = settings
+            .schedules
+            .iter()
+            .map(|expression| Schedule::from_str(expression))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            schedules,
+            holidays: settings.holidays.iter().copied().collect(),
+            output_directory: settings.output_directory.clone(),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.schedules.is_empty()
+    }
+
+    /// the first time that some schedule fires after `after`
+    pub fn next_run_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        self.schedules
+            .iter()
+            .filter_map(|schedule| schedule.after(&after).next())
+            .min()
+    }
+
+    /// the last time that some schedule fired before `before`
+    fn previous_run_before(&self, before: DateTime<Local>) -> Option<DateTime<Local>> {
+        self.schedules
+            .iter()
+            .filter_map(|schedule| schedule.after(&before).next_back())
+            .max()
+    }
+
+    /// the run of `fire_time`, it settles everything since the previous fire time
+    fn scheduled_run(&self, fire_time: DateTime<Local>) -> ScheduledRun {
+        ScheduledRun {
+            window_start: self
+                .previous_run_before(fire_time)
+                .map_or(DateTime::<Utc>::MIN_UTC, |t| t.with_timezone(&Utc)),
+            fire_time: fire_time.with_timezone(&Utc),
+        }
+    }
+
+    /// We skip the run of `fire_time` if someone already settled by hand in its window or if this
+    /// same run was already done. The storage checks the window again before settling, the
+    /// `settlements` could be old by then
+    pub fn decide(
+        &self,
+        fire_time: DateTime<Local>,
+        settlements: &[SettlementSummary],
+    ) -> RunDecision {
+        if self.holidays.contains(&fire_time.date_naive()) {
+            return RunDecision::Holiday;
+        }
+        match self.scheduled_run(fire_time).already_settled(settlements) {
+            Some(settlement) => RunDecision::AlreadySettled(settlement.id),
+            None => RunDecision::Run,
+        }
+    }
+
+    fn run(&self, fire_time: DateTime<Local>, storage: &dyn Storage) {
+        let settlements = match storage.settlements() {
+            Ok(settlements) => settlements,
+            Err(e) => {
+                log::error!("scheduled settlement of {fire_time} failed: {e}");
+                return;
+            }
+        };
+        let already_settled = |id: Uuid| {
+            log::info!(
+                "scheduled settlement of {fire_time} skipped, the settlement {id} was already done"
+            )
+        };
+        match self.decide(fire_time, &settlements) {
+            RunDecision::Holiday => {
+                log::info!("scheduled settlement of {fire_time} skipped, it is a holiday")
+            }
+            RunDecision::AlreadySettled(id) => already_settled(id),
+            RunDecision::Run => {
+                let directory = Path::new(&self.output_directory);
+                match storage.store_scheduled_balances(directory, self.scheduled_run(fire_time)) {
+                    Ok(summary) => log::info!(
+                        "scheduled settlement of {fire_time} done: {} ({} clients)",
+                        summary.file_name,
+                        summary.client_count
+                    ),
+                    // a settlement by hand was done after we read the settlements
+                    Err(DatabaseError::AlreadySettled(id)) => already_settled(id),
+                    Err(e) => log::error!("scheduled settlement of {fire_time} failed: {e}"),
+                }
+            }
+        }
+    }
+}
+
+/// wait for every fire time of the schedules and run the settlement
+pub fn spawn_settlement_scheduler(
+    scheduler: SettlementScheduler,
+    storage: SharedStorage,
+) -> tokio::task::JoinHandle<()> {
+    let scheduler = Arc::new(scheduler);
+    tokio::spawn(async move {
+        while let Some(fire_time) = scheduler.next_run_after(Local::now()) {
+            log::info!("next scheduled settlement: {fire_time}");
+            let wait = (fire_time - Local::now()).to_std().unwrap_or_default();
+            tokio::time::sleep(wait).await;
+            // the settlement writes a file and locks all the accounts, so it runs out of the
+            // async runtime
+            let (scheduler, storage) = (Arc::clone(&scheduler), Arc::clone(&storage));
+            let settlement =
+                tokio::task::spawn_blocking(move || scheduler.run(fire_time, &*storage));
+            if let Err(e) = settlement.await {
+                log::error!("scheduled settlement of {fire_time} panicked: {e}");
+            }
+        }
+    })
+}
+
+//-------------------------------------------------------------------------
+//                        unit tests
+//-------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::configuration::SettlementSettings;
+    use crate::scheduler::{RunDecision, SettlementScheduler};
+    use crate::settlement::SettlementSummary;
+    use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+    use std::collections::BTreeMap;
+    use uuid::Uuid;
+
+    fn scheduler() -> SettlementScheduler {
+        SettlementScheduler::from_settings(&SettlementSettings {
+            output_directory: "settlements".into(),
+            schedules: vec!["0 0 21 * * Mon-Fri".into()],
+            holidays: vec![NaiveDate::from_ymd_opt(2025, 12, 25).unwrap()],
+        })
+        .expect("invalid schedule")
+    }
+
+    fn local(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    fn settlement(
+        created_at: DateTime<Local>,
+        scheduled_for: Option<DateTime<Local>>,
+    ) -> SettlementSummary {
+        SettlementSummary {
+            id: Uuid::new_v4(),
+            sequence: 1,
+            file_name: String::new(),
+            created_at: created_at.with_timezone(&Utc),
+            client_count: 0,
+            totals: BTreeMap::new(),
+            scheduled_for: scheduled_for.map(|t| t.with_timezone(&Utc)),
+        }
+    }
+
+    #[test]
+    fn invalid_expressions_are_rejected() {
+        let settings = SettlementSettings {
+            output_directory: "settlements".into(),
+            schedules: vec!["every day at nine".into()],
+            holidays: vec![],
+        };
+        assert!(SettlementScheduler::from_settings(&settings).is_err());
+    }
+
+    #[test]
+    fn the_next_run_skips_the_weekend() {
+        // 2025-07-18 is a friday
+        let next = scheduler().next_run_after(local(2025, 7, 18, 22, 0));
+        assert_eq!(next, Some(local(2025, 7, 21, 21, 0)));
+    }
+
+    #[test]
+    fn holidays_are_skipped() {
+        let decision = scheduler().decide(local(2025, 12, 25, 21, 0), &[]);
+        assert_eq!(decision, RunDecision::Holiday);
+    }
+
+    #[test]
+    fn a_manual_settlement_in_the_same_window_skips_the_run() {
+        let manual = settlement(local(2025, 7, 17, 15, 0), None);
+        let id = manual.id;
+        let decision = scheduler().decide(local(2025, 7, 17, 21, 0), &[manual]);
+        assert_eq!(decision, RunDecision::AlreadySettled(id));
+    }
+
+    #[test]
+    fn the_previous_scheduled_run_does_not_skip_the_next_one() {
+        let previous = settlement(
+            local(2025, 7, 16, 21, 0) + chrono::Duration::seconds(1),
+            Some(local(2025, 7, 16, 21, 0)),
+        );
+        // a manual settlement before the previous run is in other window
+        let old_manual = settlement(local(2025, 7, 16, 10, 0), None);
+        let decision = scheduler().decide(local(2025, 7, 17, 21, 0), &[old_manual, previous]);
+        assert_eq!(decision, RunDecision::Run);
+    }
+
+    #[test]
+    fn the_same_run_is_not_done_twice() {
+        let done = settlement(
+            local(2025, 7, 17, 21, 0) + chrono::Duration::seconds(1),
+            Some(local(2025, 7, 17, 21, 0)),
+        );
+        let id = done.id;
+        let decision = scheduler().decide(local(2025, 7, 17, 21, 0), &[done]);
+        assert_eq!(decision, RunDecision::AlreadySettled(id));
+    }
+}
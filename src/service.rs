@@ -1,13 +1,27 @@
-use crate::configuration::ServiceSettings;
+use crate::configuration::{
+    DatabaseSettings, HoldSettings, ServiceSettings, SettlementSettings, StorageBackend,
+};
+use crate::country::CountryRegistry;
+use crate::exchange::{ExchangeRateTable, spawn_rates_reloader};
+use crate::hold::spawn_hold_expirer;
 use crate::local_database::Database;
+use crate::onboarding::OnboardingRules;
 use crate::routes::{
-    client_creation, decrease_balance, get_balance, increase_balance, store_balances,
+    account_creation, capture_hold, client_creation, close_client, convert, decrease_balance,
+    download_settlement, form_error, get_balance, get_client, get_client_balance,
+    get_client_by_document, get_hold, get_transactions, increase_balance, json_error,
+    list_client_holds, list_clients, list_settlements, not_found, path_error, place_hold,
+    query_error, request_id, store_balances, transfer, update_client, update_kyc_status, void_hold,
 };
+use crate::scheduler::{SettlementScheduler, spawn_settlement_scheduler};
+use crate::sqlite_database::SqliteDatabase;
+use crate::storage::SharedStorage;
 use actix_web::dev::Server;
-use actix_web::middleware::Logger;
+use actix_web::middleware::{Logger, from_fn};
 use actix_web::{App, HttpServer, web};
 use std::net::TcpListener;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+use std::time::Duration;
 
 pub struct Application {
     port: u16,
@@ -21,8 +35,32 @@ impl Application {
         let listener = TcpListener::bind(format!("{}:{}", host, port_config))?;
         // NOTE(elsuizo: 2024-10-17): obtenemos el puerto que nos ha asignado el OS
         let port = listener.local_addr().unwrap().port();
-        let database = Arc::new(Mutex::new(Database::new()));
-        let server = run(listener, database).await?;
+        let storage = open_storage(&configuration.local_database)?;
+        let countries = CountryRegistry::load(&configuration.countries.path)?;
+        let onboarding = OnboardingRules::from_settings(&configuration.onboarding, &countries)?;
+        let exchange_rates = Arc::new(ExchangeRateTable::load(&configuration.exchange.rates_path)?);
+        spawn_rates_reloader(
+            exchange_rates.clone(),
+            Duration::from_secs(configuration.exchange.reload_interval_seconds),
+        );
+        let scheduler = SettlementScheduler::from_settings(&configuration.settlement)?;
+        if !scheduler.is_empty() {
+            spawn_settlement_scheduler(scheduler, storage.clone());
+        }
+        spawn_hold_expirer(
+            storage.clone(),
+            Duration::from_secs(configuration.holds.expiration_interval_seconds),
+        );
+        let server = run(
+            listener,
+            storage,
+            countries,
+            onboarding,
+            exchange_rates,
+            configuration.settlement,
+            configuration.holds,
+        )
+        .await?;
         Ok(Self { port, server })
     }
 
@@ -35,21 +73,74 @@ impl Application {
     }
 }
 
+/// open the storage selected in the settings
+pub fn open_storage(settings: &DatabaseSettings) -> Result<SharedStorage, anyhow::Error> {
+    let storage: SharedStorage = match settings.backend {
+        StorageBackend::Log => {
+            Arc::new(Database::open(&settings.path, settings.snapshot_interval)?)
+        }
+        StorageBackend::Sqlite => Arc::new(SqliteDatabase::open(&settings.path)?),
+    };
+    Ok(storage)
+}
+
 /// server main function
 pub async fn run(
     listener: TcpListener,
-    database: Arc<Mutex<Database>>,
+    storage: SharedStorage,
+    countries: CountryRegistry,
+    onboarding: OnboardingRules,
+    exchange_rates: Arc<ExchangeRateTable>,
+    settlement_settings: SettlementSettings,
+    hold_settings: HoldSettings,
 ) -> Result<Server, anyhow::Error> {
+    let countries = web::Data::new(countries);
+    let onboarding = web::Data::new(onboarding);
     let server = HttpServer::new(move || {
         App::new()
+            .wrap(from_fn(request_id))
             .wrap(Logger::default())
+            .app_data(web::JsonConfig::default().error_handler(json_error))
+            .app_data(web::PathConfig::default().error_handler(path_error))
+            .app_data(web::QueryConfig::default().error_handler(query_error))
+            .app_data(web::FormConfig::default().error_handler(form_error))
             .route("/new_client", web::post().to(client_creation))
+            .route("/clients/{id}/kyc", web::post().to(update_kyc_status))
+            .route("/clients/{id}/close", web::post().to(close_client))
+            .route("/new_account", web::post().to(account_creation))
             .route("/new_credit_transaction", web::post().to(increase_balance))
             .route("/new_debit_transaction", web::post().to(decrease_balance))
+            .route("/transfer", web::post().to(transfer))
+            .route("/convert", web::post().to(convert))
             .route("/store_balances", web::post().to(store_balances))
+            .route("/clients/{id}/balance", web::get().to(get_client_balance))
+            .route(
+                "/clients/{id}/transactions",
+                web::get().to(get_transactions),
+            )
+            .route("/clients/{id}/holds", web::post().to(place_hold))
+            .route("/clients/{id}/holds", web::get().to(list_client_holds))
+            .route("/holds/{id}", web::get().to(get_hold))
+            .route("/holds/{id}/capture", web::post().to(capture_hold))
+            .route("/holds/{id}/void", web::post().to(void_hold))
             .route("/client_balance", web::get().to(get_balance))
+            .route(
+                "/clients/by_document",
+                web::get().to(get_client_by_document),
+            )
+            .route("/clients", web::get().to(list_clients))
+            .route("/clients/{id}", web::get().to(get_client))
+            .route("/clients/{id}", web::patch().to(update_client))
+            .route("/settlements", web::get().to(list_settlements))
+            .route("/settlements/{id}", web::get().to(download_settlement))
             // NOTE(elsuizo: 2025-07-12): clone a Arc is cheap :)
-            .app_data(web::Data::new(database.clone()))
+            .app_data(web::Data::new(storage.clone()))
+            .app_data(countries.clone())
+            .app_data(onboarding.clone())
+            .app_data(web::Data::new(exchange_rates.clone()))
+            .app_data(web::Data::new(settlement_settings.clone()))
+            .app_data(web::Data::new(hold_settings.clone()))
+            .default_service(web::to(not_found))
     })
     .listen(listener)?
     .run();
@@ -0,0 +1,388 @@
+//! Settlement file format (MPSF), version 1.
+//!
+//! A text file with one record per line and the fields separated with `|`:
+//!
+//! ```text
+//! HDR|MPSF|1|2025-07-13|000001|2025-07-13T21:00:00Z
+//! BAL|514a89b1-dad9-4cd7-a43f-9aa1854a67bf|ARS|10.50
+//! BAL|3bfd37f3-3d61-4c07-86be-6a3ead7daa6b|CLP|7300
+//! TOT|ARS|1|10.50
+//! TOT|CLP|1|7300
+//! TRL|2|<checksum>
+//! ```
+//!
+//! - `HDR`: format name, format version, settlement date (ISO 8601), sequence number and the
+//!   moment in which the file was generated (RFC 3339, UTC)
+//! - `BAL`: client id, currency (ISO 4217) and the balance settled
+//! - `TOT`: one line for every currency with the number of `BAL` records and their sum
+//! - `TRL`: the number of `BAL` records and the SHA-256 (hex) of all the bytes before this line
+//!
+//! The file name is `{date}_{sequence}.DAT`, for example `2025-07-13_000001.DAT`.
+use crate::currency::Currency;
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use uuid::Uuid;
+
+pub const FORMAT_NAME: &str = "MPSF";
+pub const FORMAT_VERSION: u32 = 1;
+const SEPARATOR: char = '|';
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SettlementError {
+    #[error("the settlement file don't start with a valid header")]
+    MissingHeader,
+    #[error("unsupported settlement file version: {0}")]
+    UnsupportedVersion(u32),
+    #[error("invalid line {0}: {1}")]
+    InvalidLine(usize, String),
+    #[error("the settlement file don't end with a trailer")]
+    MissingTrailer,
+    #[error("the trailer says {expected} records but the file has {found}")]
+    RecordCountMismatch { expected: usize, found: usize },
+    #[error("the totals of {0} don't match the records")]
+    TotalMismatch(Currency),
+    #[error("invalid checksum")]
+    ChecksumMismatch,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SettlementRecord {
+    pub client_id: Uuid,
+    pub currency: Currency,
+    pub amount: Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct CurrencyTotal {
+    pub records: usize,
+    pub total: Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SettlementFile {
+    pub date: NaiveDate,
+    pub sequence: u64,
+    pub created_at: DateTime<Utc>,
+    pub records: Vec<SettlementRecord>,
+}
+
+impl SettlementFile {
+    /// a settlement of today with the given balances, the records are sorted by client and
+    /// currency
+    pub fn new(sequence: u64, mut records: Vec<SettlementRecord>) -> Self {
+        records.sort_by_key(|record| (record.client_id, record.currency));
+        Self {
+            date: Local::now().date_naive(),
+            sequence,
+            created_at: Utc::now(),
+            records,
+        }
+    }
+
+    /// the file `file_name` of a settlement that was interrupted, `None` if the file was not
+    /// completely written. The tmp file of an unfinished write is removed
+    pub fn recover(directory: &Path, file_name: &str, sequence: u64) -> Option<Self> {
+        let _ = fs::remove_file(tmp_path(directory, file_name));
+        fs::read_to_string(directory.join(file_name))
+            .ok()
+            .and_then(|content| Self::parse(&content).ok())
+            .filter(|settlement| settlement.sequence == sequence)
+    }
+
+    pub fn file_name(&self) -> String {
+        file_name(self.date, self.sequence)
+    }
+
+    pub fn totals(&self) -> BTreeMap<Currency, CurrencyTotal> {
+        let mut totals: BTreeMap<Currency, CurrencyTotal> = BTreeMap::new();
+        for record in &self.records {
+            let total = totals.entry(record.currency).or_insert(CurrencyTotal {
+                records: 0,
+                total: Decimal::ZERO,
+            });
+            total.records += 1;
+            total.total += record.amount;
+        }
+        totals
+    }
+
+    /// `scheduled_for` is the fire time of the schedule that started the run, `None` for the
+    /// settlements started by hand
+    pub fn summary(&self, id: Uuid, scheduled_for: Option<DateTime<Utc>>) -> SettlementSummary {
+        let clients: BTreeSet<Uuid> = self.records.iter().map(|r| r.client_id).collect();
+        SettlementSummary {
+            id,
+            sequence: self.sequence,
+            file_name: self.file_name(),
+            created_at: self.created_at,
+            client_count: clients.len(),
+            totals: self.totals(),
+            scheduled_for,
+        }
+    }
+
+    /// write the file inside `directory`, the content goes first to a tmp file that is renamed
+    /// once it is in the disk so we never leave a half written settlement file
+    pub fn write_atomically(&self, directory: impl AsRef<Path>) -> io::Result<PathBuf> {
+        let directory = directory.as_ref();
+        fs::create_dir_all(directory)?;
+        let tmp_path = tmp_path(directory, &self.file_name());
+        let path = directory.join(self.file_name());
+
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(self.render().as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &path)?;
+        File::open(directory)?.sync_all()?;
+        Ok(path)
+    }
+
+    /// the content of the file
+    pub fn render(&self) -> String {
+        let mut content = format!(
+            "HDR|{FORMAT_NAME}|{FORMAT_VERSION}|{}|{:06}|{}\n",
+            self.date,
+            self.sequence,
+            self.created_at
+                .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+        );
+        for record in &self.records {
+            content += &format!(
+                "BAL|{}|{}|{}\n",
+                record.client_id, record.currency, record.amount
+            );
+        }
+        for (currency, total) in self.totals() {
+            content += &format!("TOT|{}|{}|{}\n", currency, total.records, total.total);
+        }
+        let checksum = checksum(&content);
+        content += &format!("TRL|{}|{}\n", self.records.len(), checksum);
+        content
+    }
+
+    /// parse and validate the content of a settlement file
+    pub fn parse(content: &str) -> Result<Self, SettlementError> {
+        let trailer_start = content
+            .trim_end_matches('\n')
+            .rfind('\n')
+            .map(|i| i + 1)
+            .ok_or(SettlementError::MissingTrailer)?;
+        let (body, trailer) = content.split_at(trailer_start);
+
+        let mut lines = body.lines().enumerate().map(|(i, line)| (i + 1, line));
+        let (_, header) = lines.next().ok_or(SettlementError::MissingHeader)?;
+        let header: Vec<&str> = header.split(SEPARATOR).collect();
+        if header.len() != 6 || header[0] != "HDR" || header[1] != FORMAT_NAME {
+            return Err(SettlementError::MissingHeader);
+        }
+        let version: u32 = header[2]
+            .parse()
+            .map_err(|_| SettlementError::MissingHeader)?;
+        if version != FORMAT_VERSION {
+            return Err(SettlementError::UnsupportedVersion(version));
+        }
+        let header_error = || SettlementError::InvalidLine(1, header.join("|"));
+        let date = header[3].parse::<NaiveDate>().map_err(|_| header_error())?;
+        let sequence = header[4].parse::<u64>().map_err(|_| header_error())?;
+        let created_at = DateTime::parse_from_rfc3339(header[5])
+            .map_err(|_| header_error())?
+            .with_timezone(&Utc);
+
+        let mut records = Vec::new();
+        let mut totals = BTreeMap::new();
+        for (number, line) in lines {
+            let invalid_line = || SettlementError::InvalidLine(number, line.to_string());
+            let fields: Vec<&str> = line.split(SEPARATOR).collect();
+            match fields.as_slice() {
+                ["BAL", client_id, currency, amount] => records.push(SettlementRecord {
+                    client_id: client_id.parse().map_err(|_| invalid_line())?,
+                    currency: currency.parse().map_err(|_| invalid_line())?,
+                    amount: amount.parse().map_err(|_| invalid_line())?,
+                }),
+                ["TOT", currency, count, total] => {
+                    let currency: Currency = currency.parse().map_err(|_| invalid_line())?;
+                    let total = CurrencyTotal {
+                        records: count.parse().map_err(|_| invalid_line())?,
+                        total: total.parse().map_err(|_| invalid_line())?,
+                    };
+                    totals.insert(currency, total);
+                }
+                _ => return Err(invalid_line()),
+            }
+        }
+
+        let trailer: Vec<&str> = trailer.trim_end_matches('\n').split(SEPARATOR).collect();
+        let ["TRL", count, expected_checksum] = trailer.as_slice() else {
+            return Err(SettlementError::MissingTrailer);
+        };
+        if checksum(body) != *expected_checksum {
+            return Err(SettlementError::ChecksumMismatch);
+        }
+        let expected: usize = count.parse().map_err(|_| SettlementError::MissingTrailer)?;
+        if expected != records.len() {
+            return Err(SettlementError::RecordCountMismatch {
+                expected,
+                found: records.len(),
+            });
+        }
+
+        let file = Self {
+            date,
+            sequence,
+            created_at,
+            records,
+        };
+        let computed = file.totals();
+        for currency in computed.keys().chain(totals.keys()) {
+            if computed.get(currency) != totals.get(currency) {
+                return Err(SettlementError::TotalMismatch(*currency));
+            }
+        }
+        Ok(file)
+    }
+}
+
+pub fn file_name(date: NaiveDate, sequence: u64) -> String {
+    format!("{date}_{sequence:06}.DAT")
+}
+
+/// where the file `file_name` is written before the rename
+pub fn tmp_path(directory: &Path, file_name: &str) -> PathBuf {
+    directory.join(format!(".{file_name}.tmp"))
+}
+
+/// what we know about a settlement that was already done
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct SettlementSummary {
+    pub id: Uuid,
+    pub sequence: u64,
+    pub file_name: String,
+    pub created_at: DateTime<Utc>,
+    pub client_count: usize,
+    pub totals: BTreeMap<Currency, CurrencyTotal>,
+    #[serde(default)]
+    pub scheduled_for: Option<DateTime<Utc>>,
+}
+
+/// the run of a schedule that fired at `fire_time`, it settles everything since `window_start`
+/// (the previous fire time)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScheduledRun {
+    pub window_start: DateTime<Utc>,
+    pub fire_time: DateTime<Utc>,
+}
+
+impl ScheduledRun {
+    /// the settlement that already covers this run: one done by hand in the window or this same
+    /// run done before
+    pub fn already_settled<'a>(
+        &self,
+        settlements: &'a [SettlementSummary],
+    ) -> Option<&'a SettlementSummary> {
+        settlements.iter().rev().find(|s| match s.scheduled_for {
+            Some(scheduled_for) => scheduled_for == self.fire_time,
+            None => s.created_at > self.window_start && s.created_at <= self.fire_time,
+        })
+    }
+}
+
+fn checksum(content: &str) -> String {
+    Sha256::digest(content.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+//-------------------------------------------------------------------------
+//                        unit tests
+//-------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::currency::Currency;
+    use crate::settlement::{SettlementError, SettlementFile, SettlementRecord};
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use claims::{assert_err_eq, assert_ok_eq};
+    use rust_decimal::dec;
+    use uuid::Uuid;
+
+    fn test_file() -> SettlementFile {
+        SettlementFile {
+            date: NaiveDate::from_ymd_opt(2025, 7, 13).unwrap(),
+            sequence: 7,
+            created_at: Utc.with_ymd_and_hms(2025, 7, 13, 21, 0, 0).unwrap(),
+            records: vec![
+                SettlementRecord {
+                    client_id: Uuid::new_v4(),
+                    currency: Currency::Ars,
+                    amount: dec!(10.50),
+                },
+                SettlementRecord {
+                    client_id: Uuid::new_v4(),
+                    currency: Currency::Ars,
+                    amount: dec!(0),
+                },
+                SettlementRecord {
+                    client_id: Uuid::new_v4(),
+                    currency: Currency::Clp,
+                    amount: dec!(7300),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn the_file_name_has_the_iso_date_and_the_sequence() {
+        assert_eq!(test_file().file_name(), "2025-07-13_000007.DAT");
+    }
+
+    #[test]
+    fn a_rendered_file_is_parsed_back() {
+        let file = test_file();
+        assert_ok_eq!(SettlementFile::parse(&file.render()), file);
+    }
+
+    #[test]
+    fn the_file_has_header_totals_and_trailer() {
+        let content = test_file().render();
+        let lines: Vec<&str> = content.lines().collect();
+
+        assert_eq!(
+            lines[0],
+            "HDR|MPSF|1|2025-07-13|000007|2025-07-13T21:00:00Z"
+        );
+        assert_eq!(lines[4], "TOT|ARS|2|10.50");
+        assert_eq!(lines[5], "TOT|CLP|1|7300");
+        assert!(lines[6].starts_with("TRL|3|"));
+    }
+
+    #[test]
+    fn a_modified_file_is_rejected() {
+        let content = test_file().render().replace("|7300\n", "|7301\n");
+        assert_err_eq!(
+            SettlementFile::parse(&content),
+            SettlementError::ChecksumMismatch
+        );
+    }
+
+    #[test]
+    fn a_truncated_file_is_rejected() {
+        let content = test_file().render();
+        let truncated = &content[..content.rfind("TRL").unwrap()];
+        assert!(SettlementFile::parse(truncated).is_err());
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+        let content = test_file().render().replace("HDR|MPSF|1|", "HDR|MPSF|2|");
+        assert_err_eq!(
+            SettlementFile::parse(&content),
+            SettlementError::UnsupportedVersion(2)
+        );
+    }
+}
@@ -0,0 +1,59 @@
+-- the amounts are stored as text so they keep all the decimals, a REAL would round them
+CREATE TABLE clients (
+    id TEXT PRIMARY KEY NOT NULL,
+    client_name TEXT NOT NULL,
+    birth_date TEXT NOT NULL,
+    document_number INTEGER NOT NULL UNIQUE,
+    country TEXT NOT NULL
+);
+
+CREATE TABLE accounts (
+    client_id TEXT NOT NULL REFERENCES clients (id),
+    currency TEXT NOT NULL,
+    balance TEXT NOT NULL,
+    PRIMARY KEY (client_id, currency)
+);
+
+-- the external account (the nil uuid) has entries but no row in `accounts`
+CREATE TABLE ledger_entries (
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    id TEXT NOT NULL UNIQUE,
+    transaction_id TEXT NOT NULL,
+    client_id TEXT NOT NULL,
+    currency TEXT NOT NULL,
+    direction TEXT NOT NULL,
+    amount TEXT NOT NULL,
+    balance TEXT NOT NULL,
+    timestamp TEXT NOT NULL,
+    exchange_rate TEXT
+);
+
+CREATE INDEX ledger_entries_by_account ON ledger_entries (client_id, currency, seq);
+
+CREATE TABLE idempotency_keys (
+    key TEXT PRIMARY KEY NOT NULL,
+    fingerprint TEXT NOT NULL,
+    status INTEGER NOT NULL,
+    body TEXT NOT NULL
+);
+
+CREATE TABLE settlements (
+    id TEXT PRIMARY KEY NOT NULL,
+    sequence INTEGER NOT NULL UNIQUE,
+    file_name TEXT NOT NULL,
+    path TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    client_count INTEGER NOT NULL,
+    -- json object with the number of records and the total of every currency
+    totals TEXT NOT NULL,
+    scheduled_for TEXT
+);
+
+-- a settlement that was started but we don't know yet if the file was written, at most one row
+CREATE TABLE pending_settlement (
+    id TEXT PRIMARY KEY NOT NULL,
+    sequence INTEGER NOT NULL,
+    directory TEXT NOT NULL,
+    file_name TEXT NOT NULL,
+    scheduled_for TEXT
+);
@@ -0,0 +1,17 @@
+-- a document number is only unique inside the country that issued it, sqlite can not drop the
+-- `UNIQUE` of a column so the table is rebuilt
+CREATE TABLE clients_new (
+    id TEXT PRIMARY KEY NOT NULL,
+    client_name TEXT NOT NULL,
+    birth_date TEXT NOT NULL,
+    document_number INTEGER NOT NULL,
+    country TEXT NOT NULL,
+    UNIQUE (country, document_number)
+);
+
+INSERT INTO clients_new (id, client_name, birth_date, document_number, country)
+SELECT id, client_name, birth_date, document_number, country FROM clients;
+
+DROP TABLE clients;
+
+ALTER TABLE clients_new RENAME TO clients;
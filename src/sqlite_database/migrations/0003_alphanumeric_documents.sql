@@ -0,0 +1,17 @@
+-- the documents can have letters (a chilean RUT ends in `K` sometimes), with an INTEGER column
+-- sqlite would keep the numeric ones as integers and we could not compare them with the text
+CREATE TABLE clients_new (
+    id TEXT PRIMARY KEY NOT NULL,
+    client_name TEXT NOT NULL,
+    birth_date TEXT NOT NULL,
+    document_number TEXT NOT NULL,
+    country TEXT NOT NULL,
+    UNIQUE (country, document_number)
+);
+
+INSERT INTO clients_new (id, client_name, birth_date, document_number, country)
+SELECT id, client_name, birth_date, CAST(document_number AS TEXT), country FROM clients;
+
+DROP TABLE clients;
+
+ALTER TABLE clients_new RENAME TO clients;
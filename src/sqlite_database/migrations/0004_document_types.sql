@@ -0,0 +1,20 @@
+-- a client is identified by the type of his document and the country that issued it, the
+-- clients that we already have were registered with the national id of their country
+CREATE TABLE clients_new (
+    id TEXT PRIMARY KEY NOT NULL,
+    client_name TEXT NOT NULL,
+    birth_date TEXT NOT NULL,
+    document_type TEXT NOT NULL,
+    issuing_country TEXT NOT NULL,
+    document_number TEXT NOT NULL,
+    country TEXT NOT NULL,
+    UNIQUE (document_type, issuing_country, document_number)
+);
+
+INSERT INTO clients_new
+    (id, client_name, birth_date, document_type, issuing_country, document_number, country)
+SELECT id, client_name, birth_date, 'dni', country, document_number, country FROM clients;
+
+DROP TABLE clients;
+
+ALTER TABLE clients_new RENAME TO clients;
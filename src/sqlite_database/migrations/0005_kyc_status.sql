@@ -0,0 +1,2 @@
+-- the clients created before the KYC were already moving money, so they are verified
+ALTER TABLE clients ADD COLUMN kyc_status TEXT NOT NULL DEFAULT 'verified';
@@ -0,0 +1,2 @@
+-- the closed clients are never deleted, they keep their ledger entries
+ALTER TABLE clients ADD COLUMN closed_at TEXT;
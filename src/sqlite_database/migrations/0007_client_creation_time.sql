@@ -0,0 +1,2 @@
+-- we don't know when the clients created before this were created, they stay in NULL
+ALTER TABLE clients ADD COLUMN created_at TEXT;
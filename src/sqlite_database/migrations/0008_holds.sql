@@ -0,0 +1,19 @@
+-- the authorizations over the accounts, the held money stays in `accounts.balance` until the hold
+-- is captured. The holds are never deleted
+CREATE TABLE holds (
+    id TEXT PRIMARY KEY NOT NULL,
+    client_id TEXT NOT NULL,
+    currency TEXT NOT NULL,
+    amount TEXT NOT NULL,
+    captured_amount TEXT,
+    status TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    closed_at TEXT,
+    FOREIGN KEY (client_id, currency) REFERENCES accounts (client_id, currency)
+);
+
+CREATE INDEX holds_by_client ON holds (client_id, created_at);
+
+-- the only holds that change the available balances
+CREATE INDEX active_holds ON holds (client_id, currency) WHERE status = 'active';
@@ -0,0 +1,1612 @@
+use crate::currency::Currency;
+use crate::document::DocumentType;
+use crate::hold::{self, Hold};
+use crate::idempotency::{self, IdempotencyKey, SavedResponse};
+use crate::ledger::{self, EXTERNAL_ACCOUNT, LedgerEntry, Leg};
+use crate::listing::{ClientListing, ClientPage};
+use crate::local_database::SettlementRun;
+use crate::locks::StripedLocks;
+use crate::onboarding::KycStatus;
+use crate::settlement::{self, ScheduledRun, SettlementFile, SettlementRecord, SettlementSummary};
+use crate::storage::{Storage, validate_amount};
+use crate::user::{
+    CountryName, CreateUserError, DatabaseError, Document, DocumentNumber, ProfileChange, User,
+    UserName,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use r2d2::{Pool, PooledConnection};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::types::Type;
+use rusqlite::{Connection, OptionalExtension, Row, TransactionBehavior, params};
+use rust_decimal::Decimal;
+use std::any::Any;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// name of the database file inside the `local_database.path` directory
+pub const DATABASE_FILE: &str = "mini-payment.sqlite3";
+
+/// the schema changes in the order that they are applied, the index of the last applied migration
+/// (plus one) is saved in the `user_version` of the database. Never edit a migration that was
+/// released, add a new one
+const MIGRATIONS: &[&str] = &[
+    include_str!("migrations/0001_initial.sql"),
+    include_str!("migrations/0002_document_by_country.sql"),
+    include_str!("migrations/0003_alphanumeric_documents.sql"),
+    include_str!("migrations/0004_document_types.sql"),
+    include_str!("migrations/0005_kyc_status.sql"),
+    include_str!("migrations/0006_closed_clients.sql"),
+    include_str!("migrations/0007_client_creation_time.sql"),
+    include_str!("migrations/0008_holds.sql"),
+];
+
+/// the clients, their balances and the ledger stored in a SQLite database. Every mutation runs in
+/// a single sql transaction so a crash never leaves half of a movement written.
+///
+/// Every request takes its own connection from the pool, so the reads never wait for the writes
+/// (the database is in WAL mode). SQLite only has one writer at a time, but the checks of the
+/// balances are done inside the same `IMMEDIATE` transaction that writes the movement
+#[derive(Debug)]
+pub struct SqliteDatabase {
+    pool: Pool<SqliteConnectionManager>,
+    accounts: StripedLocks,
+}
+
+type PooledSqlite = PooledConnection<SqliteConnectionManager>;
+
+impl SqliteDatabase {
+    /// open (or create) the database inside the directory `path` and bring the schema up to date.
+    /// A settlement that was interrupted by a crash is completed or aborted here
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, DatabaseError> {
+        fs::create_dir_all(&path)?;
+        let manager = SqliteConnectionManager::file(path.as_ref().join(DATABASE_FILE));
+        let database = Self::from_pool(Pool::new(manager.with_init(init_connection))?)?;
+        database
+            .connection()?
+            .pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+        Ok(database)
+    }
+
+    /// database that lives only in memory, useful for the tests
+    pub fn in_memory() -> Result<Self, DatabaseError> {
+        // NOTE(elsuizo: 2025-08-09): every connection to `:memory:` is a different database, so
+        // here the pool has only one connection that is never closed
+        let pool = Pool::builder()
+            .max_size(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .build(SqliteConnectionManager::memory().with_init(init_connection))?;
+        Self::from_pool(pool)
+    }
+
+    fn from_pool(pool: Pool<SqliteConnectionManager>) -> Result<Self, DatabaseError> {
+        let database = Self {
+            pool,
+            accounts: StripedLocks::default(),
+        };
+        database.migrate()?;
+        database.recover_settlement()?;
+        Ok(database)
+    }
+
+    /// a connection of the pool, it goes back to the pool when it is dropped
+    fn connection(&self) -> Result<PooledSqlite, DatabaseError> {
+        Ok(self.pool.get()?)
+    }
+
+    /// apply all the migrations that are not in the database yet
+    fn migrate(&self) -> Result<(), DatabaseError> {
+        let mut connection = self.connection()?;
+        let version: usize =
+            connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version > MIGRATIONS.len() {
+            return Err(DatabaseError::UnsupportedSchemaVersion(version));
+        }
+        // NOTE(elsuizo: 2025-08-16): the migrations that rebuild a table need the foreign keys
+        // disabled and that can not be changed inside a transaction, so we check them by hand
+        // before every commit (https://www.sqlite.org/lang_altertable.html#otheralter)
+        connection.pragma_update(None, "foreign_keys", false)?;
+        let migrated = apply_migrations(&mut connection, version);
+        connection.pragma_update(None, "foreign_keys", true)?;
+        migrated
+    }
+
+    /// the version of the schema of the database
+    pub fn schema_version(&self) -> Result<usize, DatabaseError> {
+        Ok(self
+            .connection()?
+            .pragma_query_value(None, "user_version", |row| row.get(0))?)
+    }
+
+    /// the balance of the user in `currency` computed only from his ledger entries
+    pub fn rebuild_balance(&self, id: Uuid, currency: Currency) -> Result<Decimal, DatabaseError> {
+        let connection = self.connection()?;
+        account_balance(&connection, id, currency)?;
+        let amounts = connection
+            .prepare(
+                "SELECT direction, amount FROM ledger_entries
+                 WHERE client_id = ?1 AND currency = ?2",
+            )?
+            .query_map(params![id.to_string(), currency.code()], |row| {
+                let direction: ledger::Direction = parse(row, 0)?;
+                Ok(direction.signed(parse(row, 1)?))
+            })?
+            .collect::<Result<Vec<Decimal>, _>>()?;
+        Ok(amounts.into_iter().sum())
+    }
+
+    fn settle(
+        &self,
+        directory: &Path,
+        scheduled: Option<ScheduledRun>,
+    ) -> Result<SettlementSummary, DatabaseError> {
+        // NOTE(elsuizo: 2025-08-09): the file is written outside of the sql transactions, so we
+        // lock all the accounts to be sure that the balances don't move until they are zeroed
+        let _accounts = self.accounts.lock_everything();
+        if let Some(run) = scheduled {
+            let settlements = self.settlements()?;
+            if let Some(done) = run.already_settled(&settlements) {
+                return Err(DatabaseError::AlreadySettled(done.id));
+            }
+        }
+        let scheduled_for = scheduled.map(|run| run.fire_time);
+        let (id, settlement) = self.start_settlement(directory, scheduled_for)?;
+        if let Err(e) = settlement.write_atomically(directory) {
+            let _ = fs::remove_file(settlement::tmp_path(directory, &settlement.file_name()));
+            self.connection()?.execute(
+                "DELETE FROM pending_settlement WHERE id = ?1",
+                [id.to_string()],
+            )?;
+            return Err(e.into());
+        }
+        self.finish_settlement(id, directory, &settlement, scheduled_for)
+    }
+
+    /// save the settlement as pending before the file is written, so we know what to do if we
+    /// crash in the middle
+    fn start_settlement(
+        &self,
+        directory: &Path,
+        scheduled_for: Option<DateTime<Utc>>,
+    ) -> Result<(Uuid, SettlementFile), DatabaseError> {
+        let mut connection = self.connection()?;
+        let tx = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        // the money of the active holds is not settled, it stays in the accounts
+        let held = held_by_account(&tx, Utc::now())?;
+        let records = tx
+            .prepare("SELECT client_id, currency, balance FROM accounts")?
+            .query_map([], |row| {
+                let client_id = parse(row, 0)?;
+                let currency = parse(row, 1)?;
+                let held = held.get(&(client_id, currency)).copied();
+                Ok(SettlementRecord {
+                    client_id,
+                    currency,
+                    amount: parse::<Decimal>(row, 2)? - held.unwrap_or_default(),
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        let sequence: u64 = tx.query_row(
+            "SELECT COALESCE(MAX(sequence), 0) + 1 FROM settlements",
+            [],
+            |row| row.get(0),
+        )?;
+        let settlement = SettlementFile::new(sequence, records);
+        let id = Uuid::new_v4();
+        tx.execute(
+            "INSERT INTO pending_settlement (id, sequence, directory, file_name, scheduled_for)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                id.to_string(),
+                sequence,
+                directory.to_string_lossy(),
+                settlement.file_name(),
+                scheduled_for.map(|t| t.to_rfc3339()),
+            ],
+        )?;
+        tx.commit()?;
+        Ok((id, settlement))
+    }
+
+    /// zero the balances that were written in the settlement file and save the settlement, all in
+    /// the same sql transaction
+    fn finish_settlement(
+        &self,
+        id: Uuid,
+        directory: &Path,
+        settlement: &SettlementFile,
+        scheduled_for: Option<DateTime<Utc>>,
+    ) -> Result<SettlementSummary, DatabaseError> {
+        let legs: Vec<Leg> = settlement
+            .records
+            .iter()
+            .filter(|record| !record.amount.is_zero())
+            .map(|record| Leg::debit(record.client_id, record.currency, record.amount))
+            .collect();
+        let summary = settlement.summary(id, scheduled_for);
+        let totals = serde_json::to_string(&summary.totals).map_err(|_| DatabaseError::Other)?;
+
+        let mut connection = self.connection()?;
+        let tx = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        write_legs(&tx, &legs, |entry| entry.transaction_id = id)?;
+        tx.execute(
+            "INSERT INTO settlements
+             (id, sequence, file_name, path, created_at, client_count, totals, scheduled_for)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                id.to_string(),
+                summary.sequence,
+                summary.file_name,
+                directory.join(&summary.file_name).to_string_lossy(),
+                summary.created_at.to_rfc3339(),
+                summary.client_count,
+                totals,
+                scheduled_for.map(|t| t.to_rfc3339()),
+            ],
+        )?;
+        tx.execute(
+            "DELETE FROM pending_settlement WHERE id = ?1",
+            [id.to_string()],
+        )?;
+        save_pending_response(&tx, &summary)?;
+        tx.commit()?;
+        Ok(summary)
+    }
+
+    /// we crash in the middle of a settlement: if the file was written we finish the settlement
+    /// with the balances of the file, if not we abort it and the balances stay as they are
+    fn recover_settlement(&self) -> Result<(), DatabaseError> {
+        let pending = self
+            .connection()?
+            .query_row(
+                "SELECT id, sequence, directory, file_name, scheduled_for FROM pending_settlement",
+                [],
+                |row| {
+                    Ok((
+                        parse::<Uuid>(row, 0)?,
+                        row.get::<_, u64>(1)?,
+                        PathBuf::from(row.get::<_, String>(2)?),
+                        row.get::<_, String>(3)?,
+                        parse_optional::<DateTime<Utc>>(row, 4)?,
+                    ))
+                },
+            )
+            .optional()?;
+        let Some((id, sequence, directory, file_name, scheduled_for)) = pending else {
+            return Ok(());
+        };
+        match SettlementFile::recover(&directory, &file_name, sequence) {
+            Some(settlement) => {
+                log::warn!("completing the interrupted settlement {id} ({file_name})");
+                self.finish_settlement(id, &directory, &settlement, scheduled_for)?;
+            }
+            None => {
+                log::warn!(
+                    "aborting the interrupted settlement {id} ({file_name}), the file was not written"
+                );
+                self.connection()?.execute(
+                    "DELETE FROM pending_settlement WHERE id = ?1",
+                    [id.to_string()],
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Storage for SqliteDatabase {
+    fn insert_new_user(&self, new_user: &User) -> Result<Uuid, CreateUserError> {
+        let mut connection = self.connection()?;
+        let tx = connection
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(DatabaseError::from)?;
+        if find_document(&tx, &new_user.document())?.is_some() {
+            return Err(CreateUserError::UserAlreadyExistsError(new_user.document()));
+        }
+        let id = Uuid::new_v4();
+        tx.execute(
+            "INSERT INTO clients
+             (id, client_name, birth_date, document_type, issuing_country, document_number, country,
+              kyc_status, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                id.to_string(),
+                new_user.client_name.inner_ref(),
+                new_user.get_bird_date().to_string(),
+                new_user.get_document_type().as_str(),
+                new_user.get_issuing_country().inner_ref(),
+                new_user.get_document_number(),
+                new_user.get_country_name(),
+                new_user.kyc_status().as_str(),
+                new_user.created_at().map(|at| at.to_rfc3339()),
+            ],
+        )
+        .map_err(DatabaseError::from)?;
+        for (currency, balance) in new_user.balances() {
+            insert_account(&tx, id, *currency, *balance)?;
+        }
+        save_pending_response(&tx, &id)?;
+        tx.commit().map_err(DatabaseError::from)?;
+        Ok(id)
+    }
+
+    fn get_user(&self, id: Uuid) -> Result<User, DatabaseError> {
+        read_user(&*self.connection()?, id)
+    }
+
+    fn find_user_by_document(&self, document: &Document) -> Result<(Uuid, User), DatabaseError> {
+        let id = find_document(&*self.connection()?, document)?
+            .ok_or_else(|| DatabaseError::UnknownDocument(document.clone()))?;
+        Ok((id, self.get_user(id)?))
+    }
+
+    fn set_kyc_status(&self, id: Uuid, status: KycStatus) -> Result<(), DatabaseError> {
+        let _account = self.accounts.lock(&id);
+        let mut connection = self.connection()?;
+        let tx = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        // a closed client is frozen, not even the compliance team can change it
+        check_open(&tx, id)?;
+        tx.execute(
+            "UPDATE clients SET kyc_status = ?1 WHERE id = ?2 AND closed_at IS NULL",
+            params![status.as_str(), id.to_string()],
+        )?;
+        save_pending_response(&tx, &())?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn list_users(&self, listing: &ClientListing) -> Result<ClientPage, DatabaseError> {
+        // NOTE(elsuizo: 2025-10-04): the balances are saved as text, so the filters and the sort
+        // are the same of the log storage and run over all the clients in memory
+        let users = read_users(&*self.connection()?)?;
+        listing.page(users.iter().map(|(id, user)| (*id, user)))
+    }
+
+    fn update_profile(&self, id: Uuid, change: &ProfileChange) -> Result<User, DatabaseError> {
+        let _account = self.accounts.lock(&id);
+        let mut connection = self.connection()?;
+        let tx = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let mut user = read_user(&tx, id)?;
+        user.check_open(id)?;
+        let currencies = user.balances().keys().copied().collect::<Vec<_>>();
+        user.change_profile(change);
+        tx.execute(
+            "UPDATE clients SET client_name = ?1, issuing_country = ?2, country = ?3 WHERE id = ?4",
+            params![
+                user.client_name.inner_ref(),
+                user.get_issuing_country().inner_ref(),
+                user.get_country_name(),
+                id.to_string(),
+            ],
+        )?;
+        for currency in user.balances().keys() {
+            if !currencies.contains(currency) {
+                insert_account(&tx, id, *currency, Decimal::ZERO)?;
+            }
+        }
+        tx.commit()?;
+        Ok(user)
+    }
+
+    fn close_user(&self, id: Uuid, at: DateTime<Utc>) -> Result<User, DatabaseError> {
+        let _account = self.accounts.lock(&id);
+        let mut connection = self.connection()?;
+        let tx = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let mut user = read_user(&tx, id)?;
+        user.close(id, at)?;
+        tx.execute(
+            "UPDATE clients SET closed_at = ?1 WHERE id = ?2",
+            params![at.to_rfc3339(), id.to_string()],
+        )?;
+        save_pending_response(&tx, &user)?;
+        tx.commit()?;
+        Ok(user)
+    }
+
+    fn get_balance(&self, id: Uuid, currency: Currency) -> Result<Decimal, DatabaseError> {
+        let connection = self.connection()?;
+        account_balance(&connection, id, currency)
+    }
+
+    fn open_account(&self, id: Uuid, currency: Currency) -> Result<(), DatabaseError> {
+        let _account = self.accounts.lock(&id);
+        let mut connection = self.connection()?;
+        let tx = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        check_open(&tx, id)?;
+        match account_balance(&tx, id, currency) {
+            Ok(_) => return Err(DatabaseError::AccountAlreadyExists(id, currency)),
+            Err(DatabaseError::UnknownAccount(_, _)) => {}
+            Err(e) => return Err(e),
+        }
+        insert_account(&tx, id, currency, Decimal::ZERO)?;
+        save_pending_response(&tx, &())?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn find_user_and_increase_balance(
+        &self,
+        id: Uuid,
+        currency: Currency,
+        amount: Decimal,
+    ) -> Result<LedgerEntry, DatabaseError> {
+        validate_amount(currency, amount)?;
+        let _account = self.accounts.lock(&id);
+        let mut connection = self.connection()?;
+        let tx = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        account_balance(&tx, id, currency)?;
+        check_may_transact(&tx, id)?;
+        let entries = write_legs(&tx, &[Leg::credit(id, currency, amount)], |_| {})?;
+        let entry = find_entry(&entries, id, currency)?;
+        save_pending_response(&tx, &entry)?;
+        tx.commit()?;
+        Ok(entry)
+    }
+
+    fn find_user_and_decrease_balance(
+        &self,
+        id: Uuid,
+        currency: Currency,
+        amount: Decimal,
+    ) -> Result<LedgerEntry, DatabaseError> {
+        validate_amount(currency, amount)?;
+        let _account = self.accounts.lock(&id);
+        let mut connection = self.connection()?;
+        let tx = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let balance = available_balance(&tx, id, currency)?;
+        check_may_transact(&tx, id)?;
+        if balance < amount {
+            return Err(DatabaseError::InsufficientBalance(balance));
+        }
+        let entries = write_legs(&tx, &[Leg::debit(id, currency, amount)], |_| {})?;
+        let entry = find_entry(&entries, id, currency)?;
+        save_pending_response(&tx, &entry)?;
+        tx.commit()?;
+        Ok(entry)
+    }
+
+    fn transfer(
+        &self,
+        from: Uuid,
+        to: Uuid,
+        currency: Currency,
+        amount: Decimal,
+    ) -> Result<(LedgerEntry, LedgerEntry), DatabaseError> {
+        if from == to {
+            return Err(DatabaseError::SelfTransfer(from));
+        }
+        validate_amount(currency, amount)?;
+        let _accounts = self.accounts.lock_all([&from, &to]);
+        let mut connection = self.connection()?;
+        let tx = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let balance = available_balance(&tx, from, currency)?;
+        account_balance(&tx, to, currency)?;
+        check_may_transact(&tx, from)?;
+        check_may_transact(&tx, to)?;
+        if balance < amount {
+            return Err(DatabaseError::InsufficientBalance(balance));
+        }
+        let entries = write_legs(
+            &tx,
+            &[
+                Leg::debit(from, currency, amount),
+                Leg::credit(to, currency, amount),
+            ],
+            |_| {},
+        )?;
+        let out = (
+            find_entry(&entries, from, currency)?,
+            find_entry(&entries, to, currency)?,
+        );
+        save_pending_response(&tx, &out)?;
+        tx.commit()?;
+        Ok(out)
+    }
+
+    fn convert(
+        &self,
+        id: Uuid,
+        from: Currency,
+        to: Currency,
+        amount: Decimal,
+        rate: Decimal,
+    ) -> Result<(LedgerEntry, LedgerEntry), DatabaseError> {
+        if from == to {
+            return Err(DatabaseError::SameCurrencyConversion(from));
+        }
+        if amount <= Decimal::ZERO || !from.is_representable(amount) {
+            return Err(DatabaseError::InvalidAmount(amount));
+        }
+        let converted = to.round(amount * rate);
+        if converted <= Decimal::ZERO {
+            return Err(DatabaseError::InvalidAmount(amount));
+        }
+        let _account = self.accounts.lock(&id);
+        let mut connection = self.connection()?;
+        let tx = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let balance = available_balance(&tx, id, from)?;
+        account_balance(&tx, id, to)?;
+        check_may_transact(&tx, id)?;
+        if balance < amount {
+            return Err(DatabaseError::InsufficientBalance(balance));
+        }
+        let entries = write_legs(
+            &tx,
+            &[Leg::debit(id, from, amount), Leg::credit(id, to, converted)],
+            |entry| entry.exchange_rate = Some(rate),
+        )?;
+        let out = (
+            find_entry(&entries, id, from)?,
+            find_entry(&entries, id, to)?,
+        );
+        save_pending_response(&tx, &out)?;
+        tx.commit()?;
+        Ok(out)
+    }
+
+    fn place_hold(
+        &self,
+        id: Uuid,
+        currency: Currency,
+        amount: Decimal,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Hold, DatabaseError> {
+        let _account = self.accounts.lock(&id);
+        let mut connection = self.connection()?;
+        let tx = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let available = available_balance(&tx, id, currency)?;
+        check_may_transact(&tx, id)?;
+        let hold = Hold::new(id, currency, amount, Utc::now(), expires_at)?;
+        if available < amount {
+            return Err(DatabaseError::InsufficientBalance(available));
+        }
+        insert_hold(&tx, &hold)?;
+        save_pending_response(&tx, &hold)?;
+        tx.commit()?;
+        Ok(hold)
+    }
+
+    fn get_hold(&self, hold_id: Uuid) -> Result<Hold, DatabaseError> {
+        read_hold(&*self.connection()?, hold_id)
+    }
+
+    fn client_holds(&self, id: Uuid) -> Result<Vec<Hold>, DatabaseError> {
+        let connection = self.connection()?;
+        client_exists(&connection, id)?;
+        let holds = connection
+            .prepare(&format!(
+                "SELECT {HOLD_COLUMNS} FROM holds WHERE client_id = ?1 ORDER BY created_at, rowid"
+            ))?
+            .query_map([id.to_string()], hold_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(holds)
+    }
+
+    fn capture_hold(
+        &self,
+        hold_id: Uuid,
+        amount: Option<Decimal>,
+    ) -> Result<(Hold, LedgerEntry), DatabaseError> {
+        let id = self.get_hold(hold_id)?.client_id;
+        let _account = self.accounts.lock(&id);
+        let mut connection = self.connection()?;
+        let tx = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        check_may_transact(&tx, id)?;
+        let mut hold = read_hold(&tx, hold_id)?;
+        let amount = hold.capture(amount, Utc::now())?;
+        let balance = account_balance(&tx, id, hold.currency)?;
+        if balance < amount {
+            return Err(DatabaseError::InsufficientBalance(balance));
+        }
+        let entries = write_legs(&tx, &[Leg::debit(id, hold.currency, amount)], |entry| {
+            entry.transaction_id = hold_id
+        })?;
+        update_hold(&tx, &hold)?;
+        let entry = find_entry(&entries, id, hold.currency)?;
+        let out = (hold, entry);
+        save_pending_response(&tx, &out)?;
+        tx.commit()?;
+        Ok(out)
+    }
+
+    fn void_hold(&self, hold_id: Uuid) -> Result<Hold, DatabaseError> {
+        let id = self.get_hold(hold_id)?.client_id;
+        let _account = self.accounts.lock(&id);
+        let mut connection = self.connection()?;
+        let tx = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let mut hold = read_hold(&tx, hold_id)?;
+        hold.void(Utc::now())?;
+        update_hold(&tx, &hold)?;
+        save_pending_response(&tx, &hold)?;
+        tx.commit()?;
+        Ok(hold)
+    }
+
+    fn expire_holds(&self, now: DateTime<Utc>) -> Result<usize, DatabaseError> {
+        let mut connection = self.connection()?;
+        let tx = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let mut count = 0;
+        for mut hold in active_holds(&tx)? {
+            if hold.expire(now) {
+                update_hold(&tx, &hold)?;
+                count += 1;
+            }
+        }
+        tx.commit()?;
+        Ok(count)
+    }
+
+    fn ledger_entries(&self, id: Uuid) -> Result<Vec<LedgerEntry>, DatabaseError> {
+        let connection = self.connection()?;
+        client_exists(&connection, id)?;
+        let entries = connection
+            .prepare(
+                "SELECT id, transaction_id, client_id, currency, direction, amount, balance,
+                        timestamp, exchange_rate
+                 FROM ledger_entries WHERE client_id = ?1 ORDER BY seq",
+            )?
+            .query_map([id.to_string()], |row| {
+                Ok(LedgerEntry {
+                    id: parse(row, 0)?,
+                    transaction_id: parse(row, 1)?,
+                    client_id: parse(row, 2)?,
+                    currency: parse(row, 3)?,
+                    direction: parse(row, 4)?,
+                    amount: parse(row, 5)?,
+                    balance: parse(row, 6)?,
+                    timestamp: parse(row, 7)?,
+                    exchange_rate: parse_optional(row, 8)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(entries)
+    }
+
+    fn get_saved_response(
+        &self,
+        key: &IdempotencyKey,
+    ) -> Result<Option<SavedResponse>, DatabaseError> {
+        Ok(self
+            .connection()?
+            .query_row(
+                "SELECT fingerprint, status, body FROM idempotency_keys WHERE key = ?1",
+                [key.inner_ref()],
+                |row| {
+                    Ok(SavedResponse {
+                        fingerprint: row.get(0)?,
+                        status: row.get(1)?,
+                        body: row.get(2)?,
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    fn save_response(
+        &self,
+        key: IdempotencyKey,
+        response: SavedResponse,
+    ) -> Result<(), DatabaseError> {
+        insert_response(&*self.connection()?, &key, &response)
+    }
+
+    fn store_balances(&self, directory: &Path) -> Result<SettlementSummary, DatabaseError> {
+        self.settle(directory, None)
+    }
+
+    fn store_scheduled_balances(
+        &self,
+        directory: &Path,
+        run: ScheduledRun,
+    ) -> Result<SettlementSummary, DatabaseError> {
+        self.settle(directory, Some(run))
+    }
+
+    fn settlements(&self) -> Result<Vec<SettlementSummary>, DatabaseError> {
+        let settlements = self
+            .connection()?
+            .prepare(
+                "SELECT id, sequence, file_name, created_at, client_count, totals, scheduled_for
+                 FROM settlements ORDER BY sequence",
+            )?
+            .query_map([], settlement_summary)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(settlements)
+    }
+
+    fn get_settlement(&self, id: Uuid) -> Result<SettlementRun, DatabaseError> {
+        self.connection()?
+            .query_row(
+                "SELECT id, sequence, file_name, created_at, client_count, totals, scheduled_for,
+                        path
+                 FROM settlements WHERE id = ?1",
+                [id.to_string()],
+                |row| {
+                    Ok(SettlementRun {
+                        summary: settlement_summary(row)?,
+                        path: PathBuf::from(row.get::<_, String>(7)?),
+                    })
+                },
+            )
+            .optional()?
+            .ok_or(DatabaseError::UnknownSettlement(id))
+    }
+}
+
+fn apply_migrations(connection: &mut Connection, version: usize) -> Result<(), DatabaseError> {
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = connection.transaction()?;
+        tx.execute_batch(migration)?;
+        if tx.prepare("PRAGMA foreign_key_check")?.exists([])? {
+            return Err(DatabaseError::ForeignKeyViolation(index + 1));
+        }
+        tx.pragma_update(None, "user_version", index + 1)?;
+        tx.commit()?;
+        log::info!("database migrated to the version {}", index + 1);
+    }
+    Ok(())
+}
+
+/// the settings of every new connection of the pool
+fn init_connection(connection: &mut Connection) -> rusqlite::Result<()> {
+    connection.pragma_update(None, "foreign_keys", true)?;
+    connection.pragma_update(None, "synchronous", "FULL")
+}
+
+/// the id of the client with the `document`
+fn find_document(
+    connection: &Connection,
+    document: &Document,
+) -> Result<Option<Uuid>, DatabaseError> {
+    Ok(connection
+        .query_row(
+            "SELECT id FROM clients
+             WHERE document_type = ?1 AND issuing_country = ?2 AND document_number = ?3",
+            params![
+                document.document_type().as_str(),
+                document.issuing_country().inner_ref(),
+                document.number().inner_ref()
+            ],
+            |row| parse::<Uuid>(row, 0),
+        )
+        .optional()?)
+}
+
+/// the columns of the clients that `user_from_row` reads, the id is the first one
+const USER_COLUMNS: &str = "id, client_name, birth_date, document_type, issuing_country,
+                            document_number, country, kyc_status, closed_at, created_at";
+
+/// the client of a row with the `USER_COLUMNS`, without his accounts
+fn user_from_row(row: &Row) -> rusqlite::Result<(Uuid, User)> {
+    let client_name = row.get::<_, String>(1)?;
+    let issuing_country = row.get::<_, String>(4)?;
+    let document_number = row.get::<_, String>(5)?;
+    let country = row.get::<_, String>(6)?;
+    let document = Document::from_stored(
+        parse::<DocumentType>(row, 3)?,
+        CountryName::from_stored(issuing_country),
+        DocumentNumber::from_stored(document_number),
+    );
+    let mut user = User::from_stored(
+        UserName::parse_and_validate(&client_name).map_err(|e| invalid_column(1, e))?,
+        parse::<NaiveDate>(row, 2)?.into(),
+        document,
+        CountryName::from_stored(country),
+    );
+    user.set_kyc_status(parse::<KycStatus>(row, 7)?);
+    user.set_closed_at(parse_optional(row, 8)?);
+    user.set_created_at(parse_optional(row, 9)?);
+    Ok((parse(row, 0)?, user))
+}
+
+/// the client `id` with all his accounts
+fn read_user(connection: &Connection, id: Uuid) -> Result<User, DatabaseError> {
+    let (_, mut user) = connection
+        .query_row(
+            &format!("SELECT {USER_COLUMNS} FROM clients WHERE id = ?1"),
+            [id.to_string()],
+            user_from_row,
+        )
+        .optional()?
+        .ok_or(DatabaseError::UnknownUser(id))?;
+    let accounts = connection
+        .prepare("SELECT currency, balance FROM accounts WHERE client_id = ?1")?
+        .query_map([id.to_string()], |row| {
+            Ok((parse::<Currency>(row, 0)?, parse::<Decimal>(row, 1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    for (currency, balance) in accounts {
+        user.open_account(currency);
+        user.increase_credit(currency, balance);
+    }
+    Ok(user)
+}
+
+/// all the clients with all their accounts
+fn read_users(connection: &Connection) -> Result<HashMap<Uuid, User>, DatabaseError> {
+    let mut users = connection
+        .prepare(&format!("SELECT {USER_COLUMNS} FROM clients"))?
+        .query_map([], user_from_row)?
+        .collect::<Result<HashMap<_, _>, _>>()?;
+    let accounts = connection
+        .prepare("SELECT client_id, currency, balance FROM accounts")?
+        .query_map([], |row| {
+            Ok((
+                parse::<Uuid>(row, 0)?,
+                parse::<Currency>(row, 1)?,
+                parse::<Decimal>(row, 2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    for (id, currency, balance) in accounts {
+        if let Some(user) = users.get_mut(&id) {
+            user.open_account(currency);
+            user.increase_credit(currency, balance);
+        }
+    }
+    Ok(users)
+}
+
+fn client_exists(connection: &Connection, id: Uuid) -> Result<(), DatabaseError> {
+    connection
+        .query_row(
+            "SELECT 1 FROM clients WHERE id = ?1",
+            [id.to_string()],
+            |_| Ok(()),
+        )
+        .optional()?
+        .ok_or(DatabaseError::UnknownUser(id))
+}
+
+/// `ClientClosed` if the client `id` was closed
+fn check_open(connection: &Connection, id: Uuid) -> Result<(), DatabaseError> {
+    let closed_at = connection
+        .query_row(
+            "SELECT closed_at FROM clients WHERE id = ?1",
+            [id.to_string()],
+            |row| parse_optional::<DateTime<Utc>>(row, 0),
+        )
+        .optional()?
+        .ok_or(DatabaseError::UnknownUser(id))?;
+    match closed_at {
+        Some(_) => Err(DatabaseError::ClientClosed(id)),
+        None => Ok(()),
+    }
+}
+
+/// `ClientClosed` or `ClientNotVerified` if the client `id` can not move money
+fn check_may_transact(connection: &Connection, id: Uuid) -> Result<(), DatabaseError> {
+    check_open(connection, id)?;
+    let status = connection
+        .query_row(
+            "SELECT kyc_status FROM clients WHERE id = ?1",
+            [id.to_string()],
+            |row| parse::<KycStatus>(row, 0),
+        )
+        .optional()?
+        .ok_or(DatabaseError::UnknownUser(id))?;
+    if status.may_transact() {
+        Ok(())
+    } else {
+        Err(DatabaseError::ClientNotVerified(id, status))
+    }
+}
+
+/// the balance of the account of the user `id` in `currency`
+fn account_balance(
+    connection: &Connection,
+    id: Uuid,
+    currency: Currency,
+) -> Result<Decimal, DatabaseError> {
+    let balance = connection
+        .query_row(
+            "SELECT balance FROM accounts WHERE client_id = ?1 AND currency = ?2",
+            params![id.to_string(), currency.code()],
+            |row| parse::<Decimal>(row, 0),
+        )
+        .optional()?;
+    match balance {
+        Some(balance) => Ok(balance),
+        None => {
+            client_exists(connection, id)?;
+            Err(DatabaseError::UnknownAccount(id, currency))
+        }
+    }
+}
+
+/// the columns of the holds that `hold_from_row` reads
+const HOLD_COLUMNS: &str = "id, client_id, currency, amount, captured_amount, status, created_at,
+                            expires_at, closed_at";
+
+fn hold_from_row(row: &Row) -> rusqlite::Result<Hold> {
+    Ok(Hold {
+        id: parse(row, 0)?,
+        client_id: parse(row, 1)?,
+        currency: parse(row, 2)?,
+        amount: parse(row, 3)?,
+        captured_amount: parse_optional(row, 4)?,
+        status: parse(row, 5)?,
+        created_at: parse(row, 6)?,
+        expires_at: parse(row, 7)?,
+        closed_at: parse_optional(row, 8)?,
+    })
+}
+
+fn read_hold(connection: &Connection, hold_id: Uuid) -> Result<Hold, DatabaseError> {
+    connection
+        .query_row(
+            &format!("SELECT {HOLD_COLUMNS} FROM holds WHERE id = ?1"),
+            [hold_id.to_string()],
+            hold_from_row,
+        )
+        .optional()?
+        .ok_or(DatabaseError::UnknownHold(hold_id))
+}
+
+/// the holds that are still marked as active, some of them can be already expired
+fn active_holds(connection: &Connection) -> Result<Vec<Hold>, DatabaseError> {
+    let holds = connection
+        .prepare(&format!(
+            "SELECT {HOLD_COLUMNS} FROM holds WHERE status = 'active'"
+        ))?
+        .query_map([], hold_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(holds)
+}
+
+/// the money that the holds reserve at the time `now` in every account that has some
+fn held_by_account(
+    connection: &Connection,
+    now: DateTime<Utc>,
+) -> Result<HashMap<(Uuid, Currency), Decimal>, DatabaseError> {
+    let mut held = HashMap::new();
+    for hold in active_holds(connection)? {
+        if hold.is_outstanding(now) {
+            *held.entry((hold.client_id, hold.currency)).or_default() += hold.amount;
+        }
+    }
+    Ok(held)
+}
+
+/// the balance of the account of the user `id` in `currency` without the money of the holds
+fn available_balance(
+    connection: &Connection,
+    id: Uuid,
+    currency: Currency,
+) -> Result<Decimal, DatabaseError> {
+    let balance = account_balance(connection, id, currency)?;
+    let holds = connection
+        .prepare(&format!(
+            "SELECT {HOLD_COLUMNS} FROM holds
+             WHERE status = 'active' AND client_id = ?1 AND currency = ?2"
+        ))?
+        .query_map(params![id.to_string(), currency.code()], hold_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    let held = hold::held_amounts(&holds, Utc::now()).remove(&currency);
+    Ok(balance - held.unwrap_or_default())
+}
+
+fn insert_hold(connection: &Connection, hold: &Hold) -> Result<(), DatabaseError> {
+    connection.execute(
+        &format!("INSERT INTO holds ({HOLD_COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"),
+        params![
+            hold.id.to_string(),
+            hold.client_id.to_string(),
+            hold.currency.code(),
+            hold.amount.to_string(),
+            hold.captured_amount.map(|amount| amount.to_string()),
+            hold.status.as_str(),
+            hold.created_at.to_rfc3339(),
+            hold.expires_at.to_rfc3339(),
+            hold.closed_at.map(|at| at.to_rfc3339()),
+        ],
+    )?;
+    Ok(())
+}
+
+/// save the new status of the hold, the rest of the hold never changes
+fn update_hold(connection: &Connection, hold: &Hold) -> Result<(), DatabaseError> {
+    connection.execute(
+        "UPDATE holds SET captured_amount = ?1, status = ?2, closed_at = ?3 WHERE id = ?4",
+        params![
+            hold.captured_amount.map(|amount| amount.to_string()),
+            hold.status.as_str(),
+            hold.closed_at.map(|at| at.to_rfc3339()),
+            hold.id.to_string(),
+        ],
+    )?;
+    Ok(())
+}
+
+fn insert_account(
+    connection: &Connection,
+    id: Uuid,
+    currency: Currency,
+    balance: Decimal,
+) -> Result<(), DatabaseError> {
+    connection.execute(
+        "INSERT INTO accounts (client_id, currency, balance) VALUES (?1, ?2, ?3)",
+        params![id.to_string(), currency.code(), balance.to_string()],
+    )?;
+    Ok(())
+}
+
+/// the balance after the last entry of the account in the ledger
+fn ledger_balance(
+    connection: &Connection,
+    id: Uuid,
+    currency: Currency,
+) -> Result<Decimal, DatabaseError> {
+    let balance = connection
+        .query_row(
+            "SELECT balance FROM ledger_entries WHERE client_id = ?1 AND currency = ?2
+             ORDER BY seq DESC LIMIT 1",
+            params![id.to_string(), currency.code()],
+            |row| parse::<Decimal>(row, 0),
+        )
+        .optional()?;
+    Ok(balance.unwrap_or_default())
+}
+
+fn insert_response(
+    connection: &Connection,
+    key: &IdempotencyKey,
+    response: &SavedResponse,
+) -> Result<(), DatabaseError> {
+    connection.execute(
+        "INSERT INTO idempotency_keys (key, fingerprint, status, body) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            key.inner_ref(),
+            response.fingerprint,
+            response.status,
+            response.body
+        ],
+    )?;
+    Ok(())
+}
+
+/// write the response of the idempotent request that runs in this thread (if any) in the
+/// transaction of its changes, `out` is what we return to the request
+fn save_pending_response<T: Any>(connection: &Connection, out: &T) -> Result<(), DatabaseError> {
+    if let Some((key, response)) = idempotency::take_pending_response(out)? {
+        insert_response(connection, &key, &response)?;
+    }
+    Ok(())
+}
+
+/// build the entries of the legs (and their counterpart in the external account) and write them
+/// with the new balances of the clients, the caller owns the sql transaction
+fn write_legs(
+    connection: &Connection,
+    legs: &[Leg],
+    mut edit: impl FnMut(&mut LedgerEntry),
+) -> Result<Vec<LedgerEntry>, DatabaseError> {
+    let mut balances = HashMap::new();
+    for leg in legs {
+        for account in [
+            (leg.client_id, leg.currency),
+            (EXTERNAL_ACCOUNT, leg.currency),
+        ] {
+            if let Entry::Vacant(balance) = balances.entry(account) {
+                balance.insert(ledger_balance(connection, account.0, account.1)?);
+            }
+        }
+    }
+    let mut entries = ledger::prepare_entries(legs, |client_id, currency| {
+        balances
+            .get(&(client_id, currency))
+            .copied()
+            .unwrap_or_default()
+    });
+    let mut insert = connection.prepare(
+        "INSERT INTO ledger_entries
+         (id, transaction_id, client_id, currency, direction, amount, balance, timestamp,
+          exchange_rate)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+    )?;
+    for entry in entries.iter_mut() {
+        edit(entry);
+        insert.execute(params![
+            entry.id.to_string(),
+            entry.transaction_id.to_string(),
+            entry.client_id.to_string(),
+            entry.currency.code(),
+            entry.direction.as_str(),
+            entry.amount.to_string(),
+            entry.balance.to_string(),
+            entry.timestamp.to_rfc3339(),
+            entry.exchange_rate.map(|rate| rate.to_string()),
+        ])?;
+        if entry.client_id != EXTERNAL_ACCOUNT {
+            let updated = connection.execute(
+                "UPDATE accounts SET balance = ?1 WHERE client_id = ?2 AND currency = ?3",
+                params![
+                    entry.balance.to_string(),
+                    entry.client_id.to_string(),
+                    entry.currency.code()
+                ],
+            )?;
+            if updated == 0 {
+                return Err(DatabaseError::UnknownAccount(
+                    entry.client_id,
+                    entry.currency,
+                ));
+            }
+        }
+    }
+    Ok(entries)
+}
+
+fn find_entry(
+    entries: &[LedgerEntry],
+    id: Uuid,
+    currency: Currency,
+) -> Result<LedgerEntry, DatabaseError> {
+    entries
+        .iter()
+        .find(|entry| entry.client_id == id && entry.currency == currency)
+        .cloned()
+        .ok_or(DatabaseError::Other)
+}
+
+fn settlement_summary(row: &Row) -> rusqlite::Result<SettlementSummary> {
+    let totals = row.get::<_, String>(5)?;
+    Ok(SettlementSummary {
+        id: parse(row, 0)?,
+        sequence: row.get(1)?,
+        file_name: row.get(2)?,
+        created_at: parse(row, 3)?,
+        client_count: row.get(4)?,
+        totals: serde_json::from_str(&totals).map_err(|e| invalid_column(5, e))?,
+        scheduled_for: parse_optional(row, 6)?,
+    })
+}
+
+/// read a text column with the `FromStr` of `T`
+fn parse<T>(row: &Row, index: usize) -> rusqlite::Result<T>
+where
+    T: FromStr,
+    T::Err: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    row.get::<_, String>(index)?
+        .parse()
+        .map_err(|e: T::Err| invalid_column(index, e))
+}
+
+fn parse_optional<T>(row: &Row, index: usize) -> rusqlite::Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    row.get::<_, Option<String>>(index)?
+        .map(|text| text.parse().map_err(|e: T::Err| invalid_column(index, e)))
+        .transpose()
+}
+
+fn invalid_column(
+    index: usize,
+    error: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(index, Type::Text, error.into())
+}
+
+//-------------------------------------------------------------------------
+//                        unit tests
+//-------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::country::test_country;
+    use crate::currency::Currency;
+    use crate::document::DocumentType;
+    use crate::hold::HoldStatus;
+    use crate::ledger::Direction;
+    use crate::onboarding::KycStatus;
+    use crate::settlement::ScheduledRun;
+    use crate::sqlite_database::{DATABASE_FILE, MIGRATIONS, SqliteDatabase};
+    use crate::storage::Storage;
+    use crate::user::{DatabaseError, Document, User, UserName};
+    use chrono::{NaiveDate, Utc};
+    use claims::{assert_err, assert_ok};
+    use rust_decimal::dec;
+
+    const ARS: Currency = Currency::Ars;
+
+    fn test_user(document_number: &str) -> User {
+        let country = test_country("Argentina");
+        let mut user = User::new(
+            UserName::parse_and_validate("Martin Noblia").unwrap(),
+            NaiveDate::from_ymd_opt(1985, 4, 9).unwrap().into(),
+            Document::parse_and_validate(DocumentType::Dni, &country, document_number).unwrap(),
+            &country,
+        );
+        user.set_kyc_status(KycStatus::Verified);
+        user
+    }
+
+    fn test_directory() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("mini-payment-{}", uuid::Uuid::new_v4()))
+    }
+
+    /// create the directory and return the path of the database file inside
+    fn path_with_file(path: &std::path::Path) -> std::path::PathBuf {
+        std::fs::create_dir_all(path).unwrap();
+        path.join(DATABASE_FILE)
+    }
+
+    #[test]
+    fn all_the_migrations_are_applied() {
+        let db = SqliteDatabase::in_memory().expect("error opening the database");
+        assert_eq!(db.schema_version().unwrap(), MIGRATIONS.len());
+    }
+
+    #[test]
+    fn a_database_from_a_newer_version_is_rejected() {
+        let path = test_directory();
+        let db = SqliteDatabase::open(&path).expect("error opening the database");
+        db.connection()
+            .unwrap()
+            .pragma_update(None, "user_version", MIGRATIONS.len() + 1)
+            .unwrap();
+        drop(db);
+
+        assert!(matches!(
+            SqliteDatabase::open(&path),
+            Err(DatabaseError::UnsupportedSchemaVersion(_))
+        ));
+    }
+
+    #[test]
+    fn users_and_balances_survive_a_restart() {
+        let path = test_directory();
+        let db = SqliteDatabase::open(&path).expect("error opening the database");
+        let id = db
+            .insert_new_user(&test_user("10000001"))
+            .expect("error inserting");
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(10.25)));
+        assert_ok!(db.find_user_and_decrease_balance(id, ARS, dec!(0.05)));
+        drop(db);
+
+        let db = SqliteDatabase::open(&path).expect("error opening the database");
+        let user = db.get_user(id).expect("the user was not saved");
+        assert_eq!(user.get_actual_credit(ARS), Some(dec!(10.20)));
+        assert_eq!(db.rebuild_balance(id, ARS).unwrap(), dec!(10.20));
+        assert_eq!(db.ledger_entries(id).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn balances_keep_all_the_decimals() {
+        let db = SqliteDatabase::in_memory().expect("error opening the database");
+        let id = db
+            .insert_new_user(&test_user("10000001"))
+            .expect("error inserting");
+        // a REAL would give 0.30000000000000004 and lose the cents of the big amount
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(0.1)));
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(0.2)));
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(0.3));
+        let amount = dec!(12345678901234567.89);
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, amount));
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(12345678901234568.19));
+    }
+
+    #[test]
+    fn a_failed_debit_does_not_write_anything() {
+        let db = SqliteDatabase::in_memory().expect("error opening the database");
+        let id = db
+            .insert_new_user(&test_user("10000001"))
+            .expect("error inserting");
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(1)));
+
+        assert_err!(db.find_user_and_decrease_balance(id, ARS, dec!(2)));
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(1));
+        assert_eq!(db.ledger_entries(id).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn only_positive_amounts_are_credited_or_debited() {
+        let db = SqliteDatabase::in_memory().expect("error opening the database");
+        let id = db
+            .insert_new_user(&test_user("10000001"))
+            .expect("error inserting");
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(10)));
+
+        for amount in [dec!(0), dec!(-50)] {
+            assert!(matches!(
+                db.find_user_and_increase_balance(id, ARS, amount),
+                Err(DatabaseError::InvalidAmount(_))
+            ));
+            assert!(matches!(
+                db.find_user_and_decrease_balance(id, ARS, amount),
+                Err(DatabaseError::InvalidAmount(_))
+            ));
+        }
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(10));
+        assert_eq!(db.ledger_entries(id).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn the_amounts_must_fit_in_the_minor_units_of_the_currency() {
+        let db = SqliteDatabase::in_memory().expect("error opening the database");
+        let id = db
+            .insert_new_user(&test_user("10000001"))
+            .expect("error inserting");
+        let other = db
+            .insert_new_user(&test_user("10000002"))
+            .expect("error inserting");
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(30)));
+
+        for amount in [dec!(0.0001), dec!(1.001)] {
+            assert_err!(db.find_user_and_increase_balance(id, ARS, amount));
+            assert_err!(db.find_user_and_decrease_balance(id, ARS, amount));
+            assert_err!(db.transfer(id, other, ARS, amount));
+        }
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(0.010)));
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(30.01));
+    }
+
+    #[test]
+    fn duplicated_documents_are_rejected() {
+        let db = SqliteDatabase::in_memory().expect("error opening the database");
+        let id = db
+            .insert_new_user(&test_user("10000001"))
+            .expect("error inserting");
+        assert_err!(db.insert_new_user(&test_user("10000001")));
+
+        let (found, user) = db
+            .find_user_by_document(&test_user("10000001").document())
+            .unwrap();
+        assert_eq!(found, id);
+        assert_eq!(user.get_document_number(), "10000001");
+        assert_eq!(user.get_document_type(), DocumentType::Dni);
+        assert_err!(db.find_user_by_document(&test_user("10000002").document()));
+
+        // the same number in a passport is other document
+        let argentina = test_country("Argentina");
+        let passport =
+            Document::parse_and_validate(DocumentType::Passport, &argentina, "10000001").unwrap();
+        let traveller = User::new(
+            UserName::parse_and_validate("Juan Perez").unwrap(),
+            NaiveDate::from_ymd_opt(1990, 1, 2).unwrap().into(),
+            passport.clone(),
+            &test_country("Chile"),
+        );
+        let traveller_id = db.insert_new_user(&traveller).expect("error inserting");
+        let (found, user) = db.find_user_by_document(&passport).unwrap();
+        assert_eq!(found, traveller_id);
+        assert_eq!(user.get_issuing_country(), &argentina.name());
+        assert_eq!(user.get_country_name(), "Chile");
+    }
+
+    #[test]
+    fn the_clients_survive_the_migration_of_the_documents() {
+        let path = test_directory();
+        let connection = rusqlite::Connection::open(path_with_file(&path)).unwrap();
+        connection.execute_batch(MIGRATIONS[0]).unwrap();
+        connection
+            .execute_batch(
+                "INSERT INTO clients VALUES ('6c4ed6d8-54a4-4d8e-9fa2-0f4d2b5f6d1e', 'Martin Noblia',
+                                             '1985-04-09', 10000001, 'Argentina');
+                 INSERT INTO accounts VALUES ('6c4ed6d8-54a4-4d8e-9fa2-0f4d2b5f6d1e', 'ARS', '3');
+                 PRAGMA user_version = 1;",
+            )
+            .unwrap();
+        drop(connection);
+
+        let db = SqliteDatabase::open(&path).expect("error opening the database");
+        let (id, user) = db
+            .find_user_by_document(&test_user("10000001").document())
+            .unwrap();
+        assert_eq!(user.get_document_type(), DocumentType::Dni);
+        assert_eq!(user.kyc_status(), KycStatus::Verified);
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(3));
+        assert_err!(db.insert_new_user(&test_user("10000001")));
+    }
+
+    #[test]
+    fn only_the_verified_clients_move_money() {
+        let db = SqliteDatabase::in_memory().expect("error opening the database");
+        let verified = db.insert_new_user(&test_user("10000001")).unwrap();
+        let mut pending = test_user("10000002");
+        pending.set_kyc_status(KycStatus::Pending);
+        let pending = db.insert_new_user(&pending).unwrap();
+        assert_ok!(db.find_user_and_increase_balance(verified, ARS, dec!(10)));
+
+        assert!(matches!(
+            db.find_user_and_increase_balance(pending, ARS, dec!(1)),
+            Err(DatabaseError::ClientNotVerified(_, KycStatus::Pending))
+        ));
+        assert_err!(db.transfer(verified, pending, ARS, dec!(1)));
+        assert_eq!(
+            db.get_user(pending).unwrap().kyc_status(),
+            KycStatus::Pending
+        );
+        assert_ok!(db.set_kyc_status(pending, KycStatus::Verified));
+        assert_ok!(db.transfer(verified, pending, ARS, dec!(1)));
+        assert_ok!(db.set_kyc_status(verified, KycStatus::Rejected));
+        assert_err!(db.find_user_and_decrease_balance(verified, ARS, dec!(1)));
+        assert_err!(db.set_kyc_status(uuid::Uuid::new_v4(), KycStatus::Verified));
+    }
+
+    #[test]
+    fn transfers_write_both_sides() {
+        let db = SqliteDatabase::in_memory().expect("error opening the database");
+        let from = db
+            .insert_new_user(&test_user("10000001"))
+            .expect("error inserting");
+        let to = db
+            .insert_new_user(&test_user("10000002"))
+            .expect("error inserting");
+        assert_ok!(db.find_user_and_increase_balance(from, ARS, dec!(10)));
+
+        let (from_entry, to_entry) = db.transfer(from, to, ARS, dec!(4)).unwrap();
+        assert_eq!(from_entry.transaction_id, to_entry.transaction_id);
+        assert_eq!(from_entry.direction, Direction::Debit);
+        assert_eq!(db.get_balance(from, ARS).unwrap(), dec!(6));
+        assert_eq!(db.get_balance(to, ARS).unwrap(), dec!(4));
+        assert_err!(db.transfer(from, to, ARS, dec!(7)));
+    }
+
+    #[test]
+    fn concurrent_debits_never_overdraw() {
+        let path = test_directory();
+        let db = SqliteDatabase::open(&path).expect("error opening the database");
+        let id = db
+            .insert_new_user(&test_user("10000001"))
+            .expect("error inserting");
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(50)));
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                let db = &db;
+                scope.spawn(move || {
+                    for _ in 0..20 {
+                        let _ = db.find_user_and_decrease_balance(id, ARS, dec!(1));
+                    }
+                });
+            }
+        });
+
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(0));
+        assert_eq!(db.rebuild_balance(id, ARS).unwrap(), dec!(0));
+        assert_eq!(db.ledger_entries(id).unwrap().len(), 51);
+    }
+
+    #[test]
+    fn settlements_zero_the_balances() {
+        let path = test_directory();
+        let db = SqliteDatabase::open(&path).expect("error opening the database");
+        let id = db
+            .insert_new_user(&test_user("10000001"))
+            .expect("error inserting");
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(3)));
+
+        let summary = db.store_balances(&path.join("settlements")).unwrap();
+        assert_eq!(summary.sequence, 1);
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(0));
+        assert_eq!(db.settlements().unwrap(), vec![summary.clone()]);
+        let run = db.get_settlement(summary.id).unwrap();
+        assert!(run.path.exists());
+        assert_eq!(db.ledger_entries(id).unwrap()[1].transaction_id, summary.id);
+    }
+
+    #[test]
+    fn a_scheduled_run_already_settled_by_hand_does_nothing() {
+        let path = test_directory();
+        let directory = path.join("settlements");
+        let db = SqliteDatabase::open(&path).expect("error opening the database");
+        let id = db
+            .insert_new_user(&test_user("10000001"))
+            .expect("error inserting user");
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(3)));
+        let run = ScheduledRun {
+            window_start: Utc::now() - chrono::Duration::hours(1),
+            fire_time: Utc::now() + chrono::Duration::hours(1),
+        };
+
+        let manual = db.store_balances(&directory).unwrap();
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(2)));
+        assert!(matches!(
+            db.store_scheduled_balances(&directory, run),
+            Err(DatabaseError::AlreadySettled(settlement_id)) if settlement_id == manual.id
+        ));
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(2));
+        assert_eq!(db.settlements().unwrap(), vec![manual]);
+
+        // the next window is not settled yet
+        let next = ScheduledRun {
+            window_start: run.fire_time,
+            fire_time: run.fire_time + chrono::Duration::days(1),
+        };
+        let scheduled = db.store_scheduled_balances(&directory, next).unwrap();
+        assert_eq!(scheduled.scheduled_for, Some(next.fire_time));
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(0));
+    }
+
+    #[test]
+    fn an_interrupted_settlement_with_the_file_written_is_completed_on_open() {
+        let path = test_directory();
+        let directory = path.join("settlements");
+        let db = SqliteDatabase::open(&path).expect("error opening the database");
+        let id = db
+            .insert_new_user(&test_user("10000001"))
+            .expect("error inserting");
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(3)));
+        let (settlement_id, settlement) = db.start_settlement(&directory, None).unwrap();
+        settlement.write_atomically(&directory).unwrap();
+        // crash before the balances are stored
+        drop(db);
+
+        let db = SqliteDatabase::open(&path).expect("error opening the database");
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(0));
+        assert_eq!(db.settlements().unwrap()[0].id, settlement_id);
+        let summary = db.store_balances(&directory).unwrap();
+        assert_eq!(summary.sequence, 2);
+    }
+
+    #[test]
+    fn an_interrupted_settlement_without_file_is_aborted_on_open() {
+        let path = test_directory();
+        let directory = path.join("settlements");
+        let db = SqliteDatabase::open(&path).expect("error opening the database");
+        let id = db
+            .insert_new_user(&test_user("10000001"))
+            .expect("error inserting");
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(3)));
+        assert_ok!(db.start_settlement(&directory, None));
+        drop(db);
+
+        let db = SqliteDatabase::open(&path).expect("error opening the database");
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(3));
+        assert!(db.settlements().unwrap().is_empty());
+        let summary = db.store_balances(&directory).unwrap();
+        assert_eq!(summary.sequence, 1);
+    }
+
+    #[test]
+    fn the_holds_reserve_money_until_they_are_captured_voided_or_expired() {
+        let path = test_directory();
+        let db = SqliteDatabase::open(&path).expect("error opening the database");
+        let id = db.insert_new_user(&test_user("10000001")).unwrap();
+        assert_ok!(db.find_user_and_increase_balance(id, ARS, dec!(10)));
+        let expires_at = Utc::now() + chrono::Duration::hours(1);
+        let captured = db.place_hold(id, ARS, dec!(6), expires_at).unwrap();
+        let voided = db.place_hold(id, ARS, dec!(3), expires_at).unwrap();
+        let expired = db.place_hold(id, ARS, dec!(1), expires_at).unwrap();
+
+        assert!(matches!(
+            db.transfer(id, db.insert_new_user(&test_user("10000002")).unwrap(), ARS, dec!(1)),
+            Err(DatabaseError::InsufficientBalance(available)) if available.is_zero()
+        ));
+        let (hold, entry) = db.capture_hold(captured.id, Some(dec!(5))).unwrap();
+        assert_eq!(hold.captured_amount, Some(dec!(5)));
+        assert_eq!(entry.balance, dec!(5));
+        assert_eq!(entry.transaction_id, captured.id);
+        assert_ok!(db.void_hold(voided.id));
+        assert!(matches!(
+            db.void_hold(voided.id),
+            Err(DatabaseError::HoldNotActive(_, HoldStatus::Voided))
+        ));
+
+        // the held money is not settled
+        let summary = db.store_balances(&path.join("settlements")).unwrap();
+        assert_eq!(summary.totals[&ARS].total, dec!(4));
+        assert_eq!(db.get_balance(id, ARS).unwrap(), dec!(1));
+        assert_eq!(db.expire_holds(expires_at).unwrap(), 1);
+        drop(db);
+
+        let db = SqliteDatabase::open(&path).expect("error opening the database");
+        let holds = db.client_holds(id).unwrap();
+        let ids = holds.iter().map(|hold| hold.id).collect::<Vec<_>>();
+        assert_eq!(ids, [captured.id, voided.id, expired.id]);
+        assert_eq!(holds[0], hold);
+        assert_eq!(holds[2].status, HoldStatus::Expired);
+        assert_ok!(db.find_user_and_decrease_balance(id, ARS, dec!(1)));
+        assert!(matches!(
+            db.get_hold(uuid::Uuid::new_v4()),
+            Err(DatabaseError::UnknownHold(_))
+        ));
+    }
+}
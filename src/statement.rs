@@ -0,0 +1,203 @@
+use crate::currency::Currency;
+use crate::ledger::{Direction, LedgerEntry};
+use crate::user::DatabaseError;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::fmt::Write;
+use uuid::Uuid;
+
+/// the columns of the statements in csv
+pub const CSV_HEADER: &str =
+    "timestamp,transaction_id,entry_id,currency,direction,amount,balance,exchange_rate";
+
+/// The filters of the statement of a client, the entries are always in the order that they were
+/// written in the ledger (the oldest first) and every one has the balance of its account after it
+#[derive(Debug, Clone, Default)]
+pub struct StatementQuery {
+    pub currency: Option<Currency>,
+    /// the entries since this day (inclusive, in UTC)
+    pub from: Option<NaiveDate>,
+    /// the entries until this day (inclusive, in UTC)
+    pub to: Option<NaiveDate>,
+    pub direction: Option<Direction>,
+    pub min_amount: Option<Decimal>,
+    pub max_amount: Option<Decimal>,
+}
+
+/// one page of a statement
+#[derive(Debug)]
+pub struct StatementPage {
+    pub entries: Vec<LedgerEntry>,
+    /// the id of the last entry of the page, `None` in the last page
+    pub next_cursor: Option<Uuid>,
+}
+
+impl StatementQuery {
+    pub const DEFAULT_LIMIT: usize = 100;
+    pub const MAX_LIMIT: usize = 1000;
+
+    pub fn matches(&self, entry: &LedgerEntry) -> bool {
+        let day = entry.timestamp.date_naive();
+        self.currency.is_none_or(|c| entry.currency == c)
+            && self.from.is_none_or(|from| day >= from)
+            && self.to.is_none_or(|to| day <= to)
+            && self.direction.is_none_or(|d| entry.direction == d)
+            && self.min_amount.is_none_or(|min| entry.amount >= min)
+            && self.max_amount.is_none_or(|max| entry.amount <= max)
+    }
+
+    /// the entries of the statement, from the ledger `entries` of the client
+    pub fn filter(&self, entries: Vec<LedgerEntry>) -> Vec<LedgerEntry> {
+        entries
+            .into_iter()
+            .filter(|entry| self.matches(entry))
+            .collect()
+    }
+
+    /// the page of at most `limit` entries that starts after the entry `after`. The ledger is
+    /// never rewritten, so a cursor is valid for ever and the new entries go to the last page
+    pub fn page(
+        &self,
+        entries: Vec<LedgerEntry>,
+        after: Option<Uuid>,
+        limit: usize,
+    ) -> Result<StatementPage, DatabaseError> {
+        let start = match after {
+            Some(after) => {
+                entries
+                    .iter()
+                    .position(|entry| entry.id == after)
+                    .ok_or_else(|| DatabaseError::InvalidCursor(after.to_string()))?
+                    + 1
+            }
+            None => 0,
+        };
+        let limit = limit.clamp(1, Self::MAX_LIMIT);
+        let mut entries = entries
+            .into_iter()
+            .skip(start)
+            .filter(|entry| self.matches(entry))
+            .take(limit + 1)
+            .collect::<Vec<_>>();
+        let next_cursor = (entries.len() > limit).then(|| entries[limit - 1].id);
+        entries.truncate(limit);
+        Ok(StatementPage {
+            entries,
+            next_cursor,
+        })
+    }
+}
+
+/// the `entries` in csv, with the `CSV_HEADER`
+pub fn to_csv(entries: &[LedgerEntry]) -> String {
+    let mut csv = format!("{CSV_HEADER}\n");
+    for entry in entries {
+        // NOTE(elsuizo: 2025-10-11): none of the fields can have a comma or a quote, so they are
+        // never quoted
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{},{},{}",
+            entry.timestamp.to_rfc3339(),
+            entry.transaction_id,
+            entry.id,
+            entry.currency.code(),
+            entry.direction.as_str(),
+            entry.amount,
+            entry.balance,
+            entry
+                .exchange_rate
+                .map(|rate| rate.to_string())
+                .unwrap_or_default(),
+        );
+    }
+    csv
+}
+
+//-------------------------------------------------------------------------
+//                        unit tests
+//-------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::currency::Currency;
+    use crate::ledger::{Direction, Ledger, LedgerEntry, Leg};
+    use crate::statement::{CSV_HEADER, StatementQuery, to_csv};
+    use claims::assert_err;
+    use rust_decimal::{Decimal, dec};
+    use uuid::Uuid;
+
+    /// the entries of a client with a credit of every amount and a debit of 1 after each one
+    fn entries(client_id: Uuid, amounts: &[Decimal]) -> Vec<LedgerEntry> {
+        let mut ledger = Ledger::default();
+        for amount in amounts {
+            for leg in [
+                Leg::credit(client_id, Currency::Ars, *amount),
+                Leg::debit(client_id, Currency::Ars, dec!(1)),
+            ] {
+                for entry in ledger.prepare(&[leg]) {
+                    ledger.append(entry);
+                }
+            }
+        }
+        ledger.entries(client_id).to_vec()
+    }
+
+    #[test]
+    fn the_statement_is_read_by_pages() {
+        let id = Uuid::new_v4();
+        let entries = entries(id, &[dec!(10), dec!(20), dec!(30)]);
+        let query = StatementQuery::default();
+
+        let page = query.page(entries.clone(), None, 4).unwrap();
+        assert_eq!(page.entries.len(), 4);
+        assert_eq!(page.entries[3].balance, dec!(28));
+        let cursor = page.next_cursor.unwrap();
+        assert_eq!(cursor, page.entries[3].id);
+
+        let page = query.page(entries.clone(), Some(cursor), 4).unwrap();
+        assert_eq!(page.entries.len(), 2);
+        assert_eq!(page.entries[1].balance, dec!(57));
+        assert!(page.next_cursor.is_none());
+
+        assert_err!(query.page(entries, Some(Uuid::new_v4()), 4));
+    }
+
+    #[test]
+    fn the_entries_can_be_filtered() {
+        let id = Uuid::new_v4();
+        let entries = entries(id, &[dec!(10), dec!(20), dec!(30)]);
+        let query = StatementQuery {
+            direction: Some(Direction::Credit),
+            min_amount: Some(dec!(15)),
+            ..Default::default()
+        };
+        let page = query.page(entries.clone(), None, 10).unwrap();
+        let amounts = page.entries.iter().map(|e| e.amount).collect::<Vec<_>>();
+        assert_eq!(amounts, [dec!(20), dec!(30)]);
+
+        let tomorrow = chrono::Utc::now().date_naive().succ_opt();
+        let query = StatementQuery {
+            from: tomorrow,
+            ..Default::default()
+        };
+        assert!(query.filter(entries.clone()).is_empty());
+        let query = StatementQuery {
+            currency: Some(Currency::Usd),
+            ..Default::default()
+        };
+        assert!(query.filter(entries).is_empty());
+    }
+
+    #[test]
+    fn the_statement_can_be_written_in_csv() {
+        let id = Uuid::new_v4();
+        let entries = entries(id, &[dec!(10.5)]);
+        let csv = to_csv(&entries);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(lines.len(), 3);
+        let fields = lines[1].split(',').collect::<Vec<_>>();
+        assert_eq!(fields.len(), 8);
+        assert_eq!(fields[3..], ["ARS", "credit", "10.5", "10.5", ""]);
+        assert!(lines[2].ends_with(",ARS,debit,1,9.5,"));
+    }
+}
@@ -0,0 +1,166 @@
+use crate::currency::Currency;
+use crate::hold::Hold;
+use crate::idempotency::{IdempotencyKey, SavedResponse};
+use crate::ledger::LedgerEntry;
+use crate::listing::{ClientListing, ClientPage};
+use crate::local_database::SettlementRun;
+use crate::onboarding::KycStatus;
+use crate::settlement::{ScheduledRun, SettlementSummary};
+use crate::user::{CreateUserError, DatabaseError, Document, ProfileChange, User};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// the storage shared by all the handlers and the background tasks
+pub type SharedStorage = Arc<dyn Storage>;
+
+/// Everything that the service needs from the place where the clients and their money live.
+/// `local_database::Database` is the implementation that we use, any other backend (or a fake
+/// for the tests) only needs to implement this trait to be used by `service::run`.
+///
+/// The storage is shared by all the workers without a global lock, so every implementation must
+/// do its own locking: the operations over different accounts should run in parallel and the ones
+/// over the same account one after the other
+pub trait Storage: fmt::Debug + Send + Sync {
+    /// save a new user and return his id, two users with the same data are not allowed
+    fn insert_new_user(&self, new_user: &User) -> Result<Uuid, CreateUserError>;
+
+    fn get_user(&self, id: Uuid) -> Result<User, DatabaseError>;
+
+    /// the user with the `document`, and his id
+    fn find_user_by_document(&self, document: &Document) -> Result<(Uuid, User), DatabaseError>;
+
+    /// change the kyc status of the user, only the verified users can move money
+    fn set_kyc_status(&self, id: Uuid, status: KycStatus) -> Result<(), DatabaseError>;
+
+    /// the page of the users that the `listing` selects
+    fn list_users(&self, listing: &ClientListing) -> Result<ClientPage, DatabaseError>;
+
+    /// change the name or the country of the user and return him changed, a closed user can not
+    /// be changed
+    fn update_profile(&self, id: Uuid, change: &ProfileChange) -> Result<User, DatabaseError>;
+
+    /// close the user at the time `at`, the user is never deleted but he can not move money any
+    /// more. Only the users with all their balances in zero can be closed
+    fn close_user(&self, id: Uuid, at: DateTime<Utc>) -> Result<User, DatabaseError>;
+
+    /// the balance of the account of the user `id` in `currency`
+    fn get_balance(&self, id: Uuid, currency: Currency) -> Result<Decimal, DatabaseError>;
+
+    /// open a new empty account in `currency` for the user `id`
+    fn open_account(&self, id: Uuid, currency: Currency) -> Result<(), DatabaseError>;
+
+    /// credit `amount` to the user and return the ledger entry of the movement
+    fn find_user_and_increase_balance(
+        &self,
+        id: Uuid,
+        currency: Currency,
+        amount: Decimal,
+    ) -> Result<LedgerEntry, DatabaseError>;
+
+    /// debit `amount` from the user and return the ledger entry of the movement
+    fn find_user_and_decrease_balance(
+        &self,
+        id: Uuid,
+        currency: Currency,
+        amount: Decimal,
+    ) -> Result<LedgerEntry, DatabaseError>;
+
+    /// move `amount` from the user `from` to the user `to` in a single transaction, so either both
+    /// balances change or none of them. Return the entries of `from` and `to`
+    fn transfer(
+        &self,
+        from: Uuid,
+        to: Uuid,
+        currency: Currency,
+        amount: Decimal,
+    ) -> Result<(LedgerEntry, LedgerEntry), DatabaseError>;
+
+    /// debit `amount` from the account of the user in `from` and credit it, converted with `rate`,
+    /// in his account in `to`. The converted amount is rounded down to the minor units of `to`.
+    /// Return the entries of the two accounts, both with the applied rate
+    fn convert(
+        &self,
+        id: Uuid,
+        from: Currency,
+        to: Currency,
+        amount: Decimal,
+        rate: Decimal,
+    ) -> Result<(LedgerEntry, LedgerEntry), DatabaseError>;
+
+    /// reserve `amount` of the account of the user `id` in `currency` until `expires_at`. The money
+    /// stays in the balance but it is not available for any other debit
+    fn place_hold(
+        &self,
+        id: Uuid,
+        currency: Currency,
+        amount: Decimal,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Hold, DatabaseError>;
+
+    fn get_hold(&self, hold_id: Uuid) -> Result<Hold, DatabaseError>;
+
+    /// all the holds of the user, the oldest first
+    fn client_holds(&self, id: Uuid) -> Result<Vec<Hold>, DatabaseError>;
+
+    /// debit `amount` of the hold (all of it when is `None`) and release the rest. Return the
+    /// captured hold and the ledger entry of the debit, its transaction id is the id of the hold
+    fn capture_hold(
+        &self,
+        hold_id: Uuid,
+        amount: Option<Decimal>,
+    ) -> Result<(Hold, LedgerEntry), DatabaseError>;
+
+    /// release the hold without moving any money
+    fn void_hold(&self, hold_id: Uuid) -> Result<Hold, DatabaseError>;
+
+    /// mark as expired all the active holds that expired before `now` and return how many
+    fn expire_holds(&self, now: DateTime<Utc>) -> Result<usize, DatabaseError>;
+
+    /// all the ledger entries of the user in the order that they were written
+    fn ledger_entries(&self, id: Uuid) -> Result<Vec<LedgerEntry>, DatabaseError>;
+
+    /// the response saved the first time that `key` was used
+    fn get_saved_response(
+        &self,
+        key: &IdempotencyKey,
+    ) -> Result<Option<SavedResponse>, DatabaseError>;
+
+    fn save_response(
+        &self,
+        key: IdempotencyKey,
+        response: SavedResponse,
+    ) -> Result<(), DatabaseError>;
+
+    /// write the balance of every account in a new settlement file inside `directory` and leave
+    /// all the balances in zero (but the money of the active holds, that stays in the accounts).
+    /// The balances only change once the file is safe in the disk, if the file can not be written
+    /// nothing changes
+    fn store_balances(&self, directory: &Path) -> Result<SettlementSummary, DatabaseError>;
+
+    /// like `store_balances` but the settlement is started by the schedule `run`. It fails with
+    /// `AlreadySettled` if some settlement already covers the run, this is checked with all the
+    /// accounts locked so a settlement done by hand at the same time can not slip in
+    fn store_scheduled_balances(
+        &self,
+        directory: &Path,
+        run: ScheduledRun,
+    ) -> Result<SettlementSummary, DatabaseError>;
+
+    /// all the settlements done, the oldest first
+    fn settlements(&self) -> Result<Vec<SettlementSummary>, DatabaseError>;
+
+    fn get_settlement(&self, id: Uuid) -> Result<SettlementRun, DatabaseError>;
+}
+
+/// `InvalidAmount` if `amount` is not positive or it has more decimals than the minor units of
+/// `currency`, every backend checks the amounts of the credits, debits and transfers with this
+pub fn validate_amount(currency: Currency, amount: Decimal) -> Result<(), DatabaseError> {
+    if amount <= Decimal::ZERO || !currency.is_representable(amount) {
+        return Err(DatabaseError::InvalidAmount(amount));
+    }
+    Ok(())
+}
@@ -1,5 +1,13 @@
-use chrono::NaiveDate;
+use crate::country::Country;
+use crate::currency::Currency;
+use crate::document::{self, DocumentRule, DocumentType};
+use crate::hold::HoldStatus;
+use crate::onboarding::KycStatus;
+use chrono::{DateTime, NaiveDate, Utc};
 use rust_decimal::Decimal;
+use serde_aux::field_attributes::deserialize_string_from_number;
+use std::collections::BTreeMap;
+use std::fmt;
 use std::hash::{Hash, Hasher};
 use thiserror::Error;
 use unicode_segmentation::UnicodeSegmentation;
@@ -17,79 +25,379 @@ pub enum CreateUserError {
     #[error("invalid county name: {0}")]
     InvalidCountryName(String),
     #[error("Invalid Document number: {0}")]
-    InvalidDocumentNumber(usize),
-    #[error("a user with this document number {0:?}, already exists!!!")]
-    UserAlreadyExistsError(DocumentNumber),
+    InvalidDocumentNumber(String),
+    #[error("a client with the document {0} already exists")]
+    UserAlreadyExistsError(Document),
+    #[error("invalid birth date: {0}, use the format YYYY-MM-DD or DD/MM/YYYY")]
+    InvalidBirthDate(String),
+    #[error("the birth date {0} is in the future")]
+    BirthDateInTheFuture(NaiveDate),
+    #[error("the birth date {0} is not plausible")]
+    ImplausibleBirthDate(NaiveDate),
+    #[error("the clients of {1} must be at least {0} years old")]
+    UnderMinimumAge(u32, String),
+    #[error("invalid fields: {}", field_names(.0))]
+    InvalidFields(Vec<(&'static str, CreateUserError)>),
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+}
+
+fn field_names(errors: &[(&'static str, CreateUserError)]) -> String {
+    errors
+        .iter()
+        .map(|(field, _)| *field)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Collect the errors of all the fields of a request, so the client can see every field that is
+/// wrong and not only the first one
+#[derive(Debug, Default)]
+pub struct FieldErrors(Vec<(&'static str, CreateUserError)>);
+
+impl FieldErrors {
+    /// the value of the `field` or `None` if it is not valid, the error is saved
+    pub fn check<T>(
+        &mut self,
+        field: &'static str,
+        result: Result<T, CreateUserError>,
+    ) -> Option<T> {
+        result.map_err(|error| self.0.push((field, error))).ok()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn into_error(self) -> CreateUserError {
+        CreateUserError::InvalidFields(self.0)
+    }
 }
 
 #[derive(Error, Debug)]
 pub enum DatabaseError {
-    #[error("invalid name: {0:?}")]
+    #[error("unknown client {0}")]
     UnknownUser(Uuid),
     #[error("Insufficient Balance {0}")]
     InsufficientBalance(Decimal),
+    #[error("the client {0} has no account in {1}")]
+    UnknownAccount(Uuid, Currency),
+    #[error("the client {0} already has an account in {1}")]
+    AccountAlreadyExists(Uuid, Currency),
+    #[error("the client {0} can not move money, his kyc status is {1}")]
+    ClientNotVerified(Uuid, KycStatus),
+    #[error("a client can not transfer to himself: {0}")]
+    SelfTransfer(Uuid),
+    #[error("invalid amount: {0}")]
+    InvalidAmount(Decimal),
+    #[error("can not convert {0} to the same currency")]
+    SameCurrencyConversion(Currency),
+    #[error("unknown exchange rate from {0} to {1}")]
+    UnknownExchangeRate(Currency, Currency),
+    #[error("invalid idempotency key: {0}")]
+    InvalidIdempotencyKey(String),
+    #[error("the idempotency key {0} was already used with a different request")]
+    IdempotencyKeyReused(String),
+    #[error("unknown settlement: {0}")]
+    UnknownSettlement(Uuid),
+    #[error("the settlement {0} was already done in the window of this run")]
+    AlreadySettled(Uuid),
+    #[error("there is no client with the document {0}")]
+    UnknownDocument(Document),
+    #[error("the client {0} is closed")]
+    ClientClosed(Uuid),
+    #[error("the client {0} can not be closed, he still has {2} {1}")]
+    BalanceNotZero(Uuid, Currency, Decimal),
+    #[error("invalid cursor: {0}")]
+    InvalidCursor(String),
+    #[error("the balances can only be used with a currency")]
+    CurrencyRequired,
+    #[error("unknown hold: {0}")]
+    UnknownHold(Uuid),
+    #[error("the hold {0} is {1}, only the active holds can be captured or voided")]
+    HoldNotActive(Uuid, HoldStatus),
+    #[error("storage error: {0}")]
+    Storage(#[from] std::io::Error),
+    #[error("a lock was poisoned by a panic in other thread")]
+    Poisoned,
+    #[error("sqlite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("no database connection available: {0}")]
+    ConnectionPool(#[from] r2d2::Error),
+    #[error("the database schema version {0} is newer than the supported by this version")]
+    UnsupportedSchemaVersion(usize),
+    #[error("the migration {0} leaves rows that break the foreign keys")]
+    ForeignKeyViolation(usize),
     #[error("UnknownError")]
     Other,
 }
 
-#[derive(Debug, Clone, Eq, serde::Deserialize)]
+/// An identity document, the same number can be used by other country or by other type of
+/// document so the three together identify a person. No two users can have the same document
+#[derive(Debug, Clone, Hash, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct Document {
+    document_type: DocumentType,
+    issuing_country: CountryName,
+    number: DocumentNumber,
+}
+
+impl Document {
+    /// validate the number with the rules of the type of document and the country that issued it
+    pub fn parse_and_validate(
+        document_type: DocumentType,
+        issuing_country: &Country,
+        raw_number: &str,
+    ) -> Result<Self, CreateUserError> {
+        let number = DocumentNumber::parse_and_validate(
+            raw_number,
+            issuing_country.document_rule(document_type),
+        )?;
+        Ok(Self {
+            document_type,
+            issuing_country: issuing_country.name(),
+            number,
+        })
+    }
+
+    /// a document that was validated when it was saved
+    pub(crate) fn from_stored(
+        document_type: DocumentType,
+        issuing_country: CountryName,
+        number: DocumentNumber,
+    ) -> Self {
+        Self {
+            document_type,
+            issuing_country,
+            number,
+        }
+    }
+
+    pub fn document_type(&self) -> DocumentType {
+        self.document_type
+    }
+
+    pub fn issuing_country(&self) -> &CountryName {
+        &self.issuing_country
+    }
+
+    pub fn number(&self) -> &DocumentNumber {
+        &self.number
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} ({})",
+            self.document_type.as_str(),
+            self.number.inner_ref(),
+            self.issuing_country.inner_ref()
+        )
+    }
+}
+
+#[derive(Debug, Clone, Eq, serde::Deserialize, serde::Serialize)]
 pub struct User {
     pub client_name: UserName,
-    bird_date: NaiveDate,
+    bird_date: BirthDate,
+    // NOTE(elsuizo: 2025-08-30): the users saved before we had the types of documents have only
+    // the number, that was always the national document of their `country`
+    #[serde(default)]
+    document_type: DocumentType,
     document_number: DocumentNumber,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    issuing_country: Option<CountryName>,
     country: CountryName,
-    credit: Decimal,
+    #[serde(default = "KycStatus::of_old_clients")]
+    kyc_status: KycStatus,
+    // NOTE(elsuizo: 2025-10-04): we don't know when the users created before this were created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created_at: Option<DateTime<Utc>>,
+    /// when the client closed his accounts, the closed clients are never deleted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    closed_at: Option<DateTime<Utc>>,
+    /// one balance for every currency in which the user has an account
+    balances: BTreeMap<Currency, Decimal>,
 }
 
 impl Hash for User {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.document_number.hash(state);
+        self.document().hash(state);
     }
 }
 
 impl User {
+    /// a new user with an empty account in the currency of his country
     pub fn new(
         client_name: UserName,
-        bird_date: NaiveDate,
-        document_number: DocumentNumber,
+        bird_date: BirthDate,
+        document: Document,
+        country: &Country,
+    ) -> Self {
+        let mut user = Self::from_stored(client_name, bird_date, document, country.name());
+        user.created_at = Some(Utc::now());
+        user.open_account(country.currency());
+        user
+    }
+
+    /// a user that was already created, without accounts
+    pub(crate) fn from_stored(
+        client_name: UserName,
+        bird_date: BirthDate,
+        document: Document,
         country: CountryName,
     ) -> Self {
         Self {
             client_name,
             bird_date,
-            document_number,
+            document_type: document.document_type,
+            document_number: document.number,
+            issuing_country: Some(document.issuing_country),
             country,
-            credit: 0.into(),
+            kyc_status: KycStatus::Pending,
+            created_at: None,
+            closed_at: None,
+            balances: BTreeMap::new(),
         }
     }
 
-    pub fn get_document_number(&self) -> usize {
-        self.document_number.0
+    pub fn get_document_number(&self) -> &str {
+        self.document_number.inner_ref()
+    }
+
+    pub fn get_document_type(&self) -> DocumentType {
+        self.document_type
+    }
+
+    pub fn get_issuing_country(&self) -> &CountryName {
+        self.issuing_country.as_ref().unwrap_or(&self.country)
     }
 
-    pub fn increase_credit(&mut self, amount: Decimal) {
-        self.credit += amount
+    /// the document of the user, it is the key of the document index
+    pub fn document(&self) -> Document {
+        Document::from_stored(
+            self.document_type,
+            self.get_issuing_country().clone(),
+            self.document_number.clone(),
+        )
     }
 
-    pub fn get_actual_credit(&self) -> Decimal {
-        self.credit
+    pub fn kyc_status(&self) -> KycStatus {
+        self.kyc_status
     }
 
-    pub fn decrease_credit(&mut self, amount: Decimal) -> Result<(), DatabaseError> {
-        if self.credit >= amount {
-            self.credit -= amount;
+    pub fn set_kyc_status(&mut self, status: KycStatus) {
+        self.kyc_status = status;
+    }
+
+    /// `None` for the users created before we saved the creation time
+    pub fn created_at(&self) -> Option<DateTime<Utc>> {
+        self.created_at
+    }
+
+    pub(crate) fn set_created_at(&mut self, created_at: Option<DateTime<Utc>>) {
+        self.created_at = created_at;
+    }
+
+    pub fn closed_at(&self) -> Option<DateTime<Utc>> {
+        self.closed_at
+    }
+
+    /// `ClientClosed` if the user `id` was closed, nothing can change in a closed user
+    pub fn check_open(&self, id: Uuid) -> Result<(), DatabaseError> {
+        match self.closed_at {
+            Some(_) => Err(DatabaseError::ClientClosed(id)),
+            None => Ok(()),
+        }
+    }
+
+    /// close the user `id` at the time `at`, only the users without money can be closed
+    pub fn close(&mut self, id: Uuid, at: DateTime<Utc>) -> Result<(), DatabaseError> {
+        self.check_open(id)?;
+        if let Some((currency, balance)) = self.balances.iter().find(|(_, b)| !b.is_zero()) {
+            return Err(DatabaseError::BalanceNotZero(id, *currency, *balance));
+        }
+        self.closed_at = Some(at);
+        Ok(())
+    }
+
+    /// a user that was closed when it was saved
+    pub(crate) fn set_closed_at(&mut self, closed_at: Option<DateTime<Utc>>) {
+        self.closed_at = closed_at;
+    }
+
+    /// apply the `change` to the profile, the user gets an account in the currency of his new
+    /// country. Return `false` if the account already existed
+    pub fn change_profile(&mut self, change: &ProfileChange) -> bool {
+        if let Some(client_name) = &change.client_name {
+            self.client_name = client_name.clone();
+        }
+        let Some((country, currency)) = &change.country else {
+            return false;
+        };
+        // NOTE(elsuizo: 2025-09-27): the old users only have the `country`, his document was
+        // issued there and that must not change with the country
+        self.issuing_country
+            .get_or_insert_with(|| self.country.clone());
+        self.country = country.clone();
+        self.open_account(*currency)
+    }
+
+    /// `ClientClosed` or `ClientNotVerified` if the user `id` can not move money
+    pub fn check_may_transact(&self, id: Uuid) -> Result<(), DatabaseError> {
+        self.check_open(id)?;
+        if self.kyc_status.may_transact() {
             Ok(())
         } else {
-            Err(DatabaseError::InsufficientBalance(self.get_actual_credit()))
+            Err(DatabaseError::ClientNotVerified(id, self.kyc_status))
         }
     }
 
-    pub fn reset_credit(&mut self) {
-        self.credit = 0.into();
+    pub fn has_account(&self, currency: Currency) -> bool {
+        self.balances.contains_key(&currency)
+    }
+
+    /// return `false` if the account already exists
+    pub fn open_account(&mut self, currency: Currency) -> bool {
+        if self.has_account(currency) {
+            false
+        } else {
+            self.balances.insert(currency, Decimal::ZERO);
+            true
+        }
+    }
+
+    /// the caller must check first that the account exists with `has_account`
+    pub fn increase_credit(&mut self, currency: Currency, amount: Decimal) {
+        *self.balances.entry(currency).or_default() += amount
+    }
+
+    /// `None` if there is no account in `currency`
+    pub fn get_actual_credit(&self, currency: Currency) -> Option<Decimal> {
+        self.balances.get(&currency).copied()
+    }
+
+    pub fn balances(&self) -> &BTreeMap<Currency, Decimal> {
+        &self.balances
+    }
+
+    pub fn decrease_credit(
+        &mut self,
+        currency: Currency,
+        amount: Decimal,
+    ) -> Result<(), DatabaseError> {
+        let credit = self.get_actual_credit(currency).unwrap_or_default();
+        if credit >= amount {
+            self.balances.insert(currency, credit - amount);
+            Ok(())
+        } else {
+            Err(DatabaseError::InsufficientBalance(credit))
+        }
     }
 
     pub fn get_bird_date(&self) -> NaiveDate {
-        self.bird_date
+        self.bird_date.inner()
     }
 
     pub fn get_country_name(&self) -> &str {
@@ -99,25 +407,32 @@ impl User {
 
 impl PartialEq for User {
     fn eq(&self, other: &Self) -> bool {
-        self.document_number == other.document_number
+        self.document() == other.document()
     }
 }
 
-#[derive(Debug, Clone, Hash, PartialEq, PartialOrd, Eq, serde::Deserialize)]
+/// The changes to the profile of a user that were already validated, the fields that are `None`
+/// don't change
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct ProfileChange {
+    client_name: Option<UserName>,
+    /// the new country and its currency
+    country: Option<(CountryName, Currency)>,
+}
+
+impl ProfileChange {
+    pub fn new(client_name: Option<UserName>, country: Option<&Country>) -> Self {
+        Self {
+            client_name,
+            country: country.map(|country| (country.name(), country.currency())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, PartialOrd, Eq, serde::Deserialize, serde::Serialize)]
 pub struct CountryName(String);
 
 impl CountryName {
-    // NOTE(elsuizo: 2025-07-12): this simulate the database of valid countrys
-    const VALID_COUNTRY: [&str; 7] = [
-        "Argentina",
-        "Brazil",
-        "Chile",
-        "Ecuador",
-        "Paraguay",
-        "Uruguay",
-        "Peru",
-    ];
-
     pub fn inner(self) -> String {
         self.0
     }
@@ -126,18 +441,9 @@ impl CountryName {
         &self.0
     }
 
-    pub fn parse_and_validate(s: &str) -> Result<Self, CreateUserError> {
-        let is_empty_or_whitespace = s.trim().is_empty();
-
-        let is_valid_country = Self::VALID_COUNTRY
-            .iter()
-            .any(|&country_name| country_name == s);
-
-        if is_empty_or_whitespace || is_valid_country {
-            Ok(Self(s.to_string()))
-        } else {
-            Err(CreateUserError::InvalidCountryName(s.to_string()))
-        }
+    /// the name of a country that we already know, the clients send it to the `CountryRegistry`
+    pub(crate) fn from_stored(name: String) -> Self {
+        Self(name)
     }
 }
 
@@ -147,24 +453,42 @@ impl AsRef<str> for CountryName {
     }
 }
 
-#[derive(Debug, Copy, Clone, Hash, PartialEq, PartialOrd, Eq, serde::Deserialize)]
-pub struct DocumentNumber(usize);
+/// the document normalized, without separators and in upper case (see `document::normalize`)
+#[derive(Debug, Clone, Hash, PartialEq, PartialOrd, Eq, serde::Deserialize, serde::Serialize)]
+pub struct DocumentNumber(
+    // NOTE(elsuizo: 2025-08-23): the old snapshots have the documents as numbers
+    #[serde(deserialize_with = "deserialize_string_from_number")] String,
+);
 
 impl DocumentNumber {
-    const UPPER_LIMIT: usize = 100000000;
-
-    pub fn inner(self) -> usize {
+    pub fn inner(self) -> String {
         self.0
     }
 
-    // TODO(elsuizo: 2025-07-12): this should be for every country...
-    pub fn parse_and_validate(raw_number: usize) -> Result<DocumentNumber, CreateUserError> {
-        if raw_number > Self::UPPER_LIMIT {
-            Err(CreateUserError::InvalidDocumentNumber(raw_number))
+    pub fn inner_ref(&self) -> &str {
+        &self.0
+    }
+
+    /// validate the document with the `rule` of its type and the country that issued it
+    pub fn parse_and_validate(
+        raw_number: &str,
+        rule: DocumentRule,
+    ) -> Result<DocumentNumber, CreateUserError> {
+        let number = document::normalize(raw_number);
+        if rule.is_valid(&number) {
+            Ok(Self(number))
         } else {
-            Ok(Self(raw_number))
+            Err(CreateUserError::InvalidDocumentNumber(
+                raw_number.to_string(),
+            ))
         }
     }
+
+    /// a document that was validated when it was saved, the rules can change so we don't check it
+    /// again when it is read
+    pub(crate) fn from_stored(number: String) -> Self {
+        Self(number)
+    }
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, PartialOrd, Eq, serde::Deserialize, serde::Serialize)]
@@ -209,14 +533,161 @@ impl AsRef<str> for UserName {
     }
 }
 
+/// the birth date of a client, it is saved in ISO 8601 like any other date
+#[derive(
+    Debug, Copy, Clone, Hash, PartialEq, PartialOrd, Eq, serde::Deserialize, serde::Serialize,
+)]
+pub struct BirthDate(NaiveDate);
+
+impl BirthDate {
+    // NOTE(elsuizo: 2025-09-13): in all our countries the dates are written day first, the ISO
+    // format is the one that we always used in the API
+    const FORMATS: [&str; 3] = ["%Y-%m-%d", "%d/%m/%Y", "%d-%m-%Y"];
+
+    pub fn inner(self) -> NaiveDate {
+        self.0
+    }
+
+    /// a date in one of the `FORMATS`, the age of the client is checked by the `OnboardingRules`
+    pub fn parse_and_validate(s: &str) -> Result<Self, CreateUserError> {
+        let s = s.trim();
+        Self::FORMATS
+            .iter()
+            .find_map(|format| NaiveDate::parse_from_str(s, format).ok())
+            .map(Self)
+            .ok_or_else(|| CreateUserError::InvalidBirthDate(s.to_string()))
+    }
+}
+
+impl From<NaiveDate> for BirthDate {
+    fn from(date: NaiveDate) -> Self {
+        Self(date)
+    }
+}
+
 //-------------------------------------------------------------------------
 //                        unit tests
 //-------------------------------------------------------------------------
 #[cfg(test)]
 mod tests {
-    use crate::user::UserName;
+    use crate::country::test_country;
+    use crate::currency::Currency;
+    use crate::document::DocumentType;
+    use crate::onboarding::KycStatus;
+    use crate::user::{
+        BirthDate, CreateUserError, DatabaseError, Document, DocumentNumber, FieldErrors,
+        ProfileChange, User, UserName,
+    };
+    use chrono::{DateTime, NaiveDate, Utc};
     use claims::{assert_err, assert_ok};
 
+    #[test]
+    fn documents_are_validated_with_the_rules_of_their_country() {
+        let argentina = test_country("Argentina");
+        let chile = test_country("Chile");
+        let document =
+            Document::parse_and_validate(DocumentType::Dni, &argentina, "29.653.164").unwrap();
+        assert_eq!(document.number().inner_ref(), "29653164");
+        assert_eq!(document.issuing_country().inner_ref(), "Argentina");
+        assert_err!(Document::parse_and_validate(
+            DocumentType::Dni,
+            &chile,
+            "29.653.164"
+        ));
+        let document =
+            Document::parse_and_validate(DocumentType::Dni, &chile, "10.000.013-k").unwrap();
+        assert_eq!(document.number().inner_ref(), "10000013K");
+        // a CUIT is not a passport
+        assert_err!(Document::parse_and_validate(
+            DocumentType::Passport,
+            &argentina,
+            "20123456786"
+        ));
+    }
+
+    #[test]
+    fn new_users_have_an_account_in_the_currency_of_their_country() {
+        let ecuador = test_country("EC");
+        let document =
+            Document::parse_and_validate(DocumentType::Passport, &ecuador, "AAB123456").unwrap();
+        let user = User::new(
+            UserName::parse_and_validate("Martin Noblia").unwrap(),
+            NaiveDate::from_ymd_opt(1982, 9, 27).unwrap().into(),
+            document,
+            &ecuador,
+        );
+        assert_eq!(user.get_country_name(), "Ecuador");
+        assert_eq!(user.balances().keys().collect::<Vec<_>>(), [&Currency::Usd]);
+    }
+
+    fn test_user() -> User {
+        let argentina = test_country("Argentina");
+        let document =
+            Document::parse_and_validate(DocumentType::Dni, &argentina, "29653164").unwrap();
+        User::new(
+            UserName::parse_and_validate("Martin Noblia").unwrap(),
+            NaiveDate::from_ymd_opt(1982, 9, 27).unwrap().into(),
+            document,
+            &argentina,
+        )
+    }
+
+    #[test]
+    fn only_the_users_without_money_can_be_closed() {
+        let id = uuid::Uuid::new_v4();
+        let at: DateTime<Utc> = Utc::now();
+        let mut user = test_user();
+        user.set_kyc_status(KycStatus::Verified);
+        user.increase_credit(Currency::Ars, rust_decimal::dec!(1));
+        assert!(matches!(
+            user.close(id, at),
+            Err(DatabaseError::BalanceNotZero(_, Currency::Ars, _))
+        ));
+        assert_ok!(user.decrease_credit(Currency::Ars, rust_decimal::dec!(1)));
+        assert_ok!(user.close(id, at));
+        assert_eq!(user.closed_at(), Some(at));
+        assert!(matches!(
+            user.check_may_transact(id),
+            Err(DatabaseError::ClientClosed(_))
+        ));
+        assert_err!(user.close(id, at));
+    }
+
+    #[test]
+    fn a_new_country_does_not_change_the_document() {
+        let mut user = test_user();
+        let change = ProfileChange::new(
+            Some(UserName::parse_and_validate("Martin N").unwrap()),
+            Some(&test_country("Chile")),
+        );
+        let document = user.document();
+        assert!(user.change_profile(&change));
+        assert_eq!(user.client_name.inner_ref(), "Martin N");
+        assert_eq!(user.get_country_name(), "Chile");
+        assert_eq!(user.document(), document);
+        assert!(user.has_account(Currency::Clp) && user.has_account(Currency::Ars));
+        // the second time the account already exists
+        assert!(!user.change_profile(&change));
+    }
+
+    #[test]
+    fn old_numeric_documents_can_be_read() {
+        let document: DocumentNumber = serde_json::from_str("29653164").unwrap();
+        assert_eq!(document.inner_ref(), "29653164");
+    }
+
+    #[test]
+    fn the_users_saved_without_document_type_have_the_national_document_of_their_country() {
+        let user: User = serde_json::from_str(
+            r#"{"client_name":"Martin Noblia","bird_date":"1982-09-27",
+                "document_number":29653164,"country":"Argentina","balances":{"ARS":"0"}}"#,
+        )
+        .unwrap();
+        assert_eq!(user.get_document_type(), DocumentType::Dni);
+        assert_eq!(user.get_issuing_country().inner_ref(), "Argentina");
+        assert_eq!(user.kyc_status(), KycStatus::Verified);
+    }
+
     #[test]
     fn a_256_grapheme_long_name_is_valid() {
         let name = "a̐".repeat(256);
@@ -248,4 +719,60 @@ mod tests {
         let name = "Martin Noblia".to_string();
         assert_ok!(UserName::parse_and_validate(&name));
     }
+
+    #[test]
+    fn birth_dates_can_be_written_in_iso_or_day_first() {
+        let date = NaiveDate::from_ymd_opt(1982, 9, 27).unwrap();
+        for s in [
+            "1982-09-27",
+            "1982-9-27",
+            "27/09/1982",
+            "27/9/1982",
+            "27-09-1982",
+            " 1982-09-27 ",
+        ] {
+            assert_eq!(
+                BirthDate::parse_and_validate(s).unwrap().inner(),
+                date,
+                "{s}"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_birth_dates_are_rejected() {
+        for s in [
+            "",
+            "27 of September",
+            "09/27/1982",
+            "1982-02-30",
+            "1982/09/27",
+            "29/02/1983",
+        ] {
+            assert_err!(BirthDate::parse_and_validate(s), "{s}");
+        }
+    }
+
+    #[test]
+    fn all_the_invalid_fields_are_reported() {
+        let mut fields = FieldErrors::default();
+        assert_eq!(
+            fields.check("client_name", UserName::parse_and_validate("<script>")),
+            None
+        );
+        assert!(
+            fields
+                .check("birth_date", BirthDate::parse_and_validate("1982-09-27"))
+                .is_some()
+        );
+        assert_eq!(
+            fields.check("birth_date", BirthDate::parse_and_validate("tomorrow")),
+            None
+        );
+        let CreateUserError::InvalidFields(errors) = fields.into_error() else {
+            panic!("the errors of the fields were lost");
+        };
+        let names = errors.iter().map(|(field, _)| *field).collect::<Vec<_>>();
+        assert_eq!(names, ["client_name", "birth_date"]);
+    }
 }
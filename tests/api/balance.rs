@@ -0,0 +1,173 @@
+use crate::helpers::{TestUser, spawn_app, spawn_sqlite_app};
+
+#[tokio::test]
+async fn the_balance_has_the_details_of_every_account() {
+    for app in [
+        spawn_app(TestUser::generate()).await,
+        spawn_sqlite_app(TestUser::generate()).await,
+    ] {
+        let client_id = app.create_client().await;
+        assert_eq!(
+            200,
+            app.post_new_account(client_id, "USD")
+                .await
+                .status()
+                .as_u16()
+        );
+        assert_eq!(
+            200,
+            app.post_credit(client_id, "10.5").await.status().as_u16()
+        );
+
+        let response = app.get_balance(client_id).await;
+        assert_eq!(200, response.status().as_u16());
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(client_id.to_string(), body["client_id"]);
+        assert_eq!("10.5", body["balances"]["ARS"]);
+        assert_eq!("0", body["balances"]["USD"]);
+
+        let accounts = body["accounts"].as_array().unwrap();
+        assert_eq!(2, accounts.len());
+        assert_eq!("ARS", accounts[0]["currency"]);
+        assert_eq!("10.5", accounts[0]["balance"]);
+        assert_eq!("10.5", accounts[0]["available"]);
+        assert_eq!("0", accounts[0]["held"]);
+        assert!(accounts[0]["updated_at"].is_string());
+        assert_eq!("USD", accounts[1]["currency"]);
+        // the account never had a movement
+        assert!(accounts[1]["updated_at"].is_null());
+    }
+}
+
+#[tokio::test]
+async fn the_balance_can_be_asked_for_one_currency() {
+    let app = spawn_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+    assert_eq!(200, app.post_credit(client_id, "3").await.status().as_u16());
+    let url = format!("{}/clients/{}/balance", app.address, client_id);
+
+    let response = app
+        .api_client
+        .get(&url)
+        .query(&[("currency", "ARS")])
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(200, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(serde_json::json!({"ARS": "3"}), body["balances"]);
+    assert_eq!(1, body["accounts"].as_array().unwrap().len());
+
+    let response = app
+        .api_client
+        .get(&url)
+        .query(&[("currency", "BRL")])
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(400, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!("unknown_account", body["error"]["code"]);
+
+    let response = app
+        .api_client
+        .get(&url)
+        .query(&[("currency", "XYZ")])
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(400, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!("invalid_query", body["error"]["code"]);
+}
+
+#[tokio::test]
+async fn the_old_balance_route_reads_the_id_from_the_query_or_the_form() {
+    let app = spawn_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+    assert_eq!(200, app.post_credit(client_id, "7").await.status().as_u16());
+    let url = format!("{}/client_balance", app.address);
+
+    let by_query = app
+        .api_client
+        .get(&url)
+        .query(&[("client_id", client_id.to_string())])
+        .send()
+        .await
+        .expect("Failed to execute request");
+    let by_form = app
+        .api_client
+        .get(&url)
+        .form(&[("client_id", client_id.to_string())])
+        .send()
+        .await
+        .expect("Failed to execute request");
+    for response in [by_query, by_form] {
+        assert_eq!(200, response.status().as_u16());
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!("7", body["balances"]["ARS"]);
+    }
+
+    let response = app
+        .api_client
+        .get(&url)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn negative_credits_and_debits_are_rejected() {
+    for app in [
+        spawn_app(TestUser::generate()).await,
+        spawn_sqlite_app(TestUser::generate()).await,
+    ] {
+        let client_id = app.create_client().await;
+        assert_eq!(
+            200,
+            app.post_credit(client_id, "30").await.status().as_u16()
+        );
+
+        for response in [
+            app.post_credit(client_id, "-50").await,
+            app.post_debit(client_id, "-80").await,
+            app.post_credit(client_id, "0").await,
+        ] {
+            assert_eq!(400, response.status().as_u16());
+            let body: serde_json::Value = response.json().await.unwrap();
+            assert_eq!("invalid_amount", body["error"]["code"]);
+        }
+
+        let body: serde_json::Value = app.get_balance(client_id).await.json().await.unwrap();
+        assert_eq!("30", body["balances"]["ARS"]);
+        let body: serde_json::Value = app
+            .get_transactions(client_id, &[])
+            .await
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(1, body["transactions"].as_array().unwrap().len());
+    }
+}
+
+#[tokio::test]
+async fn amounts_with_more_decimals_than_the_currency_are_rejected() {
+    let app = spawn_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+    assert_eq!(
+        200,
+        app.post_credit(client_id, "30").await.status().as_u16()
+    );
+
+    for response in [
+        app.post_credit(client_id, "0.0001").await,
+        app.post_debit(client_id, "0.001").await,
+    ] {
+        assert_eq!(400, response.status().as_u16());
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!("invalid_amount", body["error"]["code"]);
+    }
+    let body: serde_json::Value = app.get_balance(client_id).await.json().await.unwrap();
+    assert_eq!("30", body["balances"]["ARS"]);
+}
@@ -0,0 +1,219 @@
+use crate::helpers::{TestUser, spawn_app, spawn_sqlite_app};
+
+#[tokio::test]
+async fn a_client_can_be_found_by_his_document() {
+    for app in [
+        spawn_app(TestUser::generate()).await,
+        spawn_sqlite_app(TestUser::generate()).await,
+    ] {
+        let client_id = app.create_client().await;
+
+        let response = app.get_client_by_document("Argentina", "29.653.164").await;
+        assert_eq!(200, response.status().as_u16());
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(client_id.to_string(), body["client_id"]);
+        assert_eq!("Martin Noblia", body["client_name"]);
+        assert_eq!("1982-09-27", body["birth_date"]);
+        assert_eq!("29653164", body["document_number"]);
+    }
+}
+
+#[tokio::test]
+async fn the_same_document_number_of_other_country_is_other_client() {
+    let mut argentine = TestUser::generate();
+    // a valid DNI and a valid RUT at the same time
+    argentine.document_number = "30111222".into();
+    let app = spawn_app(argentine).await;
+    let client_id = app.create_client().await;
+    let other_id = app.create_client_from(&TestUser::other()).await;
+
+    let body: serde_json::Value = app
+        .get_client_by_document("Chile", "3.011.122-2")
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(other_id.to_string(), body["client_id"]);
+    assert_ne!(client_id, other_id);
+}
+
+#[tokio::test]
+async fn an_unknown_document_is_not_found() {
+    let app = spawn_app(TestUser::generate()).await;
+    app.create_client().await;
+
+    let response = app.get_client_by_document("Chile", "30111222").await;
+    assert_eq!(404, response.status().as_u16());
+    // not a valid RUT
+    let response = app.get_client_by_document("Chile", "29653164").await;
+    assert_eq!(400, response.status().as_u16());
+    let response = app.get_client_by_document("Narnia", "29653164").await;
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn documents_are_validated_with_the_rules_of_the_country() {
+    for app in [
+        spawn_app(TestUser::other()).await,
+        spawn_sqlite_app(TestUser::other()).await,
+    ] {
+        let mut user = TestUser::other();
+        user.document_number = "12.345.678-4".into();
+        assert_eq!(400, app.post_client(&user).await.status().as_u16());
+
+        // the RUT can end in a letter
+        user.document_number = "10.000.013-k".into();
+        let client_id = app.create_client_from(&user).await;
+        let body: serde_json::Value = app
+            .get_client_by_document("Chile", "10000013K")
+            .await
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(client_id.to_string(), body["client_id"]);
+    }
+}
+
+#[tokio::test]
+async fn documents_can_still_be_sent_as_numbers() {
+    let app = spawn_app(TestUser::generate()).await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/new_client", app.address))
+        .json(&serde_json::json!({
+            "client_name": "Martin Noblia",
+            "birth_date": "1982-09-27",
+            "document_number": 29653164,
+            "country": "Argentina",
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(200, response.status().as_u16());
+    assert!(app.post_new_client().await.status().is_client_error());
+}
+
+#[tokio::test]
+async fn a_client_can_be_registered_with_a_passport_of_other_country() {
+    for app in [
+        spawn_app(TestUser::generate()).await,
+        spawn_sqlite_app(TestUser::generate()).await,
+    ] {
+        let national_id = app.create_client().await;
+
+        let passport = serde_json::json!({
+            "client_name": "Martin Noblia",
+            "birth_date": "1982-09-27",
+            "document_type": "passport",
+            "issuing_country": "Argentina",
+            "document_number": "aab123456",
+            "country": "Chile",
+        });
+        let post_passport = || {
+            app.api_client
+                .post(format!("{}/new_client", app.address))
+                .json(&passport)
+                .send()
+        };
+        let response = post_passport().await.expect("Failed to execute request");
+        assert_eq!(200, response.status().as_u16());
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_ne!(national_id.to_string(), body["client_id"]);
+        let response = post_passport().await.expect("Failed to execute request");
+        assert_eq!(409, response.status().as_u16());
+
+        let body: serde_json::Value = app
+            .api_client
+            .get(format!("{}/clients/by_document", app.address))
+            .query(&[
+                ("country", "Argentina"),
+                ("document_type", "passport"),
+                ("document_number", "AAB123456"),
+            ])
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .json()
+            .await
+            .unwrap();
+        assert_eq!("passport", body["document_type"]);
+        assert_eq!("Argentina", body["issuing_country"]);
+        assert_eq!("Chile", body["country"]);
+    }
+}
+
+#[tokio::test]
+async fn unknown_document_types_are_rejected() {
+    let app = spawn_app(TestUser::generate()).await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/new_client", app.address))
+        .json(&serde_json::json!({
+            "client_name": "Martin Noblia",
+            "birth_date": "1982-09-27",
+            "document_type": "library_card",
+            "document_number": "29653164",
+            "country": "Argentina",
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn the_country_can_be_sent_with_its_iso_code() {
+    let app = spawn_app(TestUser::other()).await;
+    let mut user = TestUser::other();
+    user.country = "cl".into();
+    let client_id = app.create_client_from(&user).await;
+
+    let body: serde_json::Value = app
+        .get_client_by_document("CHL", "3.011.122-2")
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(client_id.to_string(), body["client_id"]);
+    // we always store the name of the country
+    assert_eq!("Chile", body["country"]);
+
+    let response = app.get_balance(client_id).await;
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!("0", body["balances"]["CLP"]);
+}
+
+#[tokio::test]
+async fn blank_and_unknown_countries_are_rejected() {
+    let app = spawn_app(TestUser::generate()).await;
+    for country in ["", "   ", "Narnia", "XX"] {
+        let mut user = TestUser::generate();
+        user.country = country.into();
+        assert_eq!(400, app.post_client(&user).await.status().as_u16());
+    }
+}
+
+#[tokio::test]
+async fn the_birth_date_can_be_sent_day_first() {
+    for app in [
+        spawn_app(TestUser::generate()).await,
+        spawn_sqlite_app(TestUser::generate()).await,
+    ] {
+        let mut user = TestUser::generate();
+        user.bird_date = "27/09/1982".into();
+        app.create_client_from(&user).await;
+
+        let body: serde_json::Value = app
+            .get_client_by_document("Argentina", "29653164")
+            .await
+            .json()
+            .await
+            .unwrap();
+        // we always store the ISO date
+        assert_eq!("1982-09-27", body["birth_date"]);
+    }
+}
@@ -0,0 +1,33 @@
+use crate::helpers::{TestUser, spawn_app};
+
+#[tokio::test]
+async fn convert_debits_one_currency_and_credits_the_other() {
+    let app = spawn_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+    app.post_new_account(client_id, "USD").await;
+    app.post_credit(client_id, "3000").await;
+
+    // the rates of `configuration/exchange_rates.yaml`: 1 USD = 1250 ARS
+    let response = app.post_convert(client_id, "ARS", "USD", "2500").await;
+
+    assert_eq!(200, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!("0.0008", body["exchange_rate"]);
+    assert_eq!("2.00", body["credited_amount"]);
+    let body: serde_json::Value = app.get_balance(client_id).await.json().await.unwrap();
+    assert_eq!("500", body["balances"]["ARS"]);
+    assert_eq!("2.00", body["balances"]["USD"]);
+}
+
+#[tokio::test]
+async fn convert_to_a_currency_without_account_is_rejected() {
+    let app = spawn_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+    app.post_credit(client_id, "3000").await;
+
+    let response = app.post_convert(client_id, "ARS", "BRL", "100").await;
+
+    assert_eq!(400, response.status().as_u16());
+    let body: serde_json::Value = app.get_balance(client_id).await.json().await.unwrap();
+    assert_eq!("3000", body["balances"]["ARS"]);
+}
@@ -0,0 +1,157 @@
+use crate::helpers::{TestUser, spawn_app};
+
+/// the `error` object of the body, after checking that it has the request id of the header
+async fn error_of(response: reqwest::Response) -> serde_json::Value {
+    let request_id = response
+        .headers()
+        .get("x-request-id")
+        .expect("the response has no request id")
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(request_id, body["error"]["request_id"]);
+    assert!(body["error"]["message"].is_string());
+    assert!(body["error"]["details"].is_object());
+    body["error"].clone()
+}
+
+#[tokio::test]
+async fn a_body_that_can_not_be_read_returns_an_error_envelope() {
+    let app = spawn_app(TestUser::generate()).await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/new_client", app.address))
+        .header("Content-Type", "application/json")
+        .body("{\"client_name\": ")
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(400, response.status().as_u16());
+    let error = error_of(response).await;
+    assert_eq!("malformed_json", error["code"]);
+    assert_eq!(1, error["details"]["line"]);
+
+    let response = app
+        .api_client
+        .post(format!("{}/new_client", app.address))
+        .json(&serde_json::json!({"client_name": "Martin Noblia"}))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(400, response.status().as_u16());
+    assert_eq!("invalid_body", error_of(response).await["code"]);
+
+    let response = app
+        .api_client
+        .post(format!("{}/new_client", app.address))
+        .body("client_name=Martin")
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(415, response.status().as_u16());
+    assert_eq!("unsupported_content_type", error_of(response).await["code"]);
+}
+
+#[tokio::test]
+async fn a_bad_uuid_or_an_unknown_route_returns_an_error_envelope() {
+    let app = spawn_app(TestUser::generate()).await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/clients/not-a-uuid/kyc", app.address))
+        .json(&serde_json::json!({"status": "verified"}))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(400, response.status().as_u16());
+    let error = error_of(response).await;
+    assert_eq!("invalid_path", error["code"]);
+    assert_eq!("/clients/not-a-uuid/kyc", error["details"]["path"]);
+
+    let response = app
+        .api_client
+        .get(format!("{}/nothing/here", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(404, response.status().as_u16());
+    assert_eq!("not_found", error_of(response).await["code"]);
+}
+
+#[tokio::test]
+async fn every_invalid_field_of_a_new_client_is_reported_by_name() {
+    let app = spawn_app(TestUser::generate()).await;
+    let mut user = TestUser::generate();
+    user.bird_date = "27 of September".into();
+
+    let response = app.post_client(&user).await;
+    assert_eq!(400, response.status().as_u16());
+    let error = error_of(response).await;
+    assert_eq!("invalid_fields", error["code"]);
+    let fields = error["details"]["fields"].as_object().unwrap();
+    assert_eq!(vec!["birth_date"], fields.keys().collect::<Vec<_>>());
+    assert_eq!("invalid_birth_date", fields["birth_date"]["code"]);
+    assert_eq!(
+        "27 of September",
+        fields["birth_date"]["details"]["birth_date"]
+    );
+
+    user.client_name = "<Martin>".into();
+    user.country = "Atlantis".into();
+    let error = error_of(app.post_client(&user).await).await;
+    let fields = error["details"]["fields"].as_object().unwrap();
+    assert_eq!(
+        vec!["birth_date", "client_name", "country"],
+        fields.keys().collect::<Vec<_>>()
+    );
+    assert_eq!("invalid_name", fields["client_name"]["code"]);
+    assert_eq!("invalid_country", fields["country"]["code"]);
+}
+
+#[tokio::test]
+async fn the_errors_of_the_operations_have_their_details() {
+    let app = spawn_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+    assert_eq!(
+        200,
+        app.post_credit(client_id, "10").await.status().as_u16()
+    );
+
+    let response = app.post_debit(client_id, "11").await;
+    assert_eq!(400, response.status().as_u16());
+    let error = error_of(response).await;
+    assert_eq!("insufficient_balance", error["code"]);
+    assert_eq!("10", error["details"]["balance"]);
+
+    let response = app.post_new_client().await;
+    assert_eq!(409, response.status().as_u16());
+    let error = error_of(response).await;
+    assert_eq!("client_already_exists", error["code"]);
+    assert_eq!("29653164", error["details"]["document_number"]);
+}
+
+#[tokio::test]
+async fn the_request_id_of_the_client_is_returned() {
+    let app = spawn_app(TestUser::generate()).await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/settlements", app.address))
+        .header("X-Request-Id", "my-request-42")
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(200, response.status().as_u16());
+    assert_eq!("my-request-42", response.headers()["x-request-id"]);
+
+    let response = app
+        .api_client
+        .get(format!("{}/nothing/here", app.address))
+        .header("X-Request-Id", "my-request-43")
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!("my-request-43", error_of(response).await["request_id"]);
+}
@@ -1,8 +1,7 @@
-use actix_web::App;
 use uuid::Uuid;
-use wiremock::MockServer;
 
-use mini_payment::configuration::get_configuration;
+use mini_payment::configuration::{ServiceSettings, StorageBackend, get_configuration};
+use mini_payment::onboarding::KycStatus;
 use mini_payment::service::Application;
 
 pub struct TestUser {
@@ -12,52 +11,391 @@ pub struct TestUser {
     pub country: String,
 }
 
-impl TestUser {}
+impl TestUser {
+    pub fn generate() -> Self {
+        Self {
+            client_name: "Martin Noblia".into(),
+            bird_date: "1982-09-27".into(),
+            document_number: "29653164".into(),
+            country: "Argentina".into(),
+        }
+    }
+
+    pub fn other() -> Self {
+        Self {
+            client_name: "Juan Perez".into(),
+            bird_date: "1990-01-02".into(),
+            document_number: "30111222".into(),
+            country: "Chile".into(),
+        }
+    }
+}
 
 pub struct TestApp {
     pub address: String,
-    pub port: u16,
     pub test_user: TestUser,
     pub api_client: reqwest::Client,
+    pub configuration: ServiceSettings,
+}
+
+impl TestApp {
+    pub async fn post_new_client(&self) -> reqwest::Response {
+        self.post_client(&self.test_user).await
+    }
+
+    pub async fn post_client(&self, user: &TestUser) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/new_client", self.address))
+            .json(&serde_json::json!({
+                "client_name": user.client_name,
+                "birth_date": user.bird_date,
+                "document_number": user.document_number,
+                "country": user.country,
+            }))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// create the test user and return his id
+    pub async fn create_client(&self) -> Uuid {
+        self.create_client_from(&self.test_user).await
+    }
+
+    pub async fn create_client_from(&self, user: &TestUser) -> Uuid {
+        let response = self.post_client(user).await;
+        assert_eq!(200, response.status().as_u16());
+        let body: serde_json::Value = response.json().await.unwrap();
+        body["client_id"].as_str().unwrap().parse().unwrap()
+    }
+
+    pub async fn post_new_account(&self, client_id: Uuid, currency: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/new_account", self.address))
+            .json(&serde_json::json!({"client_id": client_id, "currency": currency}))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// credit `amount` in ARS, the currency of the test user
+    pub async fn post_credit(&self, client_id: Uuid, amount: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/new_credit_transaction", self.address))
+            .json(&serde_json::json!({
+                "client_id": client_id,
+                "currency": "ARS",
+                "credit_amount": amount,
+            }))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_credit_with_key(
+        &self,
+        client_id: Uuid,
+        amount: &str,
+        idempotency_key: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/new_credit_transaction", self.address))
+            .header("Idempotency-Key", idempotency_key)
+            .json(&serde_json::json!({
+                "client_id": client_id,
+                "currency": "ARS",
+                "credit_amount": amount,
+            }))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// post the json `body` (if any) to `path` with the `Idempotency-Key` header
+    pub async fn post_with_key(
+        &self,
+        path: &str,
+        body: Option<serde_json::Value>,
+        idempotency_key: &str,
+    ) -> reqwest::Response {
+        let mut request = self
+            .api_client
+            .post(format!("{}{}", self.address, path))
+            .header("Idempotency-Key", idempotency_key);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        request.send().await.expect("Failed to execute request")
+    }
+
+    /// debit `amount` in ARS, the currency of the test user
+    pub async fn post_debit(&self, client_id: Uuid, amount: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/new_debit_transaction", self.address))
+            .json(&serde_json::json!({
+                "client_id": client_id,
+                "currency": "ARS",
+                "credit_amount": amount,
+            }))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_transfer(&self, from: Uuid, to: Uuid, amount: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/transfer", self.address))
+            .json(&serde_json::json!({
+                "from_client_id": from,
+                "to_client_id": to,
+                "currency": "ARS",
+                "amount": amount,
+            }))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_convert(
+        &self,
+        client_id: Uuid,
+        from: &str,
+        to: &str,
+        amount: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/convert", self.address))
+            .json(&serde_json::json!({
+                "client_id": client_id,
+                "from_currency": from,
+                "to_currency": to,
+                "amount": amount,
+            }))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_store_balances(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/store_balances", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_settlements(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/settlements", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_settlement(&self, id: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/settlements/{}", self.address, id))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_balance(&self, client_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/clients/{}/balance", self.address, client_id))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_kyc_status(&self, client_id: Uuid, status: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/clients/{}/kyc", self.address, client_id))
+            .json(&serde_json::json!({"status": status}))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_clients(&self, query: &[(&str, &str)]) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/clients", self.address))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_transactions(
+        &self,
+        client_id: Uuid,
+        query: &[(&str, &str)],
+    ) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/clients/{}/transactions",
+                self.address, client_id
+            ))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_client(&self, client_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/clients/{}", self.address, client_id))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn patch_client(
+        &self,
+        client_id: Uuid,
+        body: serde_json::Value,
+    ) -> reqwest::Response {
+        self.api_client
+            .patch(format!("{}/clients/{}", self.address, client_id))
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_close_client(&self, client_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/clients/{}/close", self.address, client_id))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// hold `amount` in ARS, the currency of the test user
+    pub async fn post_hold(&self, client_id: Uuid, amount: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/clients/{}/holds", self.address, client_id))
+            .json(&serde_json::json!({"currency": "ARS", "amount": amount}))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// place a hold of `amount` and return its id
+    pub async fn place_hold(&self, client_id: Uuid, amount: &str) -> Uuid {
+        let response = self.post_hold(client_id, amount).await;
+        assert_eq!(200, response.status().as_u16());
+        let body: serde_json::Value = response.json().await.unwrap();
+        body["id"].as_str().unwrap().parse().unwrap()
+    }
+
+    pub async fn post_capture(&self, hold_id: Uuid, body: serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/holds/{}/capture", self.address, hold_id))
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_void(&self, hold_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/holds/{}/void", self.address, hold_id))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_hold(&self, hold_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/holds/{}", self.address, hold_id))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_holds(&self, client_id: Uuid, query: &[(&str, &str)]) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/clients/{}/holds", self.address, client_id))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_client_by_document(
+        &self,
+        country: &str,
+        document_number: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/clients/by_document", self.address))
+            .query(&[
+                ("country", country.to_string()),
+                ("document_number", document_number.to_string()),
+            ])
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
 }
 
-impl TestApp {}
+/// the configuration of a new instance of the app, with its own database
+pub fn test_configuration() -> ServiceSettings {
+    let mut c = get_configuration().expect("Failed to read configuration");
+    // usamos una base de datos diferente para cada caso de test
+    c.local_database.path = std::env::temp_dir()
+        .join(format!("mini-payment-{}", Uuid::new_v4()))
+        .to_string_lossy()
+        .into_owned();
+    c.settlement.output_directory = std::path::Path::new(&c.local_database.path)
+        .join("settlements")
+        .to_string_lossy()
+        .into_owned();
+    // the settlements in the tests are always started by hand
+    c.settlement.schedules.clear();
+    // most of the tests move money as soon as the client is created
+    c.onboarding.kyc_status_of_new_clients = KycStatus::Verified;
+    // usamos un puerto del OS random
+    c.application.port = 0;
+    c
+}
 
 /// con esta funcion lo que hacemos es crear una instancia de la app
 pub async fn spawn_app(test_user: TestUser) -> TestApp {
-    let email_server = MockServer::start().await;
-
-    let configuration = {
-        let mut c = get_configuration().expect("Failed to read configuration");
-        // usamos una base de datos diferente para cada caso de test
-        // usamos un puerto del OS random
-        c.application.port = 0;
-        // usamos al server de test como API de email
-        c
-    };
+    spawn_app_with_configuration(test_user, test_configuration()).await
+}
 
-    // Create and migrate the database
+/// like `spawn_app` but the data is stored in a SQLite database
+pub async fn spawn_sqlite_app(test_user: TestUser) -> TestApp {
+    let mut configuration = test_configuration();
+    configuration.local_database.backend = StorageBackend::Sqlite;
+    spawn_app_with_configuration(test_user, configuration).await
+}
 
+/// spawn a new instance of the app that use the given configuration, useful to simulate a restart
+/// of the service over the same database
+pub async fn spawn_app_with_configuration(
+    test_user: TestUser,
+    configuration: ServiceSettings,
+) -> TestApp {
     let application = Application::build(configuration.clone())
         .await
         .expect("Failed to build application");
     // obtenemos el port antes de spamear la aplicacion
     let address = format!("http://localhost:{}", application.get_port_number());
-    let application_port = application.get_port_number();
 
-    let _ = tokio::spawn(application.run_until_stopped());
+    tokio::spawn(application.run_until_stopped());
 
     let client = reqwest::Client::builder()
         .redirect(reqwest::redirect::Policy::none())
         .build()
         .unwrap();
 
-    let test_app = TestApp {
+    TestApp {
         address,
-        port: application_port,
         test_user,
         api_client: client,
-    };
-
-    test_app
+        configuration,
+    }
 }
@@ -0,0 +1,202 @@
+use crate::helpers::{
+    TestUser, spawn_app, spawn_app_with_configuration, spawn_sqlite_app, test_configuration,
+};
+
+async fn ars_account(app: &crate::helpers::TestApp, client_id: uuid::Uuid) -> serde_json::Value {
+    let body: serde_json::Value = app.get_balance(client_id).await.json().await.unwrap();
+    body["accounts"][0].clone()
+}
+
+#[tokio::test]
+async fn a_hold_reserves_money_until_it_is_captured() {
+    for app in [
+        spawn_app(TestUser::generate()).await,
+        spawn_sqlite_app(TestUser::generate()).await,
+    ] {
+        let client_id = app.create_client().await;
+        assert_eq!(
+            200,
+            app.post_credit(client_id, "100").await.status().as_u16()
+        );
+
+        let hold_id = app.place_hold(client_id, "60").await;
+        let account = ars_account(&app, client_id).await;
+        assert_eq!("100", account["balance"]);
+        assert_eq!("40", account["available"]);
+        assert_eq!("60", account["held"]);
+
+        // the held money can not be used by other debit
+        let response = app.post_debit(client_id, "50").await;
+        assert_eq!(400, response.status().as_u16());
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!("insufficient_balance", body["error"]["code"]);
+        assert_eq!("40", body["error"]["details"]["balance"]);
+        assert_eq!(400, app.post_hold(client_id, "41").await.status().as_u16());
+
+        // a partial capture releases the rest
+        let response = app
+            .post_capture(hold_id, serde_json::json!({"amount": "45.50"}))
+            .await;
+        assert_eq!(200, response.status().as_u16());
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!("captured", body["hold"]["status"]);
+        assert_eq!("45.50", body["hold"]["captured_amount"]);
+        assert_eq!("54.50", body["actual_balance"]);
+        assert_eq!(hold_id.to_string(), body["transaction_id"]);
+
+        let account = ars_account(&app, client_id).await;
+        assert_eq!("54.50", account["balance"]);
+        assert_eq!("54.50", account["available"]);
+        assert_eq!("0", account["held"]);
+
+        let response = app.post_capture(hold_id, serde_json::json!({})).await;
+        assert_eq!(409, response.status().as_u16());
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!("hold_not_active", body["error"]["code"]);
+        assert_eq!("captured", body["error"]["details"]["status"]);
+    }
+}
+
+#[tokio::test]
+async fn a_voided_hold_releases_all_the_money() {
+    let app = spawn_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+    assert_eq!(
+        200,
+        app.post_credit(client_id, "10").await.status().as_u16()
+    );
+    let hold_id = app.place_hold(client_id, "10").await;
+    assert_eq!(400, app.post_debit(client_id, "1").await.status().as_u16());
+
+    let response = app.post_void(hold_id).await;
+    assert_eq!(200, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!("voided", body["status"]);
+    assert_eq!(200, app.post_debit(client_id, "10").await.status().as_u16());
+    assert_eq!(409, app.post_void(hold_id).await.status().as_u16());
+    // nothing was debited by the hold
+    let body: serde_json::Value = app
+        .get_transactions(client_id, &[])
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(2, body["transactions"].as_array().unwrap().len());
+}
+
+#[tokio::test]
+async fn the_holds_expire_after_their_ttl() {
+    let mut configuration = test_configuration();
+    configuration.holds.ttl_seconds = 1;
+    configuration.holds.expiration_interval_seconds = 1;
+    let app = spawn_app_with_configuration(TestUser::generate(), configuration).await;
+    let client_id = app.create_client().await;
+    assert_eq!(
+        200,
+        app.post_credit(client_id, "10").await.status().as_u16()
+    );
+    let hold_id = app.place_hold(client_id, "8").await;
+    assert_eq!("2", ars_account(&app, client_id).await["available"]);
+
+    tokio::time::sleep(std::time::Duration::from_millis(2500)).await;
+
+    let body: serde_json::Value = app.get_hold(hold_id).await.json().await.unwrap();
+    assert_eq!("expired", body["status"]);
+    assert_eq!(body["expires_at"], body["closed_at"]);
+    assert_eq!("10", ars_account(&app, client_id).await["available"]);
+    assert_eq!(
+        409,
+        app.post_capture(hold_id, serde_json::json!({}))
+            .await
+            .status()
+            .as_u16()
+    );
+    assert_eq!(200, app.post_debit(client_id, "10").await.status().as_u16());
+}
+
+#[tokio::test]
+async fn the_holds_of_a_client_can_be_listed() {
+    let app = spawn_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+    assert_eq!(
+        200,
+        app.post_credit(client_id, "10").await.status().as_u16()
+    );
+    let first = app.place_hold(client_id, "1").await;
+    let second = app.place_hold(client_id, "2").await;
+    assert_eq!(200, app.post_void(first).await.status().as_u16());
+
+    let body: serde_json::Value = app.get_holds(client_id, &[]).await.json().await.unwrap();
+    let ids = body["holds"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|hold| hold["id"].as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(vec![first.to_string(), second.to_string()], ids);
+
+    let body: serde_json::Value = app
+        .get_holds(client_id, &[("status", "active")])
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(1, body["holds"].as_array().unwrap().len());
+    assert_eq!(second.to_string(), body["holds"][0]["id"]);
+    assert_eq!("2", body["holds"][0]["amount"]);
+}
+
+#[tokio::test]
+async fn invalid_holds_are_rejected() {
+    let app = spawn_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+    assert_eq!(
+        200,
+        app.post_credit(client_id, "10").await.status().as_u16()
+    );
+
+    for amount in ["0", "-1", "0.001"] {
+        let response = app.post_hold(client_id, amount).await;
+        assert_eq!(400, response.status().as_u16());
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!("invalid_amount", body["error"]["code"], "{amount}");
+    }
+    let hold_id = app.place_hold(client_id, "5").await;
+    let response = app
+        .post_capture(hold_id, serde_json::json!({"amount": "5.01"}))
+        .await;
+    assert_eq!(400, response.status().as_u16());
+
+    let unknown = uuid::Uuid::new_v4();
+    let response = app.get_hold(unknown).await;
+    assert_eq!(404, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!("unknown_hold", body["error"]["code"]);
+    assert_eq!(404, app.post_void(unknown).await.status().as_u16());
+    assert_eq!(404, app.post_hold(unknown, "1").await.status().as_u16());
+}
+
+#[tokio::test]
+async fn the_held_money_is_not_settled() {
+    let app = spawn_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+    assert_eq!(
+        200,
+        app.post_credit(client_id, "10").await.status().as_u16()
+    );
+    let hold_id = app.place_hold(client_id, "4").await;
+
+    assert_eq!(200, app.post_store_balances().await.status().as_u16());
+
+    let account = ars_account(&app, client_id).await;
+    assert_eq!("4", account["balance"]);
+    assert_eq!("0", account["available"]);
+    assert_eq!(
+        200,
+        app.post_capture(hold_id, serde_json::json!({}))
+            .await
+            .status()
+            .as_u16()
+    );
+    assert_eq!("0", ars_account(&app, client_id).await["balance"]);
+}
@@ -0,0 +1,237 @@
+use mini_payment::sqlite_database::DATABASE_FILE;
+use uuid::Uuid;
+
+use crate::helpers::{
+    TestApp, TestUser, spawn_app, spawn_app_with_configuration, spawn_sqlite_app,
+};
+
+/// post the same request twice with `idempotency_key`, check that the second one is a replay of
+/// the first and return the body of the response
+async fn post_twice(
+    app: &TestApp,
+    path: &str,
+    body: Option<serde_json::Value>,
+    idempotency_key: &str,
+) -> serde_json::Value {
+    let first = app.post_with_key(path, body.clone(), idempotency_key).await;
+    assert_eq!(200, first.status().as_u16(), "{}", path);
+    let first: serde_json::Value = first.json().await.unwrap();
+    let second = app.post_with_key(path, body, idempotency_key).await;
+    assert_eq!(200, second.status().as_u16(), "{}", path);
+    assert_eq!(
+        Some("true"),
+        second
+            .headers()
+            .get("Idempotent-Replayed")
+            .and_then(|v| v.to_str().ok()),
+        "{}",
+        path
+    );
+    let second: serde_json::Value = second.json().await.unwrap();
+    assert_eq!(first, second, "{}", path);
+    first
+}
+
+fn id(body: &serde_json::Value, field: &str) -> Uuid {
+    body[field].as_str().unwrap().parse().unwrap()
+}
+
+#[tokio::test]
+async fn a_retried_credit_is_applied_only_once() {
+    let app = spawn_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+
+    let first = app.post_credit_with_key(client_id, "10", "key-1").await;
+    assert_eq!(200, first.status().as_u16());
+    let first: serde_json::Value = first.json().await.unwrap();
+    let second = app.post_credit_with_key(client_id, "10", "key-1").await;
+    assert_eq!(200, second.status().as_u16());
+    assert_eq!(
+        Some("true"),
+        second
+            .headers()
+            .get("Idempotent-Replayed")
+            .and_then(|v| v.to_str().ok())
+    );
+    let second: serde_json::Value = second.json().await.unwrap();
+
+    assert_eq!(first, second);
+    let body: serde_json::Value = app.get_balance(client_id).await.json().await.unwrap();
+    assert_eq!("10", body["balances"]["ARS"]);
+}
+
+#[tokio::test]
+async fn reusing_a_key_with_a_different_body_is_a_conflict() {
+    let app = spawn_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+
+    app.post_credit_with_key(client_id, "10", "key-1").await;
+    let response = app.post_credit_with_key(client_id, "20", "key-1").await;
+
+    assert_eq!(409, response.status().as_u16());
+    let body: serde_json::Value = app.get_balance(client_id).await.json().await.unwrap();
+    assert_eq!("10", body["balances"]["ARS"]);
+}
+
+#[tokio::test]
+async fn saved_responses_survive_a_restart() {
+    let app = spawn_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+    app.post_credit_with_key(client_id, "10", "key-1").await;
+
+    let restarted = spawn_app_with_configuration(TestUser::generate(), app.configuration).await;
+    let response = restarted
+        .post_credit_with_key(client_id, "10", "key-1")
+        .await;
+
+    assert_eq!(200, response.status().as_u16());
+    let body: serde_json::Value = restarted.get_balance(client_id).await.json().await.unwrap();
+    assert_eq!("10", body["balances"]["ARS"]);
+}
+
+#[tokio::test]
+async fn an_invalid_key_is_rejected() {
+    let app = spawn_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+
+    let response = app.post_credit_with_key(client_id, "10", "").await;
+
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn every_post_can_be_retried_with_the_same_key() {
+    for app in [
+        spawn_app(TestUser::generate()).await,
+        spawn_sqlite_app(TestUser::generate()).await,
+    ] {
+        let user = &app.test_user;
+        let body = post_twice(
+            &app,
+            "/new_client",
+            Some(serde_json::json!({
+                "client_name": user.client_name,
+                "birth_date": user.bird_date,
+                "document_number": user.document_number,
+                "country": user.country,
+            })),
+            "new-client",
+        )
+        .await;
+        let client_id = id(&body, "client_id");
+        post_twice(
+            &app,
+            &format!("/clients/{}/kyc", client_id),
+            Some(serde_json::json!({"status": "verified"})),
+            "kyc",
+        )
+        .await;
+        post_twice(
+            &app,
+            "/new_account",
+            Some(serde_json::json!({"client_id": client_id, "currency": "USD"})),
+            "new-account",
+        )
+        .await;
+        for (path, amount) in [
+            ("/new_credit_transaction", "3000"),
+            ("/new_debit_transaction", "100"),
+        ] {
+            post_twice(
+                &app,
+                path,
+                Some(serde_json::json!({
+                    "client_id": client_id,
+                    "currency": "ARS",
+                    "credit_amount": amount,
+                })),
+                path,
+            )
+            .await;
+        }
+        let to = app.create_client_from(&TestUser::other()).await;
+        app.post_new_account(to, "ARS").await;
+        post_twice(
+            &app,
+            "/transfer",
+            Some(serde_json::json!({
+                "from_client_id": client_id,
+                "to_client_id": to,
+                "currency": "ARS",
+                "amount": "150",
+            })),
+            "transfer",
+        )
+        .await;
+        post_twice(
+            &app,
+            "/convert",
+            Some(serde_json::json!({
+                "client_id": client_id,
+                "from_currency": "ARS",
+                "to_currency": "USD",
+                "amount": "1250",
+            })),
+            "convert",
+        )
+        .await;
+        let hold = Some(serde_json::json!({"currency": "ARS", "amount": "100"}));
+        let path = format!("/clients/{}/holds", client_id);
+        let captured = id(&post_twice(&app, &path, hold.clone(), "hold-1").await, "id");
+        let voided = id(&post_twice(&app, &path, hold, "hold-2").await, "id");
+        post_twice(
+            &app,
+            &format!("/holds/{}/capture", captured),
+            Some(serde_json::json!({"amount": "40"})),
+            "capture",
+        )
+        .await;
+        post_twice(&app, &format!("/holds/{}/void", voided), None, "void").await;
+        // every change was made only once
+        let body: serde_json::Value = app.get_balance(client_id).await.json().await.unwrap();
+        assert_eq!("1460", body["balances"]["ARS"]);
+        assert_eq!("1.00", body["balances"]["USD"]);
+        let body: serde_json::Value = app.get_balance(to).await.json().await.unwrap();
+        assert_eq!("150", body["balances"]["ARS"]);
+        post_twice(&app, "/store_balances", None, "store-balances").await;
+        let closed = app
+            .create_client_from(&TestUser {
+                client_name: "Ana Gomez".into(),
+                bird_date: "1975-05-06".into(),
+                document_number: "20333444".into(),
+                country: "Argentina".into(),
+            })
+            .await;
+        post_twice(&app, &format!("/clients/{}/close", closed), None, "close").await;
+    }
+}
+
+#[tokio::test]
+async fn a_credit_is_not_applied_when_its_response_can_not_be_saved() {
+    let app = spawn_sqlite_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+    let connection = rusqlite::Connection::open(
+        std::path::Path::new(&app.configuration.local_database.path).join(DATABASE_FILE),
+    )
+    .unwrap();
+    connection
+        .execute_batch(
+            "CREATE TRIGGER no_space BEFORE INSERT ON idempotency_keys \
+             BEGIN SELECT RAISE(FAIL, 'database or disk is full'); END;",
+        )
+        .unwrap();
+
+    let response = app.post_credit_with_key(client_id, "10", "key-1").await;
+    assert_eq!(500, response.status().as_u16());
+    let body: serde_json::Value = app.get_balance(client_id).await.json().await.unwrap();
+    assert_eq!("0", body["balances"]["ARS"]);
+
+    connection.execute_batch("DROP TRIGGER no_space;").unwrap();
+    let response = app.post_credit_with_key(client_id, "10", "key-1").await;
+    assert_eq!(200, response.status().as_u16());
+    let response = app.post_credit_with_key(client_id, "10", "key-1").await;
+    assert_eq!(200, response.status().as_u16());
+    assert!(response.headers().contains_key("Idempotent-Replayed"));
+    let body: serde_json::Value = app.get_balance(client_id).await.json().await.unwrap();
+    assert_eq!("10", body["balances"]["ARS"]);
+}
@@ -0,0 +1,132 @@
+use crate::helpers::{TestApp, TestUser, spawn_app, spawn_sqlite_app};
+use uuid::Uuid;
+
+/// the clients of the tests, with a balance in the currency of their country
+async fn create_clients(app: &TestApp) -> Vec<Uuid> {
+    let mut ids = Vec::new();
+    for (name, document, country) in [
+        ("Carla", "30000001", "Argentina"),
+        ("ana", "30000002", "Argentina"),
+        ("Bruno", "30000003", "Argentina"),
+        ("Dario", "3.011.122-2", "Chile"),
+    ] {
+        let user = TestUser {
+            client_name: name.into(),
+            bird_date: "1990-01-02".into(),
+            document_number: document.into(),
+            country: country.into(),
+        };
+        ids.push(app.create_client_from(&user).await);
+    }
+    ids
+}
+
+async fn names(response: reqwest::Response) -> (Vec<String>, Option<String>) {
+    assert_eq!(200, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    let names = body["clients"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|client| client["client_name"].as_str().unwrap().to_string())
+        .collect();
+    (names, body["next_cursor"].as_str().map(str::to_string))
+}
+
+#[tokio::test]
+async fn the_clients_are_listed_by_pages() {
+    for app in [
+        spawn_app(TestUser::generate()).await,
+        spawn_sqlite_app(TestUser::generate()).await,
+    ] {
+        create_clients(&app).await;
+
+        let (page, cursor) = names(app.get_clients(&[("limit", "3")]).await).await;
+        assert_eq!(vec!["ana", "Bruno", "Carla"], page);
+        let cursor = cursor.expect("there is a second page");
+
+        // a client created in the middle of the listing that goes in the first page
+        let mut late = TestUser::generate();
+        late.client_name = "Aaron".into();
+        app.create_client_from(&late).await;
+
+        let (page, cursor) = names(
+            app.get_clients(&[("limit", "3"), ("cursor", &cursor)])
+                .await,
+        )
+        .await;
+        assert_eq!(vec!["Dario"], page);
+        assert!(cursor.is_none());
+    }
+}
+
+#[tokio::test]
+async fn the_clients_can_be_filtered_and_sorted() {
+    for app in [
+        spawn_app(TestUser::generate()).await,
+        spawn_sqlite_app(TestUser::generate()).await,
+    ] {
+        let ids = create_clients(&app).await;
+        for (id, amount) in ids.iter().zip(["5", "20", "1"]) {
+            assert_eq!(200, app.post_credit(*id, amount).await.status().as_u16());
+        }
+        assert_eq!(200, app.post_close_client(ids[3]).await.status().as_u16());
+
+        let (page, _) = names(app.get_clients(&[("country", "CL")]).await).await;
+        assert_eq!(vec!["Dario"], page);
+
+        let (page, _) = names(
+            app.get_clients(&[("currency", "ARS"), ("sort", "balance"), ("order", "desc")])
+                .await,
+        )
+        .await;
+        assert_eq!(vec!["ana", "Carla", "Bruno"], page);
+
+        let query = [
+            ("currency", "ARS"),
+            ("min_balance", "2"),
+            ("max_balance", "10"),
+        ];
+        let (page, _) = names(app.get_clients(&query).await).await;
+        assert_eq!(vec!["Carla"], page);
+
+        let (page, _) = names(app.get_clients(&[("status", "closed")]).await).await;
+        assert_eq!(vec!["Dario"], page);
+
+        let today = chrono::Utc::now().date_naive().to_string();
+        let query = [("status", "open"), ("created_from", &today)];
+        let (page, _) = names(app.get_clients(&query).await).await;
+        assert_eq!(3, page.len());
+        let query = [("created_to", "2020-01-01")];
+        let (page, _) = names(app.get_clients(&query).await).await;
+        assert!(page.is_empty());
+    }
+}
+
+#[tokio::test]
+async fn invalid_listings_are_rejected() {
+    let app = spawn_app(TestUser::generate()).await;
+
+    for (query, code) in [
+        (vec![("sort", "balance")], "currency_required"),
+        (vec![("cursor", "not-a-cursor")], "invalid_cursor"),
+        (vec![("country", "Narnia")], "invalid_country"),
+        (vec![("sort", "age")], "invalid_query"),
+        (vec![("page", "2")], "invalid_query"),
+    ] {
+        let response = app.get_clients(&query).await;
+        assert_eq!(400, response.status().as_u16());
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(code, body["error"]["code"], "{query:?}");
+    }
+
+    // the cursor of other order
+    create_clients(&app).await;
+    let (_, cursor) = names(app.get_clients(&[("limit", "1")]).await).await;
+    let query = [
+        ("limit", "1"),
+        ("order", "desc"),
+        ("cursor", &cursor.unwrap()),
+    ];
+    assert_eq!(400, app.get_clients(&query).await.status().as_u16());
+}
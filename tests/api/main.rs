@@ -1 +1,14 @@
+mod balance;
+mod clients;
+mod convert;
+mod errors;
 mod helpers;
+mod holds;
+mod idempotency;
+mod listing;
+mod onboarding;
+mod persistence;
+mod profile;
+mod settlement;
+mod statement;
+mod transfer;
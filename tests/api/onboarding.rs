@@ -0,0 +1,92 @@
+use crate::helpers::{TestUser, spawn_app_with_configuration, test_configuration};
+use chrono::{Datelike, Days, Utc};
+use mini_payment::onboarding::KycStatus;
+
+#[tokio::test]
+async fn the_new_clients_can_not_move_money_until_they_are_verified() {
+    for backend in [
+        mini_payment::configuration::StorageBackend::Log,
+        mini_payment::configuration::StorageBackend::Sqlite,
+    ] {
+        let mut configuration = test_configuration();
+        configuration.local_database.backend = backend;
+        configuration.onboarding.kyc_status_of_new_clients = KycStatus::Pending;
+        let app = spawn_app_with_configuration(TestUser::generate(), configuration).await;
+
+        let response = app.post_new_client().await;
+        assert_eq!(200, response.status().as_u16());
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!("pending", body["kyc_status"]);
+        let client_id = body["client_id"].as_str().unwrap().parse().unwrap();
+
+        assert_eq!(
+            403,
+            app.post_credit(client_id, "10").await.status().as_u16()
+        );
+
+        let response = app.post_kyc_status(client_id, "verified").await;
+        assert_eq!(200, response.status().as_u16());
+        assert_eq!(
+            200,
+            app.post_credit(client_id, "10").await.status().as_u16()
+        );
+
+        assert_eq!(
+            200,
+            app.post_kyc_status(client_id, "rejected")
+                .await
+                .status()
+                .as_u16()
+        );
+        assert_eq!(403, app.post_debit(client_id, "1").await.status().as_u16());
+    }
+}
+
+#[tokio::test]
+async fn the_kyc_status_must_be_known() {
+    let app = spawn_app_with_configuration(TestUser::generate(), test_configuration()).await;
+    let client_id = app.create_client().await;
+
+    assert_eq!(
+        400,
+        app.post_kyc_status(client_id, "approved")
+            .await
+            .status()
+            .as_u16()
+    );
+    assert_eq!(
+        404,
+        app.post_kyc_status(uuid::Uuid::new_v4(), "verified")
+            .await
+            .status()
+            .as_u16()
+    );
+}
+
+#[tokio::test]
+async fn minors_and_implausible_birth_dates_are_rejected() {
+    let mut configuration = test_configuration();
+    configuration
+        .onboarding
+        .minimum_age_by_country
+        .insert("CL".into(), 21);
+    let app = spawn_app_with_configuration(TestUser::generate(), configuration).await;
+    let today = Utc::now().date_naive();
+
+    let mut user = TestUser::generate();
+    user.bird_date = (today - Days::new(10 * 365)).to_string();
+    assert_eq!(422, app.post_client(&user).await.status().as_u16());
+
+    user.bird_date = today.succ_opt().unwrap().to_string();
+    assert_eq!(400, app.post_client(&user).await.status().as_u16());
+
+    user.bird_date = "1850-01-01".into();
+    assert_eq!(400, app.post_client(&user).await.status().as_u16());
+
+    // 19 years old is an adult in Argentina but not for our clients of Chile
+    let mut chilean = TestUser::other();
+    chilean.bird_date = (today.year() - 19).to_string() + "-01-01";
+    assert_eq!(422, app.post_client(&chilean).await.status().as_u16());
+    user.bird_date = chilean.bird_date;
+    assert_eq!(200, app.post_client(&user).await.status().as_u16());
+}
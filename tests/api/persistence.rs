@@ -0,0 +1,72 @@
+use crate::helpers::{TestUser, spawn_app, spawn_app_with_configuration, spawn_sqlite_app};
+
+#[tokio::test]
+async fn balances_survive_a_restart() {
+    let app = spawn_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+    assert_eq!(
+        200,
+        app.post_credit(client_id, "100.50").await.status().as_u16()
+    );
+    assert_eq!(200, app.post_debit(client_id, "30").await.status().as_u16());
+
+    let restarted = spawn_app_with_configuration(TestUser::generate(), app.configuration).await;
+
+    let response = restarted.get_balance(client_id).await;
+    assert_eq!(200, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!("70.50", body["balances"]["ARS"]);
+}
+
+#[tokio::test]
+async fn a_restarted_app_keeps_rejecting_duplicated_documents() {
+    let app = spawn_app(TestUser::generate()).await;
+    app.create_client().await;
+
+    let restarted = spawn_app_with_configuration(TestUser::generate(), app.configuration).await;
+
+    assert!(restarted.post_new_client().await.status().is_client_error());
+}
+
+#[tokio::test]
+async fn balances_survive_a_restart_with_sqlite() {
+    let app = spawn_sqlite_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+    assert_eq!(
+        200,
+        app.post_credit(client_id, "100.50").await.status().as_u16()
+    );
+    assert_eq!(200, app.post_debit(client_id, "30").await.status().as_u16());
+    assert!(
+        app.post_debit(client_id, "1000")
+            .await
+            .status()
+            .is_client_error()
+    );
+
+    let restarted = spawn_app_with_configuration(TestUser::generate(), app.configuration).await;
+
+    let response = restarted.get_balance(client_id).await;
+    assert_eq!(200, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!("70.50", body["balances"]["ARS"]);
+    assert!(restarted.post_new_client().await.status().is_client_error());
+}
+
+#[tokio::test]
+async fn settlements_work_with_sqlite() {
+    let app = spawn_sqlite_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+    app.post_credit(client_id, "12.30").await;
+
+    let response = app.post_store_balances().await;
+    assert_eq!(200, response.status().as_u16());
+    let summary: serde_json::Value = response.json().await.unwrap();
+
+    let response = app.get_settlement(summary["id"].as_str().unwrap()).await;
+    assert_eq!(200, response.status().as_u16());
+    let content = response.text().await.unwrap();
+    assert!(content.contains(&format!("BAL|{client_id}|ARS|12.30")));
+    let body: serde_json::Value = app.get_balance(client_id).await.json().await.unwrap();
+    assert_eq!("0.00", body["balances"]["ARS"]);
+}
@@ -0,0 +1,182 @@
+use crate::helpers::{TestUser, spawn_app, spawn_app_with_configuration, spawn_sqlite_app};
+use serde_json::json;
+
+#[tokio::test]
+async fn the_profile_of_a_client_can_be_read() {
+    for app in [
+        spawn_app(TestUser::generate()).await,
+        spawn_sqlite_app(TestUser::generate()).await,
+    ] {
+        let client_id = app.create_client().await;
+
+        let response = app.get_client(client_id).await;
+        assert_eq!(200, response.status().as_u16());
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(client_id.to_string(), body["client_id"]);
+        assert_eq!("Martin Noblia", body["client_name"]);
+        assert_eq!("1982-09-27", body["birth_date"]);
+        assert_eq!("dni", body["document_type"]);
+        assert_eq!("29653164", body["document_number"]);
+        assert_eq!("Argentina", body["country"]);
+        assert_eq!("verified", body["kyc_status"]);
+        assert!(body["closed_at"].is_null());
+
+        let response = app.get_client(uuid::Uuid::new_v4()).await;
+        assert_eq!(404, response.status().as_u16());
+    }
+}
+
+#[tokio::test]
+async fn unknown_clients_are_not_found() {
+    for app in [
+        spawn_app(TestUser::generate()).await,
+        spawn_sqlite_app(TestUser::generate()).await,
+    ] {
+        let unknown = uuid::Uuid::new_v4();
+        for response in [
+            app.get_client(unknown).await,
+            app.patch_client(unknown, json!({"client_name": "Martin"}))
+                .await,
+            app.post_close_client(unknown).await,
+        ] {
+            assert_eq!(404, response.status().as_u16());
+            let body: serde_json::Value = response.json().await.unwrap();
+            assert_eq!("unknown_client", body["error"]["code"]);
+        }
+    }
+}
+
+#[tokio::test]
+async fn the_name_and_the_country_can_be_changed() {
+    for app in [
+        spawn_app(TestUser::generate()).await,
+        spawn_sqlite_app(TestUser::generate()).await,
+    ] {
+        let client_id = app.create_client().await;
+
+        let response = app
+            .patch_client(
+                client_id,
+                json!({"client_name": "Martin N", "country": "UY"}),
+            )
+            .await;
+        assert_eq!(200, response.status().as_u16());
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!("Martin N", body["client_name"]);
+        assert_eq!("Uruguay", body["country"]);
+        // the document is still the one issued by Argentina
+        assert_eq!("Argentina", body["issuing_country"]);
+
+        let body: serde_json::Value = app.get_client(client_id).await.json().await.unwrap();
+        assert_eq!("Martin N", body["client_name"]);
+        let body: serde_json::Value = app.get_balance(client_id).await.json().await.unwrap();
+        assert_eq!(json!({"ARS": "0", "UYU": "0"}), body["balances"]);
+        let response = app.get_client_by_document("Argentina", "29653164").await;
+        assert_eq!(200, response.status().as_u16());
+
+        // only the name
+        let response = app
+            .patch_client(client_id, json!({"client_name": "Martin"}))
+            .await;
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!("Martin", body["client_name"]);
+        assert_eq!("Uruguay", body["country"]);
+    }
+}
+
+#[tokio::test]
+async fn invalid_changes_of_the_profile_are_rejected() {
+    let app = spawn_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+
+    let response = app
+        .patch_client(
+            client_id,
+            json!({"client_name": "<Martin>", "country": "Narnia"}),
+        )
+        .await;
+    assert_eq!(400, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!("invalid_fields", body["error"]["code"]);
+    let fields = body["error"]["details"]["fields"].as_object().unwrap();
+    assert_eq!(
+        vec!["client_name", "country"],
+        fields.keys().collect::<Vec<_>>()
+    );
+
+    // the document can not be changed
+    let response = app
+        .patch_client(client_id, json!({"document_number": "30111222"}))
+        .await;
+    assert_eq!(400, response.status().as_u16());
+
+    let body: serde_json::Value = app.get_client(client_id).await.json().await.unwrap();
+    assert_eq!("Martin Noblia", body["client_name"]);
+    assert_eq!("Argentina", body["country"]);
+}
+
+#[tokio::test]
+async fn only_a_client_without_money_can_be_closed() {
+    for app in [
+        spawn_app(TestUser::generate()).await,
+        spawn_sqlite_app(TestUser::generate()).await,
+    ] {
+        let client_id = app.create_client().await;
+        assert_eq!(200, app.post_credit(client_id, "5").await.status().as_u16());
+
+        let response = app.post_close_client(client_id).await;
+        assert_eq!(409, response.status().as_u16());
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!("balance_not_zero", body["error"]["code"]);
+        assert_eq!("ARS", body["error"]["details"]["currency"]);
+
+        assert_eq!(200, app.post_debit(client_id, "5").await.status().as_u16());
+        let response = app.post_close_client(client_id).await;
+        assert_eq!(200, response.status().as_u16());
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert!(body["closed_at"].is_string());
+
+        // the client is still there but nothing can change
+        let body: serde_json::Value = app.get_client(client_id).await.json().await.unwrap();
+        assert!(body["closed_at"].is_string());
+        for response in [
+            app.post_credit(client_id, "1").await,
+            app.post_new_account(client_id, "USD").await,
+            app.patch_client(client_id, json!({"client_name": "Martin"}))
+                .await,
+            app.post_close_client(client_id).await,
+            app.post_kyc_status(client_id, "rejected").await,
+        ] {
+            assert_eq!(409, response.status().as_u16());
+            let body: serde_json::Value = response.json().await.unwrap();
+            assert_eq!("client_closed", body["error"]["code"]);
+        }
+        let body: serde_json::Value = app.get_client(client_id).await.json().await.unwrap();
+        assert_eq!("verified", body["kyc_status"]);
+        // a closed client still has his document
+        assert_eq!(409, app.post_new_client().await.status().as_u16());
+    }
+}
+
+#[tokio::test]
+async fn the_changes_of_the_profile_survive_a_restart() {
+    let app = spawn_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+    let closed_id = app.create_client_from(&TestUser::other()).await;
+    let response = app
+        .patch_client(client_id, json!({"country": "Chile"}))
+        .await;
+    assert_eq!(200, response.status().as_u16());
+    assert_eq!(
+        200,
+        app.post_close_client(closed_id).await.status().as_u16()
+    );
+
+    let restarted = spawn_app_with_configuration(TestUser::generate(), app.configuration).await;
+
+    let body: serde_json::Value = restarted.get_client(client_id).await.json().await.unwrap();
+    assert_eq!("Chile", body["country"]);
+    assert_eq!("Argentina", body["issuing_country"]);
+    let body: serde_json::Value = restarted.get_client(closed_id).await.json().await.unwrap();
+    assert!(body["closed_at"].is_string());
+}
@@ -0,0 +1,88 @@
+use crate::helpers::{TestUser, spawn_app, spawn_app_with_configuration};
+use mini_payment::currency::Currency;
+use mini_payment::settlement::SettlementFile;
+use rust_decimal::dec;
+
+#[tokio::test]
+async fn store_balances_writes_a_valid_settlement_file() {
+    let app = spawn_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+    app.post_credit(client_id, "10.50").await;
+
+    let response = app.post_store_balances().await;
+
+    assert_eq!(200, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    let file_name = body["file_name"].as_str().unwrap();
+    let path = std::path::Path::new(&app.configuration.settlement.output_directory).join(file_name);
+    let content = std::fs::read_to_string(path).expect("the settlement file was not written");
+    let settlement = SettlementFile::parse(&content).expect("invalid settlement file");
+    assert_eq!(settlement.sequence, 1);
+    assert_eq!(settlement.records.len(), 1);
+    assert_eq!(settlement.records[0].client_id, client_id);
+    assert_eq!(settlement.records[0].currency, Currency::Ars);
+    assert_eq!(settlement.records[0].amount, dec!(10.50));
+
+    let body: serde_json::Value = app.get_balance(client_id).await.json().await.unwrap();
+    assert_eq!("0.00", body["balances"]["ARS"]);
+}
+
+#[tokio::test]
+async fn the_sequence_number_survives_a_restart() {
+    let app = spawn_app(TestUser::generate()).await;
+    app.create_client().await;
+    app.post_store_balances().await;
+
+    let restarted = spawn_app_with_configuration(TestUser::generate(), app.configuration).await;
+    let body: serde_json::Value = restarted.post_store_balances().await.json().await.unwrap();
+
+    assert_eq!(2, body["sequence"]);
+    assert!(body["file_name"].as_str().unwrap().ends_with("_000002.DAT"));
+}
+
+#[tokio::test]
+async fn past_settlements_are_listed() {
+    let app = spawn_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+    app.post_credit(client_id, "10.50").await;
+    app.post_store_balances().await;
+    app.post_credit(client_id, "1").await;
+    app.post_store_balances().await;
+
+    let response = app.get_settlements().await;
+
+    assert_eq!(200, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    let settlements = body.as_array().unwrap();
+    assert_eq!(2, settlements.len());
+    assert_eq!(1, settlements[0]["sequence"]);
+    assert_eq!(1, settlements[0]["client_count"]);
+    assert_eq!("10.50", settlements[0]["totals"]["ARS"]["total"]);
+    assert_eq!("1", settlements[1]["totals"]["ARS"]["total"]);
+    assert!(settlements[1]["created_at"].is_string());
+}
+
+#[tokio::test]
+async fn a_settlement_file_can_be_downloaded() {
+    let app = spawn_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+    app.post_credit(client_id, "10.50").await;
+    let body: serde_json::Value = app.post_store_balances().await.json().await.unwrap();
+
+    let response = app.get_settlement(body["id"].as_str().unwrap()).await;
+
+    assert_eq!(200, response.status().as_u16());
+    let content = response.text().await.unwrap();
+    let settlement = SettlementFile::parse(&content).expect("invalid settlement file");
+    assert_eq!(settlement.file_name(), body["file_name"]);
+    assert_eq!(settlement.records[0].amount, dec!(10.50));
+}
+
+#[tokio::test]
+async fn an_unknown_settlement_is_not_found() {
+    let app = spawn_app(TestUser::generate()).await;
+
+    let response = app.get_settlement(&uuid::Uuid::new_v4().to_string()).await;
+
+    assert_eq!(404, response.status().as_u16());
+}
@@ -0,0 +1,137 @@
+use crate::helpers::{TestUser, spawn_app, spawn_sqlite_app};
+
+#[tokio::test]
+async fn the_statement_has_the_running_balance_of_every_movement() {
+    for app in [
+        spawn_app(TestUser::generate()).await,
+        spawn_sqlite_app(TestUser::generate()).await,
+    ] {
+        let client_id = app.create_client().await;
+        for amount in ["10", "20", "30"] {
+            assert_eq!(
+                200,
+                app.post_credit(client_id, amount).await.status().as_u16()
+            );
+        }
+        assert_eq!(200, app.post_debit(client_id, "15").await.status().as_u16());
+
+        let response = app.get_transactions(client_id, &[("limit", "3")]).await;
+        assert_eq!(200, response.status().as_u16());
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(client_id.to_string(), body["client_id"]);
+        let transactions = body["transactions"].as_array().unwrap();
+        let balances = transactions
+            .iter()
+            .map(|t| t["balance"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["10", "30", "60"], balances);
+        let cursor = body["next_cursor"].as_str().unwrap().to_string();
+
+        let query = [("limit", "3"), ("cursor", cursor.as_str())];
+        let body: serde_json::Value = app
+            .get_transactions(client_id, &query)
+            .await
+            .json()
+            .await
+            .unwrap();
+        let transactions = body["transactions"].as_array().unwrap();
+        assert_eq!(1, transactions.len());
+        assert_eq!("debit", transactions[0]["direction"]);
+        assert_eq!("45", transactions[0]["balance"]);
+        assert!(body["next_cursor"].is_null());
+    }
+}
+
+#[tokio::test]
+async fn the_statement_can_be_filtered() {
+    let app = spawn_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+    for amount in ["10", "20", "30"] {
+        assert_eq!(
+            200,
+            app.post_credit(client_id, amount).await.status().as_u16()
+        );
+    }
+    assert_eq!(200, app.post_debit(client_id, "15").await.status().as_u16());
+    let today = chrono::Utc::now().date_naive();
+    let yesterday = today.pred_opt().unwrap().to_string();
+    let today = today.to_string();
+
+    for (query, expected) in [
+        (vec![("direction", "credit")], 3),
+        (vec![("direction", "debit")], 1),
+        (vec![("min_amount", "15"), ("max_amount", "20")], 2),
+        (vec![("from", today.as_str()), ("to", today.as_str())], 4),
+        (vec![("to", yesterday.as_str())], 0),
+        (vec![("currency", "USD")], 0),
+    ] {
+        let body: serde_json::Value = app
+            .get_transactions(client_id, &query)
+            .await
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(
+            expected,
+            body["transactions"].as_array().unwrap().len(),
+            "{query:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn the_statement_can_be_exported_to_csv() {
+    let app = spawn_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+    assert_eq!(
+        200,
+        app.post_credit(client_id, "10.50").await.status().as_u16()
+    );
+    assert_eq!(
+        200,
+        app.post_debit(client_id, "0.50").await.status().as_u16()
+    );
+
+    let response = app
+        .get_transactions(client_id, &[("format", "csv"), ("limit", "1")])
+        .await;
+    assert_eq!(200, response.status().as_u16());
+    assert_eq!(
+        "text/csv; charset=utf-8",
+        response.headers()["content-type"]
+    );
+    assert!(
+        response.headers()["content-disposition"]
+            .to_str()
+            .unwrap()
+            .contains(&format!("{client_id}_transactions.csv"))
+    );
+    let csv = response.text().await.unwrap();
+    let lines = csv.lines().collect::<Vec<_>>();
+    // the csv has no pages
+    assert_eq!(3, lines.len());
+    assert!(lines[0].starts_with("timestamp,transaction_id,entry_id"));
+    assert!(lines[1].ends_with(",ARS,credit,10.50,10.50,"));
+    assert!(lines[2].ends_with(",ARS,debit,0.50,10.00,"));
+}
+
+#[tokio::test]
+async fn invalid_statements_are_rejected() {
+    let app = spawn_app(TestUser::generate()).await;
+    let client_id = app.create_client().await;
+
+    let unknown = uuid::Uuid::new_v4().to_string();
+    for (query, code) in [
+        (vec![("cursor", unknown.as_str())], "invalid_cursor"),
+        (vec![("direction", "sideways")], "invalid_query"),
+        (vec![("format", "xml")], "invalid_query"),
+    ] {
+        let response = app.get_transactions(client_id, &query).await;
+        assert_eq!(400, response.status().as_u16());
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(code, body["error"]["code"], "{query:?}");
+    }
+
+    let response = app.get_transactions(uuid::Uuid::new_v4(), &[]).await;
+    assert_eq!(404, response.status().as_u16());
+}
@@ -0,0 +1,51 @@
+use crate::helpers::{TestUser, spawn_app};
+
+#[tokio::test]
+async fn transfer_returns_both_balances() {
+    let app = spawn_app(TestUser::generate()).await;
+    let from = app.create_client().await;
+    let to = app.create_client_from(&TestUser::other()).await;
+    app.post_new_account(to, "ARS").await;
+    app.post_credit(from, "10").await;
+
+    let response = app.post_transfer(from, to, "2.5").await;
+
+    assert_eq!(200, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!("7.5", body["from_balance"]);
+    assert_eq!("2.5", body["to_balance"]);
+}
+
+#[tokio::test]
+async fn a_failed_transfer_does_not_move_money() {
+    let app = spawn_app(TestUser::generate()).await;
+    let from = app.create_client().await;
+    let to = app.create_client_from(&TestUser::other()).await;
+    app.post_new_account(to, "ARS").await;
+    app.post_credit(from, "10").await;
+
+    for (from, to, amount) in [(from, to, "11"), (from, from, "1"), (from, to, "-1")] {
+        let response = app.post_transfer(from, to, amount).await;
+        assert_eq!(400, response.status().as_u16());
+    }
+
+    let body: serde_json::Value = app.get_balance(from).await.json().await.unwrap();
+    assert_eq!("10", body["balances"]["ARS"]);
+    let body: serde_json::Value = app.get_balance(to).await.json().await.unwrap();
+    assert_eq!("0", body["balances"]["ARS"]);
+}
+
+#[tokio::test]
+async fn a_transfer_to_a_client_without_an_account_in_the_currency_is_rejected() {
+    let app = spawn_app(TestUser::generate()).await;
+    let from = app.create_client().await;
+    // the other client is from Chile so he has only an account in CLP
+    let to = app.create_client_from(&TestUser::other()).await;
+    app.post_credit(from, "10").await;
+
+    let response = app.post_transfer(from, to, "1").await;
+
+    assert_eq!(400, response.status().as_u16());
+    let body: serde_json::Value = app.get_balance(to).await.json().await.unwrap();
+    assert_eq!(serde_json::json!({"CLP": "0"}), body["balances"]);
+}